@@ -1,3 +1,6 @@
 pub mod model;
 pub mod ports;
-pub mod service;
\ No newline at end of file
+pub mod service;
+// Checks shared by the tests of the adapters
+#[cfg(feature = "testkit")]
+pub mod testkit;
\ No newline at end of file
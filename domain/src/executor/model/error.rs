@@ -8,6 +8,10 @@ pub enum TaskError {
     ExecutionError {
         source: anyhow::Error
     },
+    #[error("Invalid task definition : {0}")]
+    ValidationError(String),
+    #[error("Permission denied : {0}")]
+    PermissionError(String),
     #[error("Unexpected error while processing the command")]
     UnexpectedError {
         source: Box<dyn std::error::Error>
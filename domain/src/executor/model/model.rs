@@ -1,4 +1,11 @@
 use im::HashMap;
+use std::cmp::Reverse;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use crate::executor::model::error::TaskError;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Task {
@@ -6,13 +13,376 @@ pub struct Task {
     pub name: Option<String>,
     pub command: String,
     pub env: Option<HashMap<String, String>>,
+    pub timeout: Option<Duration>,
+    pub labels: HashMap<String, String>,
+    // Absolute path of the directory where the command is run
+    pub working_dir: Option<PathBuf>,
+    // Payload written on the standard input of the command
+    pub stdin: Option<Vec<u8>>,
+    pub umask: Option<u32>,
+    pub limits: ResourceLimits,
+    // Run the command isolated in its own namespaces
+    pub sandboxed: bool,
+    // User and groups the command is run as, None keeps the ones of the executor
+    pub credentials: Option<Credentials>,
+    // Image the command is run in instead of the host
+    pub container: Option<Container>,
+    // Remote host the command is run on, None runs it where the executor is
+    pub host: Option<String>,
+    // WASI module run by an embedded runtime, the command gives its arguments
+    pub wasm: Option<WasmModule>,
+    // Name of the executor running the command, None lets the executor choose from the other settings
+    pub executor: Option<String>,
+    // Glob patterns of the files collected from the working directory once the command is completed
+    pub artifacts: Vec<String>,
+    pub hooks: TaskHooks,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub gid: u32,
+    // Supplementary groups
+    pub groups: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Container {
+    // Root filesystem of the unpacked image
+    pub rootfs: PathBuf,
+    pub mounts: Vec<Mount>,
+}
+
+// Host directory made visible in the container
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mount {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub read_only: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WasmModule {
+    pub source: WasmSource,
+    // Host directory preopened as / for the module
+    pub dir: Option<PathBuf>,
+    // Maximum number of instructions the module can execute
+    pub fuel: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WasmSource {
+    // Module file read when the task is executed
+    Path(PathBuf),
+    // Module content stored with the task
+    Blob(Vec<u8>),
+}
+
+// Shell commands run where tasc runs, before the command of the task and once it is completed
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct TaskHooks {
+    pub pre: Option<String>,
+    pub post: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+impl fmt::Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookStage::Pre => write!(f, "pre"),
+            HookStage::Post => write!(f, "post"),
+        }
+    }
+}
+
+impl FromStr for HookStage {
+    type Err = TaskError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "pre" => Ok(HookStage::Pre),
+            "post" => Ok(HookStage::Post),
+            _ => Err(TaskError::ValidationError(format!("{} is not a valid hook stage", source))),
+        }
+    }
+}
+
+// Hook which failed, it is recorded without changing the status of the task
+#[derive(Clone, Debug, PartialEq)]
+pub struct HookFailure {
+    pub stage: HookStage,
+    pub message: String,
+}
+
+// Limits applied to the process of a task, None means unlimited
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ResourceLimits {
+    pub cpu_time: Option<Duration>,
+    // Memory in bytes
+    pub memory: Option<u64>,
+    pub open_files: Option<u64>,
+    pub processes: Option<u64>,
+}
+
+// Command and env values can contain {{param}} placeholders filled when the template is instantiated
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskTemplate {
+    pub name: String,
+    pub command: String,
+    pub env: Option<HashMap<String, String>>,
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Clone, Debug)]
 pub enum TaskStatus {
     Scheduled,
-    Success(String),
-    Error(String),
+    Success(TaskOutput),
+    // Logs of the execution and exit code of the command if it has been run
+    Error(TaskOutput, Option<i32>),
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            TaskStatus::Scheduled => false,
+            TaskStatus::Success(_) | TaskStatus::Error(_, _) | TaskStatus::Cancelled => true,
+        }
+    }
+
+    pub fn outcome(&self) -> Option<TaskOutcome> {
+        match self {
+            TaskStatus::Scheduled => None,
+            TaskStatus::Success(_) => Some(TaskOutcome::Success),
+            TaskStatus::Error(_, _) => Some(TaskOutcome::Error),
+            TaskStatus::Cancelled => Some(TaskOutcome::Cancelled),
+        }
+    }
+
+    pub fn output(&self) -> Option<&TaskOutput> {
+        match self {
+            TaskStatus::Success(output) | TaskStatus::Error(output, _) => Some(output),
+            TaskStatus::Scheduled | TaskStatus::Cancelled => None,
+        }
+    }
+
+    // Events of the transition of a task to this status
+    pub fn events(&self) -> Vec<TaskEvent> {
+        let (output, success, exit_code) = match self {
+            TaskStatus::Scheduled => return vec![],
+            TaskStatus::Cancelled => return vec![TaskEvent::Cancelled],
+            TaskStatus::Success(output) => (output, true, Some(0)),
+            TaskStatus::Error(output, code) => (output, false, *code),
+        };
+        let mut events = vec![];
+        if !output.content.is_empty() {
+            events.push(TaskEvent::Output { size: output.content.len() as u64 });
+        }
+        events.push(TaskEvent::Completed { success, exit_code, truncated: output.truncated, log: output.log.clone() });
+        events
+    }
+
+    // Status of a task after its events, replayed from the first one, the content of its output is left empty
+    pub fn project<'a>(events: impl IntoIterator<Item = &'a TaskEvent>) -> TaskStatus {
+        events.into_iter().fold(TaskStatus::Scheduled, |status, event| match event {
+            TaskEvent::Submitted { .. } => TaskStatus::Scheduled,
+            TaskEvent::Started | TaskEvent::Output { .. } | TaskEvent::ArtifactsFailed { .. } => status,
+            TaskEvent::Completed { success, exit_code, truncated, log } => {
+                let output = TaskOutput { content: vec![], truncated: *truncated, log: log.clone() };
+                if *success { TaskStatus::Success(output) } else { TaskStatus::Error(output, *exit_code) }
+            }
+            TaskEvent::Cancelled => TaskStatus::Cancelled,
+        })
+    }
+}
+
+// Status of a completed task, without its output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskOutcome {
+    Success,
+    Error,
+    Cancelled,
+}
+
+impl fmt::Display for TaskOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskOutcome::Success => write!(f, "success"),
+            TaskOutcome::Error => write!(f, "error"),
+            TaskOutcome::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl FromStr for TaskOutcome {
+    type Err = TaskError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "success" => Ok(TaskOutcome::Success),
+            "error" => Ok(TaskOutcome::Error),
+            "cancelled" => Ok(TaskOutcome::Cancelled),
+            _ => Err(TaskError::ValidationError(format!("{} is not a valid task status, expected success, error or cancelled", source))),
+        }
+    }
+}
+
+// Completed task as listed by the storage for the retention policy
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletedTask {
+    pub id: String,
+    pub name: Option<String>,
+    pub outcome: TaskOutcome,
+    // None for the tasks completed before the storages recorded it
+    pub completed_at: Option<SystemTime>,
+}
+
+// Rules selecting the completed tasks to purge, the tasks still scheduled are never purged
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetentionPolicy {
+    // Tasks completed for longer are purged, the ones without completion time being older than any age
+    pub max_age: Option<Duration>,
+    // Number of completed tasks kept for each name, the older ones are purged
+    pub max_per_name: Option<usize>,
+    // Number of the last failed tasks kept whatever the other rules
+    pub keep_failures: Option<usize>,
+    // Outcomes of the tasks which can be purged, all of them when empty
+    pub outcomes: Vec<TaskOutcome>,
+}
+
+impl RetentionPolicy {
+    // Without age or count there is nothing to purge
+    pub fn is_empty(&self) -> bool {
+        self.max_age.is_none() && self.max_per_name.is_none()
+    }
+
+    // Tasks to purge among the completed ones, from the most recently completed
+    pub fn select(&self, mut tasks: Vec<CompletedTask>, now: SystemTime) -> Vec<CompletedTask> {
+        tasks.sort_by_key(|task| Reverse(task.completed_at));
+        let mut failures = 0;
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        tasks.into_iter()
+            .filter(|task| {
+                let kept_failure = task.outcome == TaskOutcome::Error && self.keep_failures.is_some_and(|keep| {
+                    failures += 1;
+                    failures <= keep
+                });
+                let too_many = match task.name {
+                    Some(ref name) => {
+                        let count = by_name.entry(name.clone()).or_insert(0);
+                        *count += 1;
+                        self.max_per_name.is_some_and(|max| *count > max)
+                    }
+                    None => false,
+                };
+                let too_old = self.max_age.is_some_and(|max_age| task.completed_at
+                    .is_none_or(|completed_at| now.duration_since(completed_at).is_ok_and(|age| age > max_age)));
+                let purgeable = self.outcomes.is_empty() || self.outcomes.contains(&task.outcome);
+                purgeable && (too_old || too_many) && !kept_failure
+            })
+            .collect()
+    }
+}
+
+// Everything stored about a task, moved from a storage to another by export and import
+#[derive(Clone, Debug)]
+pub struct ExportedTask {
+    pub task: Task,
+    pub status: TaskStatus,
+    pub completed_at: Option<SystemTime>,
+    pub artifacts: Vec<Artifact>,
+    pub hook_failures: Vec<HookFailure>,
+}
+
+// Transition of a task, appended to its event log which is never rewritten
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskEvent {
+    Submitted { command: String },
+    Started,
+    // Size of the output of the command, given before the completion, its content is only kept by the storage
+    Output { size: u64 },
+    Completed { success: bool, exit_code: Option<i32>, truncated: u64, log: Option<PathBuf> },
+    Cancelled,
+    // Collecting the artifacts failed once the task was completed, its status is unchanged
+    ArtifactsFailed { message: String },
+}
+
+impl fmt::Display for TaskEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskEvent::Submitted { command } => write!(f, "Submitted {}", command),
+            TaskEvent::Started => write!(f, "Started"),
+            TaskEvent::Output { size } => write!(f, "Output of {} bytes", size),
+            TaskEvent::Completed { success: true, .. } => write!(f, "Succeeded"),
+            TaskEvent::Completed { exit_code: Some(code), .. } => write!(f, "Failed with exit code {}", code),
+            TaskEvent::Completed { .. } => write!(f, "Failed"),
+            TaskEvent::Cancelled => write!(f, "Cancelled"),
+            TaskEvent::ArtifactsFailed { message } => write!(f, "Artifacts not collected : {}", message),
+        }
+    }
+}
+
+// Event as kept by the event store, with when and by whom it was appended
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedEvent {
+    pub task_id: String,
+    // Order of the event in the store
+    pub sequence: u64,
+    pub at: SystemTime,
+    // User and host of the tasc process
+    pub actor: String,
+    pub event: TaskEvent,
+}
+
+// File collected from the working directory of a task
+#[derive(Clone, Debug, PartialEq)]
+pub struct Artifact {
+    // Path relative to the working directory
+    pub path: PathBuf,
+    // SHA-256 of the content, its key in the artifact store
+    pub digest: String,
+    pub size: u64,
+}
+
+impl Artifact {
+    // Digests are paths of the artifact store, anything else than 64 lowercase hex characters could escape it
+    pub fn is_digest(digest: &str) -> bool {
+        digest.len() == 64 && digest.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+    }
+}
+
+// Output of a command, only its head and tail are kept when it exceeds the capture limit
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TaskOutput {
+    pub content: Vec<u8>,
+    // Number of bytes dropped from the middle of the content
+    pub truncated: u64,
+    // File holding the full output when it has been truncated
+    pub log: Option<PathBuf>,
+}
+
+impl From<String> for TaskOutput {
+    fn from(content: String) -> Self {
+        TaskOutput { content: content.into_bytes(), ..TaskOutput::default() }
+    }
+}
+
+impl From<&str> for TaskOutput {
+    fn from(content: &str) -> Self {
+        TaskOutput::from(content.to_string())
+    }
+}
+
+// Invalid UTF-8 sequences are displayed as replacement characters
+impl fmt::Display for TaskOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.content))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -29,4 +399,204 @@ impl From<&Task> for TaskId {
             TaskId::Id(task.id.clone())
         }
     }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LabelRequirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+}
+
+// Selection of tasks on their labels, an empty selector matches every task
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct LabelSelector {
+    pub requirements: Vec<LabelRequirement>,
+}
+
+impl LabelSelector {
+    pub fn matches(&self, labels: &HashMap<String, String>) -> bool {
+        self.requirements.iter().all(|requirement| match requirement {
+            LabelRequirement::Equals(key, value) => labels.get(key) == Some(value),
+            LabelRequirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            LabelRequirement::Exists(key) => labels.contains_key(key),
+        })
+    }
+}
+
+// Parse selectors like team=data,env!=prod,batch
+impl FromStr for LabelSelector {
+    type Err = TaskError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        source.split(',')
+            .map(str::trim)
+            .filter(|requirement| !requirement.is_empty())
+            .map(|requirement| {
+                let parsed = if let Some(index) = requirement.find("!=") {
+                    LabelRequirement::NotEquals(requirement[..index].trim().to_string(), requirement[index + 2..].trim().to_string())
+                } else if let Some(index) = requirement.find('=') {
+                    LabelRequirement::Equals(requirement[..index].trim().to_string(), requirement[index + 1..].trim().to_string())
+                } else {
+                    LabelRequirement::Exists(requirement.to_string())
+                };
+                match parsed {
+                    LabelRequirement::Equals(ref key, _) | LabelRequirement::NotEquals(ref key, _) if key.is_empty() =>
+                        Err(TaskError::ValidationError(format!("Invalid label selector {}", requirement))),
+                    _ => Ok(parsed)
+                }
+            })
+            .collect::<Result<Vec<LabelRequirement>, TaskError>>()
+            .and_then(|requirements| match requirements.is_empty() {
+                // An empty selector would match every task
+                true => Err(TaskError::ValidationError(format!("No label requirement in selector {:?}", source))),
+                false => Ok(LabelSelector { requirements }),
+            })
+    }
+}
+
+// Words searched in sequence in the name, command or output of the tasks, ignoring case and punctuation
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchQuery {
+    pub words: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn matches(&self, text: &str) -> bool {
+        let words: Vec<String> = search_words(text).collect();
+        !self.words.is_empty() && words.windows(self.words.len()).any(|window| window == self.words.as_slice())
+    }
+
+    // The output of a task is searched once it is completed
+    pub fn matches_task(&self, task: &Task, status: &TaskStatus) -> bool {
+        let output = match status {
+            TaskStatus::Success(output) | TaskStatus::Error(output, _) => Some(output.to_string()),
+            TaskStatus::Scheduled | TaskStatus::Cancelled => None,
+        };
+        task.name.iter().chain(Some(&task.command)).chain(output.iter())
+            .any(|text| self.matches(text))
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = TaskError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let words: Vec<String> = search_words(source).collect();
+        if words.is_empty() {
+            return Err(TaskError::ValidationError(format!("No word to search in {:?}", source)));
+        }
+        Ok(SearchQuery { words })
+    }
+}
+
+// Letters and digits split on anything else, as the storages index them
+fn search_words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(values: Vec<(&str, &str)>) -> HashMap<String, String> {
+        values.into_iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_label_selector_parse() {
+        assert_eq!("team=data, env!=prod,batch".parse::<LabelSelector>().unwrap(), LabelSelector {
+            requirements: vec![
+                LabelRequirement::Equals("team".to_string(), "data".to_string()),
+                LabelRequirement::NotEquals("env".to_string(), "prod".to_string()),
+                LabelRequirement::Exists("batch".to_string()),
+            ]
+        });
+        assert_eq!(format!("{}", "=data".parse::<LabelSelector>().unwrap_err()), "Invalid task definition : Invalid label selector =data");
+        assert_eq!(format!("{}", " , ".parse::<LabelSelector>().unwrap_err()), "Invalid task definition : No label requirement in selector \" , \"");
+    }
+
+    #[test]
+    fn test_label_selector_matches() {
+        let selector: LabelSelector = "team=data,env!=prod".parse().unwrap();
+        assert!(selector.matches(&labels(vec![("team", "data"), ("env", "dev")])));
+        assert!(selector.matches(&labels(vec![("team", "data")])));
+        assert!(!selector.matches(&labels(vec![("team", "data"), ("env", "prod")])));
+        assert!(!selector.matches(&labels(vec![("team", "web")])));
+        assert!(LabelSelector::default().matches(&labels(vec![])));
+    }
+
+    #[test]
+    fn test_search_query_matches_words_in_sequence() {
+        let query = "Connection refused".parse::<SearchQuery>().unwrap();
+        assert_eq!(query.words, vec!["connection", "refused"]);
+        assert!(query.matches("curl: (7) Failed to connect: CONNECTION-refused."));
+        assert!(!query.matches("refused connection"));
+        assert!(!query.matches("connection was refused"));
+        assert!(" ,; ".parse::<SearchQuery>().is_err());
+    }
+
+    #[test]
+    fn test_task_status_project_should_replay_transition_events() {
+        let statuses = vec![
+            TaskStatus::Success("done".into()),
+            TaskStatus::Error(TaskOutput { content: vec![], truncated: 0, log: None }, Some(2)),
+            TaskStatus::Error(TaskOutput { content: b"oops".to_vec(), truncated: 12, log: Some(PathBuf::from("/var/log/tasc/id.log")) }, None),
+            TaskStatus::Cancelled,
+        ];
+        for status in statuses {
+            let events: Vec<TaskEvent> = vec![TaskEvent::Submitted { command: "ls".to_string() }, TaskEvent::Started].into_iter()
+                .chain(status.events())
+                .collect();
+            // The events only record the size of the output
+            let mut expected = status.clone();
+            if let TaskStatus::Success(ref mut output) | TaskStatus::Error(ref mut output, _) = expected {
+                output.content.clear();
+            }
+            assert_eq!(format!("{:?}", TaskStatus::project(&events)), format!("{:?}", expected));
+        }
+        assert!(matches!(TaskStatus::project(&[]), TaskStatus::Scheduled));
+    }
+
+    #[test]
+    fn test_task_output_display_is_lossy() {
+        let output = TaskOutput { content: b"ok\xff\xfe".to_vec(), ..TaskOutput::default() };
+        assert_eq!(output.to_string(), "ok\u{fffd}\u{fffd}");
+    }
+
+    #[test]
+    fn test_retention_policy_select_should_keep_recent_and_last_failures() {
+        let now = SystemTime::now();
+        let task = |id: &str, name: Option<&str>, outcome: TaskOutcome, days: Option<u64>| CompletedTask {
+            id: id.to_string(),
+            name: name.map(String::from),
+            outcome,
+            completed_at: days.map(|days| now - Duration::from_secs(days * 86400)),
+        };
+        let tasks = vec![
+            task("old-success", None, TaskOutcome::Success, Some(40)),
+            task("old-error", None, TaskOutcome::Error, Some(50)),
+            task("older-error", None, TaskOutcome::Error, Some(60)),
+            task("unknown", None, TaskOutcome::Cancelled, None),
+            task("recent", None, TaskOutcome::Success, Some(1)),
+            task("backup-1", Some("backup"), TaskOutcome::Success, Some(1)),
+            task("backup-2", Some("backup"), TaskOutcome::Success, Some(2)),
+            task("backup-3", Some("backup"), TaskOutcome::Error, Some(3)),
+        ];
+        let ids = |policy: RetentionPolicy| policy.select(tasks.clone(), now).into_iter().map(|task| task.id).collect::<Vec<String>>();
+
+        assert_eq!(ids(RetentionPolicy::default()), Vec::<String>::new());
+        assert_eq!(ids(RetentionPolicy { max_age: Some(Duration::from_secs(30 * 86400)), ..RetentionPolicy::default() }),
+                   vec!["old-success", "old-error", "older-error", "unknown"]);
+        assert_eq!(ids(RetentionPolicy { max_age: Some(Duration::from_secs(30 * 86400)), keep_failures: Some(2), ..RetentionPolicy::default() }),
+                   vec!["old-success", "older-error", "unknown"]);
+        assert_eq!(ids(RetentionPolicy { max_age: Some(Duration::from_secs(30 * 86400)), outcomes: vec![TaskOutcome::Success], ..RetentionPolicy::default() }),
+                   vec!["old-success"]);
+        assert_eq!(ids(RetentionPolicy { max_per_name: Some(1), ..RetentionPolicy::default() }),
+                   vec!["backup-2", "backup-3"]);
+        assert_eq!(ids(RetentionPolicy { max_per_name: Some(1), keep_failures: Some(1), ..RetentionPolicy::default() }),
+                   vec!["backup-2"]);
+    }
 }
\ No newline at end of file
@@ -1,18 +1,96 @@
 use anyhow::Error;
 use im::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::executor::model::model::{TaskId, TaskStatus};
+use crate::executor::model::model::{Artifact, CompletedTask, Container, Credentials, ExportedTask, HookFailure, LabelSelector, RecordedEvent, ResourceLimits, RetentionPolicy, SearchQuery, Task, TaskHooks, TaskId, TaskStatus, TaskTemplate, WasmModule};
 
 pub trait TaskSchedulerPort {
     fn schedule_task<T>(&mut self, input_task: T) -> Result<TaskId, Error>
         where T: Into<TaskInput>;
 
+    fn schedule_tasks<T>(&mut self, input_tasks: Vec<T>) -> Result<Vec<TaskId>, Error>
+        where T: Into<TaskInput>;
+
+    fn schedule_template<T>(&mut self, input_template: T) -> Result<TaskId, Error>
+        where T: Into<TemplateInput>;
+
+    fn create_template<T>(&mut self, template: T) -> Result<(), Error>
+        where T: Into<TaskTemplate>;
+
     fn task_status<T>(&mut self, id: T) -> Result<TaskStatus, Error>
         where T: Into<TaskId> ;
+
+    fn list_tasks(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error>;
+
+    fn search_tasks(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error>;
+
+    fn cancel_tasks(&mut self, selector: &LabelSelector) -> Result<Vec<TaskId>, Error>;
+
+    fn task_artifacts<T>(&mut self, id: T) -> Result<Vec<Artifact>, Error>
+        where T: Into<TaskId>;
+
+    // Copy the content of the artifact of the task at path to destination
+    fn extract_artifact<T>(&mut self, id: T, path: &Path, destination: &Path) -> Result<(), Error>
+        where T: Into<TaskId>;
+
+    fn task_hook_failures<T>(&mut self, id: T) -> Result<Vec<HookFailure>, Error>
+        where T: Into<TaskId>;
+
+    fn task_events(&mut self, id: &str) -> Result<Vec<RecordedEvent>, Error>;
+
+    // Completed tasks selected by the policy, they are only listed with dry_run
+    fn purge_tasks(&mut self, policy: &RetentionPolicy, dry_run: bool) -> Result<Vec<CompletedTask>, Error>;
+
+    // Every stored task given to write in the order they were saved, the number of exported tasks is returned
+    fn export_tasks(&mut self, write: &mut dyn FnMut(ExportedTask) -> Result<(), Error>) -> Result<usize, Error>;
+
+    // Tasks whose id is already stored are skipped, the number of imported tasks is returned
+    fn import_tasks<I>(&mut self, tasks: I) -> Result<usize, Error>
+        where I: IntoIterator<Item = Result<ExportedTask, Error>>;
 }
 
+#[derive(Clone, Debug)]
 pub struct TaskInput {
     pub name: Option<String>,
     pub command: String,
     pub env: Option<HashMap<String, String>>,
+    pub timeout: Option<Duration>,
+    // Names of the tasks that must succeed before this one is executed
+    pub dependencies: Vec<String>,
+    pub labels: HashMap<String, String>,
+    pub working_dir: Option<PathBuf>,
+    pub stdin: Option<Vec<u8>>,
+    pub umask: Option<u32>,
+    pub limits: ResourceLimits,
+    pub sandboxed: bool,
+    pub credentials: Option<Credentials>,
+    pub container: Option<Container>,
+    pub host: Option<String>,
+    pub wasm: Option<WasmModule>,
+    pub executor: Option<String>,
+    pub artifacts: Vec<String>,
+    pub hooks: TaskHooks,
+}
+
+#[derive(Clone, Debug)]
+pub struct TemplateInput {
+    pub template: String,
+    pub name: Option<String>,
+    pub params: HashMap<String, String>,
+    pub labels: HashMap<String, String>,
+    pub working_dir: Option<PathBuf>,
+    pub stdin: Option<Vec<u8>>,
+    pub umask: Option<u32>,
+    pub limits: ResourceLimits,
+    pub sandboxed: bool,
+    pub credentials: Option<Credentials>,
+    pub container: Option<Container>,
+    pub host: Option<String>,
+    pub wasm: Option<WasmModule>,
+    pub executor: Option<String>,
+    pub artifacts: Vec<String>,
+    pub hooks: TaskHooks,
+    // Bound of the timeout of the template, the task is killed once the shortest one elapsed
+    pub timeout: Option<Duration>,
 }
\ No newline at end of file
@@ -1,16 +1,61 @@
 use anyhow::Error;
+use std::path::{Path, PathBuf};
 use mockall::*;
 
-use crate::executor::model::model::{Task, TaskId, TaskStatus};
+use crate::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, HookStage, LabelSelector, RecordedEvent, SearchQuery, Task, TaskEvent, TaskId, TaskStatus, TaskTemplate};
 
 #[automock]
 pub trait TaskStoragePort {
     fn save(&mut self, task: Task) -> Result<Task, Error>;
 
+    fn save_all(&mut self, tasks: Vec<Task>) -> Result<Vec<Task>, Error>;
+
     fn status(&mut self, id: TaskId) -> Result<TaskStatus, Error>;
 
+    // Id of the task, looked up when it is given by name
+    fn resolve(&mut self, id: TaskId) -> Result<String, Error>;
+
     fn complete(&mut self, task: &Task, status: TaskStatus) -> Result<(), Error>;
 
+    fn find(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error>;
+
+    // Tasks matching the query, in the order they were saved
+    fn search(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error>;
+
+    fn save_template(&mut self, template: TaskTemplate) -> Result<(), Error>;
+
+    fn template(&mut self, name: &str) -> Result<TaskTemplate, Error>;
+
+    fn save_artifacts(&mut self, task: &Task, artifacts: Vec<Artifact>) -> Result<(), Error>;
+
+    fn artifacts(&mut self, id: TaskId) -> Result<Vec<Artifact>, Error>;
+
+    fn save_hook_failure(&mut self, task: &Task, failure: HookFailure) -> Result<(), Error>;
+
+    fn hook_failures(&mut self, id: TaskId) -> Result<Vec<HookFailure>, Error>;
+
+    // Tasks which are no longer scheduled, without their output
+    fn completed(&mut self) -> Result<Vec<CompletedTask>, Error>;
+
+    // Tasks are deleted with their output, artifacts and hook failures, missing ones are ignored. The digests of the deleted
+    // artifacts which no stored task refers to anymore are returned
+    fn delete(&mut self, ids: &[String]) -> Result<Vec<String>, Error>;
+
+    // Page of the tasks in the order they were saved
+    fn export(&mut self, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error>;
+
+    // Tasks are stored as they were exported, the ones whose id is already stored are skipped
+    fn import(&mut self, tasks: Vec<ExportedTask>) -> Result<usize, Error>;
+
+}
+
+#[automock]
+pub trait EventStorePort {
+    // Events are only ever appended, the store records when and by whom
+    fn append(&mut self, task_id: &str, event: TaskEvent) -> Result<(), Error>;
+
+    // Events of the task in the order they were appended
+    fn events(&mut self, task_id: &str) -> Result<Vec<RecordedEvent>, Error>;
 }
 
 #[automock]
@@ -18,6 +63,23 @@ pub trait TaskExecutionPort {
     fn execute(&self, task: &Task) -> Result<TaskStatus, Error>;
 }
 
+#[automock]
+pub trait ArtifactStorePort {
+    // Copy the files of the task matching its artifact patterns into the store
+    fn collect(&self, task: &Task) -> Result<Vec<Artifact>, Error>;
+
+    fn extract(&self, artifact: &Artifact, destination: &Path) -> Result<(), Error>;
+
+    // Remove the contents of the digests and the output logs of purged tasks, missing files are ignored
+    fn remove(&self, digests: &[String], logs: &[PathBuf]) -> Result<(), Error>;
+}
+
+#[automock]
+pub trait HookPort {
+    // Run the hooks of the task for the stage, status being the one of the task at that time
+    fn run(&self, task: &Task, stage: HookStage, status: &TaskStatus) -> Result<(), Error>;
+}
+
 #[automock]
 pub trait IdGeneratorPort {
     fn generate_id(&self) -> String;
@@ -1,33 +1,206 @@
-use crate::executor::ports::secondary::{TaskStoragePort, TaskExecutionPort, IdGeneratorPort};
-use crate::executor::ports::primary::{TaskSchedulerPort, TaskInput};
-use crate::executor::model::model::{Task, TaskId, TaskStatus};
-use anyhow::{Error, Context};
+use crate::executor::ports::secondary::{ArtifactStorePort, EventStorePort, HookPort, TaskStoragePort, TaskExecutionPort, IdGeneratorPort};
+use crate::executor::ports::primary::{TaskSchedulerPort, TaskInput, TemplateInput};
+use crate::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, HookStage, LabelSelector, RecordedEvent, RetentionPolicy, SearchQuery, Task, TaskEvent, TaskId, TaskStatus, TaskTemplate, WasmSource};
+use crate::executor::model::error::TaskError;
+use anyhow::{anyhow, Error, Context};
+use im::HashMap;
+use std::collections::HashSet;
+use std::path::{Component, Path};
+use std::time::SystemTime;
+
+// Tasks read from or written to the storage at once by export and import
+const TRANSFER_PAGE_SIZE: usize = 100;
 
 pub struct TaskScheduler<'a> {
     storage: &'a mut dyn TaskStoragePort,
+    events: &'a mut dyn EventStorePort,
     execution: &'a dyn TaskExecutionPort,
+    artifacts: &'a dyn ArtifactStorePort,
+    hooks: &'a dyn HookPort,
     id_generator: &'a dyn IdGeneratorPort,
 }
 
 impl TaskSchedulerPort for TaskScheduler<'_> {
     fn schedule_task<T>(&mut self, input_task: T) -> Result<TaskId, Error>
         where T: Into<TaskInput> {
-        self.storage.save(task(input_task.into(), self.id_generator.generate_id())).context("Error storing task during schedule")
+        let input_task = order_by_dependencies(vec![input_task.into()])?.remove(0);
+        self.storage.save(task(input_task, self.id_generator.generate_id())).context("Error storing task during schedule")
+            .and_then(|into_task| submit_task(&into_task, self.events).map(|_| into_task))
             // No rule logic for the moment, execute after
-            .and_then(|into_task| execute_task(into_task.into(), self.execution, self.storage)).context("Error during task execution")
+            .and_then(|into_task| execute_task(into_task, self.execution, self.artifacts, self.hooks, self.storage, self.events)).context("Error during task execution")
+    }
+
+    fn schedule_tasks<T>(&mut self, input_tasks: Vec<T>) -> Result<Vec<TaskId>, Error>
+        where T: Into<TaskInput> {
+        let ordered_inputs = order_by_dependencies(input_tasks.into_iter().map(Into::into).collect())
+            .context("Error validating tasks")?;
+        let tasks: Vec<(Task, Vec<String>)> = ordered_inputs.into_iter()
+            .map(|input| {
+                let dependencies = input.dependencies.clone();
+                (task(input, self.id_generator.generate_id()), dependencies)
+            })
+            .collect();
+        self.storage.save_all(tasks.iter().map(|(task, _)| task.clone()).collect()).context("Error storing tasks during schedule")?;
+        tasks.iter().try_for_each(|(task, _)| submit_task(task, self.events))?;
+
+        let mut failed_names = HashSet::new();
+        let mut ids = Vec::with_capacity(tasks.len());
+        for (task, dependencies) in tasks {
+            let status = match dependencies.iter().find(|dependency| failed_names.contains(*dependency)) {
+                Some(dependency) => {
+                    let status = TaskStatus::Error(format!("Dependency {} did not succeed", dependency).into(), None);
+                    complete_task(&task, status.clone(), self.storage, self.events).context(format!("Error skipping task {}", task.id))?;
+                    status
+                }
+                // The failed task is completed as an error by run_task, the rest of the tasks is still run
+                None => run_task(&task, self.execution, self.artifacts, self.hooks, self.storage, self.events)
+                    .unwrap_or_else(|error| TaskStatus::Error(format!("{:#}", error).into(), None))
+            };
+            if let (TaskStatus::Error(_, _), Some(name)) = (&status, &task.name) {
+                failed_names.insert(name.clone());
+            }
+            ids.push(TaskId::from(&task));
+        }
+        Ok(ids)
     }
 
+    fn schedule_template<T>(&mut self, input_template: T) -> Result<TaskId, Error>
+        where T: Into<TemplateInput> {
+        let input_template = input_template.into();
+        let template = self.storage.template(&input_template.template)
+            .context(format!("Error loading template {}", input_template.template))?;
+        let input_task = instantiate(&template, input_template)
+            .context(format!("Error instantiating template {}", template.name))?;
+        self.schedule_task(input_task)
+    }
+
+    fn create_template<T>(&mut self, template: T) -> Result<(), Error>
+        where T: Into<TaskTemplate> {
+        let template = template.into();
+        validate_template(&template).context("Error validating template")?;
+        self.storage.save_template(template).context("Error storing template")
+    }
+
+    // The status is the projection of the events of the task, whose name is resolved by the storage. The storage
+    // keeps the status of the tasks submitted before the event log, and the content of the outputs, the projection
+    // of a purged task having none
     fn task_status<T>(&mut self, id: T) -> Result<TaskStatus, Error>
         where T: Into<TaskId> {
-        self.storage.status(id.into()).context("Error on task status")
+        let id = match id.into() {
+            TaskId::Id(id) => id,
+            name => self.storage.resolve(name).context("Error on task status")?,
+        };
+        let events = self.events.events(&id).context("Error on task events")?;
+        if events.is_empty() {
+            return self.storage.status(TaskId::Id(id)).context("Error on task status");
+        }
+        let status = TaskStatus::project(events.iter().map(|recorded| &recorded.event));
+        if status.output().is_none() {
+            return Ok(status);
+        }
+        match self.storage.status(TaskId::Id(id)) {
+            Ok(stored) if stored.outcome() == status.outcome() => Ok(stored),
+            _ => Ok(status),
+        }
+    }
+
+    fn list_tasks(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        self.storage.find(selector).context("Error listing tasks")
+    }
+
+    fn search_tasks(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        self.storage.search(query).context("Error searching tasks")
+    }
+
+    // Only tasks which are still scheduled can be cancelled
+    fn cancel_tasks(&mut self, selector: &LabelSelector) -> Result<Vec<TaskId>, Error> {
+        if selector.requirements.is_empty() {
+            return Err(TaskError::ValidationError("Tasks to cancel must be selected by at least one label".to_string()).into());
+        }
+        let tasks = self.storage.find(selector).context("Error searching tasks to cancel")?;
+        tasks.into_iter()
+            .filter(|(_, status)| !status.is_terminal())
+            .map(|(task, _)| complete_task(&task, TaskStatus::Cancelled, self.storage, self.events)
+                .context(format!("Error cancelling task {}", task.id))
+                .map(|_| TaskId::from(&task)))
+            .collect()
+    }
+
+    fn task_artifacts<T>(&mut self, id: T) -> Result<Vec<Artifact>, Error>
+        where T: Into<TaskId> {
+        self.storage.artifacts(id.into()).context("Error listing task artifacts")
+    }
+
+    fn extract_artifact<T>(&mut self, id: T, path: &Path, destination: &Path) -> Result<(), Error>
+        where T: Into<TaskId> {
+        let artifact = self.storage.artifacts(id.into()).context("Error listing task artifacts")?
+            .into_iter()
+            .find(|artifact| artifact.path == path)
+            .ok_or_else(|| anyhow!("No artifact {} for this task", path.display()))?;
+        self.artifacts.extract(&artifact, destination)
+            .context(format!("Error extracting artifact {}", path.display()))
+    }
+
+    fn task_hook_failures<T>(&mut self, id: T) -> Result<Vec<HookFailure>, Error>
+        where T: Into<TaskId> {
+        self.storage.hook_failures(id.into()).context("Error listing task hook failures")
+    }
+
+    fn task_events(&mut self, id: &str) -> Result<Vec<RecordedEvent>, Error> {
+        self.events.events(id).context("Error listing task events")
+    }
+
+    // The events of the purged tasks are kept, the event log is never rewritten but it does not hold their output.
+    // Their output logs and the artifact contents no other task refers to are removed once the tasks are deleted
+    fn purge_tasks(&mut self, policy: &RetentionPolicy, dry_run: bool) -> Result<Vec<CompletedTask>, Error> {
+        let completed = self.storage.completed().context("Error listing completed tasks")?;
+        let purged = policy.select(completed, SystemTime::now());
+        if !dry_run && !purged.is_empty() {
+            let ids: Vec<String> = purged.iter().map(|task| task.id.clone()).collect();
+            let mut logs = vec![];
+            for id in ids.iter() {
+                let status = self.storage.status(TaskId::Id(id.clone())).context(format!("Error loading purged task {}", id))?;
+                logs.extend(status.output().and_then(|output| output.log.clone()));
+            }
+            let digests = self.storage.delete(&ids).context("Error purging tasks")?;
+            self.artifacts.remove(&digests, &logs).context("Error removing the files of the purged tasks")?;
+        }
+        Ok(purged)
+    }
+
+    fn export_tasks(&mut self, write: &mut dyn FnMut(ExportedTask) -> Result<(), Error>) -> Result<usize, Error> {
+        let mut exported = 0;
+        loop {
+            let page = self.storage.export(exported, TRANSFER_PAGE_SIZE).context("Error exporting tasks")?;
+            let count = page.len();
+            page.into_iter().try_for_each(&mut *write)?;
+            exported += count;
+            if count < TRANSFER_PAGE_SIZE {
+                return Ok(exported);
+            }
+        }
+    }
+
+    fn import_tasks<I>(&mut self, tasks: I) -> Result<usize, Error>
+        where I: IntoIterator<Item = Result<ExportedTask, Error>> {
+        let mut tasks = tasks.into_iter().peekable();
+        let mut imported = 0;
+        while tasks.peek().is_some() {
+            let page = tasks.by_ref().take(TRANSFER_PAGE_SIZE).collect::<Result<Vec<ExportedTask>, Error>>()?;
+            imported += self.storage.import(page).context("Error importing tasks")?;
+        }
+        Ok(imported)
     }
 }
 
 impl TaskScheduler<'_> {
-    pub fn new<'a>(storage: &'a mut dyn TaskStoragePort, execution: &'a dyn TaskExecutionPort, id_generator: &'a dyn IdGeneratorPort) -> TaskScheduler<'a> {
+    pub fn new<'a>(storage: &'a mut dyn TaskStoragePort, events: &'a mut dyn EventStorePort, execution: &'a dyn TaskExecutionPort, artifacts: &'a dyn ArtifactStorePort, hooks: &'a dyn HookPort, id_generator: &'a dyn IdGeneratorPort) -> TaskScheduler<'a> {
         TaskScheduler {
             storage,
+            events,
             execution,
+            artifacts,
+            hooks,
             id_generator,
         }
     }
@@ -40,27 +213,299 @@ fn task(input: TaskInput, id: String) -> Task {
         command: input.command,
         name: input.name,
         env: input.env,
+        timeout: input.timeout,
+        labels: input.labels,
+        working_dir: input.working_dir,
+        stdin: input.stdin,
+        umask: input.umask,
+        limits: input.limits,
+        sandboxed: input.sandboxed,
+        credentials: input.credentials,
+        container: input.container,
+        host: input.host,
+        wasm: input.wasm,
+        executor: input.executor,
+        artifacts: input.artifacts,
+        hooks: input.hooks,
     }
 }
 
-fn execute_task(task: Task, executor: &dyn TaskExecutionPort, storage: &mut dyn TaskStoragePort) -> Result<TaskId, Error> {
-    executor.execute(&task)
-        .map_err(|error| {
-            match storage.complete(&task, TaskStatus::Error(error.to_string())) {
-                Ok(_) => error.context(format!("Error during task {} execution", task.id)),
-                Err(err) => err.context(format!("Error executing task {} and during status save execution", task.id))
+fn validate_input(input: &TaskInput) -> Result<(), TaskError> {
+    let name = input.name.as_deref().unwrap_or("without name");
+    if input.command.trim().is_empty() {
+        return Err(TaskError::ValidationError(format!("Task {} has an empty command", name)));
+    }
+    if let Some(ref working_dir) = input.working_dir {
+        if !working_dir.is_absolute() {
+            return Err(TaskError::ValidationError(format!("Task {} working directory {} is not an absolute path", name, working_dir.display())));
+        }
+    }
+    if let Some(umask) = input.umask {
+        if umask > 0o777 {
+            return Err(TaskError::ValidationError(format!("Task {} umask {:o} is not a valid permission mask", name, umask)));
+        }
+    }
+    let limits = &input.limits;
+    if limits.cpu_time.is_some_and(|cpu_time| cpu_time.as_secs() == 0)
+        || [limits.memory, limits.open_files, limits.processes].contains(&Some(0)) {
+        return Err(TaskError::ValidationError(format!("Task {} resource limits must be greater than 0", name)));
+    }
+    if input.host.as_deref().is_some_and(|host| host.trim().is_empty()) {
+        return Err(TaskError::ValidationError(format!("Task {} has an empty host", name)));
+    }
+    // The host is given to the ssh client, it must not be read as one of its options
+    if let Some(host) = input.host.as_deref().filter(|host| host.starts_with('-') || host.contains(char::is_whitespace)) {
+        return Err(TaskError::ValidationError(format!("Task {} host {} is not a valid host", name, host)));
+    }
+    if input.executor.as_deref().is_some_and(|executor| executor.trim().is_empty()) {
+        return Err(TaskError::ValidationError(format!("Task {} has an empty executor", name)));
+    }
+    if input.hooks.pre.as_deref().is_some_and(|hook| hook.trim().is_empty())
+        || input.hooks.post.as_deref().is_some_and(|hook| hook.trim().is_empty()) {
+        return Err(TaskError::ValidationError(format!("Task {} has an empty hook", name)));
+    }
+    // Hooks run on the host without the isolation of the sandbox, nor a user which may not be the default one
+    if (input.hooks.pre.is_some() || input.hooks.post.is_some()) && (input.sandboxed || input.credentials.is_some()) {
+        return Err(TaskError::ValidationError(format!("Task {} hooks can't be combined with a sandbox or a user", name)));
+    }
+    // The sandbox isolation is given by the sandbox executor only, a container shares the users of the host
+    if input.container.is_some() && input.sandboxed {
+        return Err(TaskError::ValidationError(format!("Task {} container can't be sandboxed", name)));
+    }
+    if let Some(ref wasm) = input.wasm {
+        if input.container.is_some() || input.host.is_some() || input.sandboxed || input.credentials.is_some() {
+            return Err(TaskError::ValidationError(format!("Task {} WASI module can't be combined with a container, a host, a sandbox or a user", name)));
+        }
+        if wasm.fuel == Some(0) {
+            return Err(TaskError::ValidationError(format!("Task {} fuel must be greater than 0", name)));
+        }
+        if let WasmSource::Path(ref path) = wasm.source {
+            if !path.is_absolute() {
+                return Err(TaskError::ValidationError(format!("Task {} WASI module {} is not an absolute path", name, path.display())));
+            }
+        }
+    }
+    if !input.artifacts.is_empty() {
+        if input.working_dir.is_none() {
+            return Err(TaskError::ValidationError(format!("Task {} needs a working directory to collect artifacts", name)));
+        }
+        if input.container.is_some() || input.host.is_some() {
+            return Err(TaskError::ValidationError(format!("Task {} artifacts can't be collected from a container or a remote host", name)));
+        }
+        let invalid = input.artifacts.iter()
+            .find(|pattern| pattern.trim().is_empty() || Path::new(pattern).components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)));
+        if let Some(invalid) = invalid {
+            return Err(TaskError::ValidationError(format!("Task {} artifact pattern {} must stay in the working directory", name, invalid)));
+        }
+    }
+    if let Some(ref container) = input.container {
+        let relative = std::iter::once(&container.rootfs)
+            .chain(container.mounts.iter().flat_map(|mount| vec![&mount.source, &mount.destination]))
+            .find(|path| !path.is_absolute());
+        if let Some(relative) = relative {
+            return Err(TaskError::ValidationError(format!("Task {} container path {} is not an absolute path", name, relative.display())));
+        }
+    }
+    Ok(())
+}
+
+// Validate the inputs and sort them so that every task comes after its dependencies
+fn order_by_dependencies(inputs: Vec<TaskInput>) -> Result<Vec<TaskInput>, TaskError> {
+    let mut names: Vec<String> = vec![];
+    for input in inputs.iter() {
+        validate_input(input)?;
+        if let Some(ref name) = input.name {
+            if names.contains(name) {
+                return Err(TaskError::ValidationError(format!("Task name {} is defined more than once", name)));
+            }
+            names.push(name.clone());
+        }
+    }
+    for input in inputs.iter() {
+        if let Some(unknown) = input.dependencies.iter().find(|dependency| !names.contains(dependency)) {
+            return Err(TaskError::ValidationError(format!("Task {} depends on unknown task {}", input.name.as_deref().unwrap_or("without name"), unknown)));
+        }
+    }
+
+    let mut remaining = inputs;
+    let mut ordered = Vec::with_capacity(remaining.len());
+    let mut scheduled_names = HashSet::new();
+    while !remaining.is_empty() {
+        match remaining.iter().position(|input| input.dependencies.iter().all(|dependency| scheduled_names.contains(dependency))) {
+            Some(index) => {
+                let input = remaining.remove(index);
+                if let Some(ref name) = input.name {
+                    scheduled_names.insert(name.clone());
+                }
+                ordered.push(input);
             }
+            None => return Err(TaskError::ValidationError(format!("Cyclic dependencies between tasks {}",
+                remaining.iter().filter_map(|input| input.name.clone()).collect::<Vec<String>>().join(", "))))
+        }
+    }
+    Ok(ordered)
+}
+
+fn validate_template(template: &TaskTemplate) -> Result<(), TaskError> {
+    if template.command.trim().is_empty() {
+        return Err(TaskError::ValidationError(format!("Template {} has an empty command", template.name)));
+    }
+    let mut check_placeholder = |_: &str| Ok(String::new());
+    render(&template.command, &mut check_placeholder)?;
+    template.env.iter()
+        .flat_map(|env| env.values())
+        .try_for_each(|value| render(value, &mut check_placeholder).map(|_| ()))
+}
+
+fn instantiate(template: &TaskTemplate, input: TemplateInput) -> Result<TaskInput, TaskError> {
+    let mut used_params = HashSet::new();
+    let mut resolve = |param: &str| input.params.get(param)
+        .cloned()
+        .inspect(|_| {
+            used_params.insert(param.to_string());
         })
-        .and_then(|result| storage.complete(&task, result))
+        .ok_or_else(|| TaskError::ValidationError(format!("Missing value for parameter {}", param)));
+    // The command is split on whitespace into its arguments, a value can't add arguments to it
+    let command = render(&template.command, &mut |param| resolve(param).and_then(|value| match value.contains(char::is_whitespace) {
+        true => Err(TaskError::ValidationError(format!("Value of parameter {} used in the command can't contain whitespace", param))),
+        false => Ok(value),
+    }))?;
+    let env = match template.env {
+        Some(ref env) => Some(env.iter()
+            .map(|(key, value)| render(value, &mut resolve).map(|rendered| (key.clone(), rendered)))
+            .collect::<Result<HashMap<String, String>, TaskError>>()?),
+        None => None,
+    };
+    if let Some(unknown) = input.params.keys().find(|param| !used_params.contains(*param)) {
+        return Err(TaskError::ValidationError(format!("Parameter {} is not used by template {}", unknown, template.name)));
+    }
+    Ok(TaskInput {
+        name: input.name,
+        command,
+        env,
+        timeout: match (template.timeout, input.timeout) {
+            (Some(timeout), Some(bound)) => Some(timeout.min(bound)),
+            (timeout, bound) => timeout.or(bound),
+        },
+        dependencies: vec![],
+        labels: input.labels,
+        working_dir: input.working_dir,
+        stdin: input.stdin,
+        umask: input.umask,
+        limits: input.limits,
+        sandboxed: input.sandboxed,
+        credentials: input.credentials,
+        container: input.container,
+        host: input.host,
+        wasm: input.wasm,
+        executor: input.executor,
+        artifacts: input.artifacts,
+        hooks: input.hooks,
+    })
+}
+
+// Replace every {{param}} placeholder of source with the value given by resolve
+fn render(source: &str, resolve: &mut dyn FnMut(&str) -> Result<String, TaskError>) -> Result<String, TaskError> {
+    let mut rendered = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let placeholder = &rest[start + 2..];
+        let end = placeholder.find("}}")
+            .ok_or_else(|| TaskError::ValidationError(format!("Unclosed placeholder in {}", source)))?;
+        let param = placeholder[..end].trim();
+        if param.is_empty() {
+            return Err(TaskError::ValidationError(format!("Empty placeholder in {}", source)));
+        }
+        rendered.push_str(&resolve(param)?);
+        rest = &placeholder[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+fn execute_task(task: Task, executor: &dyn TaskExecutionPort, artifacts: &dyn ArtifactStorePort, hooks: &dyn HookPort, storage: &mut dyn TaskStoragePort, events: &mut dyn EventStorePort) -> Result<TaskId, Error> {
+    run_task(&task, executor, artifacts, hooks, storage, events)
         .map(|_| TaskId::from(&task))
 }
 
+fn run_task(task: &Task, executor: &dyn TaskExecutionPort, artifacts: &dyn ArtifactStorePort, hooks: &dyn HookPort, storage: &mut dyn TaskStoragePort, events: &mut dyn EventStorePort) -> Result<TaskStatus, Error> {
+    let result = match run_hook(task, HookStage::Pre, &TaskStatus::Scheduled, hooks, storage)? {
+        // The command may depend on what the pre hook prepares, it is not run without it
+        Some(failure) => {
+            let status = TaskStatus::Error(format!("Pre hook failed : {}", failure).into(), None);
+            complete_task(task, status.clone(), storage, events).map(|_| status)
+        }
+        None => events.append(&task.id, TaskEvent::Started)
+            .context(format!("Error recording start of task {}", task.id))
+            .and_then(|_| executor.execute(task)
+            .map_err(|error| {
+                match complete_task(task, TaskStatus::Error(error.to_string().into(), None), storage, events) {
+                    Ok(_) => error.context(format!("Error during task {} execution", task.id)),
+                    Err(err) => err.context(format!("Error executing task {} and during status save execution", task.id))
+                }
+            })
+            .and_then(|result| complete_task(task, result.clone(), storage, events).map(|_| result))
+            .and_then(|result| collect_artifacts(task, artifacts, storage, events).map(|_| result))),
+    };
+    // Post hooks run whatever happened so they can clean up after the pre hooks
+    let status = match result {
+        Ok(ref status) => status.clone(),
+        Err(ref error) => TaskStatus::Error(error.to_string().into(), None),
+    };
+    run_hook(task, HookStage::Post, &status, hooks, storage)?;
+    result
+}
+
+fn submit_task(task: &Task, events: &mut dyn EventStorePort) -> Result<(), Error> {
+    events.append(&task.id, TaskEvent::Submitted { command: task.command.clone() })
+        .context(format!("Error recording submission of task {}", task.id))
+}
+
+// The status is stored then its transition is appended to the event log of the task
+fn complete_task(task: &Task, status: TaskStatus, storage: &mut dyn TaskStoragePort, events: &mut dyn EventStorePort) -> Result<(), Error> {
+    let transition = status.events();
+    storage.complete(task, status)?;
+    transition.into_iter()
+        .try_for_each(|event| events.append(&task.id, event))
+        .context(format!("Error recording completion of task {}", task.id))
+}
+
+// A failed hook is recorded with the task and returned, only a storage failure is an error
+fn run_hook(task: &Task, stage: HookStage, status: &TaskStatus, hooks: &dyn HookPort, storage: &mut dyn TaskStoragePort) -> Result<Option<String>, Error> {
+    match hooks.run(task, stage, status) {
+        Ok(_) => Ok(None),
+        Err(error) => {
+            let message = format!("{:#}", error);
+            storage.save_hook_failure(task, HookFailure { stage, message: message.clone() })
+                .context(format!("Error recording {} hook failure of task {}", stage, task.id))
+                .map(|_| Some(message))
+        }
+    }
+}
+
+// Artifacts are collected whatever the status of the command, they often help to understand a failure
+// The task is already completed, a failure is recorded in its events instead of failing the rest of the schedule
+fn collect_artifacts(task: &Task, artifacts: &dyn ArtifactStorePort, storage: &mut dyn TaskStoragePort, events: &mut dyn EventStorePort) -> Result<(), Error> {
+    if task.artifacts.is_empty() {
+        return Ok(());
+    }
+    match artifacts.collect(task).and_then(|collected| storage.save_artifacts(task, collected)) {
+        Ok(_) => Ok(()),
+        Err(error) => events.append(&task.id, TaskEvent::ArtifactsFailed { message: format!("{:#}", error) })
+            .context(format!("Error recording artifacts failure of task {}", task.id)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::executor::model::error::TaskError;
+    use crate::executor::model::model::{Container, Mount, ResourceLimits, TaskHooks, TaskOutcome, TaskOutput, WasmModule};
+    use anyhow::anyhow;
     use mockall::*;
-    use crate::executor::ports::secondary::{MockTaskExecutionPort, MockTaskStoragePort, MockIdGeneratorPort};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::executor::ports::secondary::{MockArtifactStorePort, MockEventStorePort, MockHookPort, MockTaskExecutionPort, MockTaskStoragePort, MockIdGeneratorPort};
 
     // TODO add storage of commands to check num of interaction on tests impls
     #[test]
@@ -70,19 +515,191 @@ mod tests {
             name: None,
             command: "ls /home".to_string(),
             env: None,
+            timeout: None,
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
         };
 
         let mut execution_mock = MockTaskExecutionPort::new();
         execution_mock.expect_execute()
             .times(1)
-            .returning(|_| Ok(TaskStatus::Success("Coucou".to_string())));
+            .returning(|_| Ok(TaskStatus::Success("Coucou".into())));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_complete()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        assert_eq!(execute_task(input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock(), &mut storage_mock, &mut events_mock()).unwrap(), TaskId::Id("test_id".to_string()));
+    }
+
+    #[test]
+    fn test_execute_task_should_collect_artifacts_after_completion() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Error("partial report".into(), Some(2))));
+
+        let mut artifacts_mock = MockArtifactStorePort::new();
+        artifacts_mock.expect_collect()
+            .times(1)
+            .returning(|_| Ok(vec![Artifact { path: PathBuf::from("report.pdf"), digest: "e3b0c442".to_string(), size: 0 }]));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        let mut sequence = Sequence::new();
+        storage_mock.expect_complete()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+        storage_mock.expect_save_artifacts()
+            .withf(|task, artifacts| task.id == "test_id" && artifacts.len() == 1 && artifacts[0].path == Path::new("report.pdf"))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+
+        let mut report = input("report", "make report", vec![]);
+        report.working_dir = Some(PathBuf::from("/tmp/reports"));
+        report.artifacts = vec!["*.pdf".to_string()];
+        let input_task = task(report, "test_id".to_string());
+        assert_eq!(execute_task(input_task, &execution_mock, &artifacts_mock, &hooks_mock(), &mut storage_mock, &mut events_mock()).unwrap(), TaskId::Name("report".to_string()));
+    }
+
+    #[test]
+    fn test_execute_task_should_record_artifacts_failure_without_failing() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Success("done".into())));
+
+        let mut artifacts_mock = MockArtifactStorePort::new();
+        artifacts_mock.expect_collect()
+            .times(1)
+            .returning(|_| Err(anyhow!("Artifact report.pdf is a symbolic link")));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_complete()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut events_mock = MockEventStorePort::new();
+        events_mock.expect_append()
+            .withf(|_, event| *event == TaskEvent::ArtifactsFailed { message: "Artifact report.pdf is a symbolic link".to_string() })
+            .times(1)
+            .returning(|_, _| Ok(()));
+        events_mock.expect_append()
+            .returning(|_, _| Ok(()));
+
+        let mut report = input("report", "make report", vec![]);
+        report.working_dir = Some(PathBuf::from("/tmp/reports"));
+        report.artifacts = vec!["*.pdf".to_string()];
+        let input_task = task(report, "test_id".to_string());
+        assert_eq!(execute_task(input_task, &execution_mock, &artifacts_mock, &hooks_mock(), &mut storage_mock, &mut events_mock).unwrap(), TaskId::Name("report".to_string()));
+    }
+
+    #[test]
+    fn test_execute_task_should_not_run_command_when_pre_hook_fails() {
+        let execution_mock = MockTaskExecutionPort::new();
+
+        let mut hooks_mock = MockHookPort::new();
+        hooks_mock.expect_run()
+            .withf(|_, stage, _| *stage == HookStage::Pre)
+            .times(1)
+            .returning(|_, _, _| Err(anyhow!("mount: /mnt/data: permission denied")));
+        hooks_mock.expect_run()
+            .withf(|_, stage, status| *stage == HookStage::Post && matches!(status, TaskStatus::Error(_, None)))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_save_hook_failure()
+            .withf(|_, failure| failure.stage == HookStage::Pre && failure.message == "mount: /mnt/data: permission denied")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        storage_mock.expect_complete()
+            .withf(|_, status| matches!(status, TaskStatus::Error(output, None) if output.to_string() == "Pre hook failed : mount: /mnt/data: permission denied"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let input_task = task(input("report", "make report", vec![]), "test_id".to_string());
+        assert_eq!(execute_task(input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock, &mut storage_mock, &mut events_mock()).unwrap(), TaskId::Name("report".to_string()));
+    }
+
+    #[test]
+    fn test_execute_task_should_record_post_hook_failure_without_changing_status() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Success("done".into())));
+
+        let mut hooks_mock = MockHookPort::new();
+        hooks_mock.expect_run()
+            .withf(|_, stage, _| *stage == HookStage::Pre)
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        hooks_mock.expect_run()
+            .withf(|_, stage, status| *stage == HookStage::Post && matches!(status, TaskStatus::Success(_)))
+            .times(1)
+            .returning(|_, _, _| Err(anyhow!("rm: cannot remove /tmp/report")));
 
         let mut storage_mock = MockTaskStoragePort::new();
         storage_mock.expect_complete()
+            .withf(|_, status| matches!(status, TaskStatus::Success(_)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        storage_mock.expect_save_hook_failure()
+            .withf(|_, failure| failure.stage == HookStage::Post)
             .times(1)
             .returning(|_, _| Ok(()));
 
-        assert_eq!(execute_task(input_task, &execution_mock, &mut storage_mock).unwrap(), TaskId::Id("test_id".to_string()));
+        let input_task = task(input("report", "make report", vec![]), "test_id".to_string());
+        assert!(matches!(run_task(&input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock, &mut storage_mock, &mut events_mock()).unwrap(), TaskStatus::Success(_)));
+    }
+
+    #[test]
+    fn test_execute_task_with_command_failure_should_store_exit_code() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Error("mv: missing destination file operand".into(), Some(1))));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_complete()
+            .withf(|_, status| matches!(status, TaskStatus::Error(_, Some(1))))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let input_task = Task {
+            id: "test_id".to_string(),
+            name: None,
+            command: "mv /test/inexistant".to_string(),
+            env: None,
+            timeout: None,
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
+        };
+        assert_eq!(execute_task(input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock(), &mut storage_mock, &mut events_mock()).unwrap(), TaskId::Id("test_id".to_string()));
     }
 
     #[test]
@@ -90,7 +707,7 @@ mod tests {
         let mut execution_mock = MockTaskExecutionPort::new();
         execution_mock.expect_execute()
             .times(1)
-            .returning(|_| Err(TaskError::CommandError("Cannot move /test/inexistant, file does not exists".to_string())));
+            .returning(|_| Err(TaskError::CommandError("Cannot move /test/inexistant, file does not exists".to_string()).into()));
 
         let mut storage_mock = MockTaskStoragePort::new();
         storage_mock.expect_complete()
@@ -102,8 +719,22 @@ mod tests {
             name: None,
             command: "mv /test/inexistant".to_string(),
             env: None,
+            timeout: None,
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
         };
-        assert_eq!(format!("{}", execute_task(input_task, &execution_mock, &mut storage_mock).unwrap_err()), "Error during task test_id execution");
+        assert_eq!(format!("{}", execute_task(input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock(), &mut storage_mock, &mut events_mock()).unwrap_err()), "Error during task test_id execution");
     }
 
     #[test]
@@ -111,7 +742,7 @@ mod tests {
         let mut execution_mock = MockTaskExecutionPort::new();
         execution_mock.expect_execute()
             .times(1)
-            .returning(|_| Err(TaskError::CommandError("Cannot move /test/inexistant, file does not exists".to_string())));
+            .returning(|_| Err(TaskError::CommandError("Cannot move /test/inexistant, file does not exists".to_string()).into()));
 
         let mut storage_mock = MockTaskStoragePort::new();
         storage_mock.expect_complete()
@@ -123,8 +754,22 @@ mod tests {
             name: None,
             command: "mv /test/inexistant".to_string(),
             env: None,
+            timeout: None,
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
         };
-        assert_eq!(format!("{}", execute_task(input_task, &execution_mock, &mut storage_mock).unwrap_err()), "Error executing task test_id and during status save execution");
+        assert_eq!(format!("{}", execute_task(input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock(), &mut storage_mock, &mut events_mock()).unwrap_err()), "Error executing task test_id and during status save execution");
     }
 
     #[test]
@@ -132,7 +777,7 @@ mod tests {
         let mut execution_mock = MockTaskExecutionPort::new();
         execution_mock.expect_execute()
             .times(1)
-            .returning(|_| Ok(TaskStatus::Success("Coucou".to_string())));
+            .returning(|_| Ok(TaskStatus::Success("Coucou".into())));
 
         let mut storage_mock = MockTaskStoragePort::new();
         storage_mock.expect_complete()
@@ -144,8 +789,22 @@ mod tests {
             name: None,
             command: "mv /test/inexistant".to_string(),
             env: None,
+            timeout: None,
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
         };
-        assert_eq!(format!("{}", execute_task(input_task, &execution_mock, &mut storage_mock).unwrap_err()), "Storage failed");
+        assert_eq!(format!("{}", execute_task(input_task, &execution_mock, &MockArtifactStorePort::new(), &hooks_mock(), &mut storage_mock, &mut events_mock()).unwrap_err()), "Storage failed");
     }
 
     #[test]
@@ -153,7 +812,7 @@ mod tests {
         let mut execution_mock = MockTaskExecutionPort::new();
         execution_mock.expect_execute()
             .times(1)
-            .returning(|_| Ok(TaskStatus::Success("Coucou".to_string())));
+            .returning(|_| Ok(TaskStatus::Success("Coucou".into())));
 
         let mut storage_mock = MockTaskStoragePort::new();
         storage_mock.expect_save()
@@ -168,13 +827,609 @@ mod tests {
             .times(1)
             .returning(|| "test_id".to_string());
 
-        let mut service = TaskScheduler::new(&mut storage_mock, &execution_mock, &id_mock);
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
 
         let input_task = TaskInput {
             name: None,
             command: "ls /home".to_string(),
-            env: None
+            env: None,
+            timeout: None,
+            dependencies: vec![],
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
         };
         assert_eq!(service.schedule_task(input_task).unwrap(), TaskId::Id("test_id".to_string()));
     }
+
+    #[test]
+    fn test_task_scheduler_schedule_task_should_append_transitions_to_event_log() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Error("Coucou".into(), Some(2))));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_save()
+            .times(1)
+            .returning(|x| Ok(x));
+        storage_mock.expect_complete()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut id_mock = MockIdGeneratorPort::new();
+        id_mock.expect_generate_id()
+            .times(1)
+            .returning(|| "test_id".to_string());
+
+        let mut events_mock = MockEventStorePort::new();
+        let mut sequence = Sequence::new();
+        events_mock.expect_append()
+            .withf(|id, event| id == "test_id" && *event == TaskEvent::Submitted { command: "ls /home".to_string() })
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+        events_mock.expect_append()
+            .withf(|_, event| *event == TaskEvent::Started)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+        events_mock.expect_append()
+            .withf(|_, event| *event == TaskEvent::Output { size: 6 })
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+        events_mock.expect_append()
+            .withf(|_, event| matches!(event, TaskEvent::Completed { success: false, exit_code: Some(2), .. }))
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(|_, _| Ok(()));
+
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        assert_eq!(service.schedule_task(input("report", "ls /home", vec![])).unwrap(), TaskId::Name("report".to_string()));
+    }
+
+    #[test]
+    fn test_task_scheduler_task_status_should_project_events() {
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_status()
+            .withf(|id| *id == TaskId::Id("old_id".to_string()))
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Scheduled));
+        storage_mock.expect_status()
+            .withf(|id| *id == TaskId::Id("done_id".to_string()))
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Success("Coucou".into())));
+        storage_mock.expect_status()
+            .withf(|id| *id == TaskId::Id("purged_id".to_string()))
+            .times(1)
+            .returning(|_| Err(anyhow!("No task with id purged_id")));
+        storage_mock.expect_resolve()
+            .withf(|id| *id == TaskId::Name("report".to_string()))
+            .times(1)
+            .returning(|_| Ok("test_id".to_string()));
+
+        let mut events_mock = MockEventStorePort::new();
+        events_mock.expect_events()
+            .withf(|id| id == "test_id")
+            .times(2)
+            .returning(|id| Ok(recorded(id, vec![TaskEvent::Submitted { command: "ls /home".to_string() }, TaskEvent::Started, TaskEvent::Cancelled])));
+        events_mock.expect_events()
+            .withf(|id| id == "done_id" || id == "purged_id")
+            .times(2)
+            .returning(|id| Ok(recorded(id, vec![TaskEvent::Started, TaskEvent::Output { size: 6 }, TaskEvent::Completed { success: true, exit_code: Some(0), truncated: 0, log: None }])));
+        events_mock.expect_events()
+            .times(1)
+            .returning(|_| Ok(vec![]));
+
+        let execution_mock = MockTaskExecutionPort::new();
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let id_mock = MockIdGeneratorPort::new();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        assert!(matches!(service.task_status(TaskId::Id("test_id".to_string())).unwrap(), TaskStatus::Cancelled));
+        // A name gives the projection of the events of the task it resolves to
+        assert!(matches!(service.task_status(TaskId::Name("report".to_string())).unwrap(), TaskStatus::Cancelled));
+        // Tasks submitted before the event log keep the status of the storage
+        assert!(matches!(service.task_status(TaskId::Id("old_id".to_string())).unwrap(), TaskStatus::Scheduled));
+        // The output is read from the storage while it keeps the task
+        assert!(matches!(service.task_status(TaskId::Id("done_id".to_string())).unwrap(), TaskStatus::Success(output) if output.content == b"Coucou"));
+        assert!(matches!(service.task_status(TaskId::Id("purged_id".to_string())).unwrap(), TaskStatus::Success(output) if output.content.is_empty()));
+    }
+
+    fn recorded(id: &str, events: Vec<TaskEvent>) -> Vec<RecordedEvent> {
+        events.into_iter()
+            .enumerate()
+            .map(|(sequence, event)| RecordedEvent { task_id: id.to_string(), sequence: sequence as u64, at: std::time::UNIX_EPOCH, actor: "tasc".to_string(), event })
+            .collect()
+    }
+
+    #[test]
+    fn test_task_scheduler_purge_tasks_should_delete_selected_tasks() {
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_completed()
+            .times(2)
+            .returning(|| Ok(vec![
+                CompletedTask { id: "old".to_string(), name: None, outcome: TaskOutcome::Success, completed_at: None },
+                CompletedTask { id: "recent".to_string(), name: None, outcome: TaskOutcome::Success, completed_at: Some(SystemTime::now()) },
+            ]));
+        storage_mock.expect_status()
+            .withf(|id| *id == TaskId::Id("old".to_string()))
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Success(TaskOutput { content: b"head".to_vec(), truncated: 10, log: Some(PathBuf::from("/var/log/tasc/old.stdout")) })));
+        storage_mock.expect_delete()
+            .withf(|ids| ids == ["old".to_string()])
+            .times(1)
+            .returning(|_| Ok(vec!["0a1b".to_string()]));
+
+        let execution_mock = MockTaskExecutionPort::new();
+        let mut artifacts_mock = MockArtifactStorePort::new();
+        // The files of the tasks are only removed once they are deleted
+        artifacts_mock.expect_remove()
+            .withf(|digests, logs| digests == ["0a1b".to_string()] && logs == [PathBuf::from("/var/log/tasc/old.stdout")])
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+        let id_mock = MockIdGeneratorPort::new();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        let policy = RetentionPolicy { max_age: Some(std::time::Duration::from_secs(86400)), ..RetentionPolicy::default() };
+        // Nothing is deleted on dry run
+        assert_eq!(service.purge_tasks(&policy, true).unwrap().into_iter().map(|task| task.id).collect::<Vec<String>>(), vec!["old"]);
+        assert_eq!(service.purge_tasks(&policy, false).unwrap().into_iter().map(|task| task.id).collect::<Vec<String>>(), vec!["old"]);
+    }
+
+    #[test]
+    fn test_task_scheduler_export_tasks_should_read_storage_by_pages() {
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_export()
+            .withf(|offset, limit| *offset == 0 && *limit == TRANSFER_PAGE_SIZE)
+            .times(1)
+            .returning(|_, limit| Ok((0..limit).map(|index| exported(&format!("id-{}", index))).collect()));
+        storage_mock.expect_export()
+            .withf(|offset, _| *offset == TRANSFER_PAGE_SIZE)
+            .times(1)
+            .returning(|_, _| Ok(vec![exported("last")]));
+
+        let execution_mock = MockTaskExecutionPort::new();
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+        let id_mock = MockIdGeneratorPort::new();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        let mut ids = vec![];
+        let count = service.export_tasks(&mut |task| {
+            ids.push(task.task.id);
+            Ok(())
+        }).unwrap();
+        assert_eq!(count, TRANSFER_PAGE_SIZE + 1);
+        assert_eq!(ids.first().map(String::as_str), Some("id-0"));
+        assert_eq!(ids.last().map(String::as_str), Some("last"));
+    }
+
+    #[test]
+    fn test_task_scheduler_import_tasks_should_write_storage_by_pages_until_invalid_task() {
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_import()
+            .withf(|tasks| tasks.len() == TRANSFER_PAGE_SIZE)
+            .times(1)
+            // The tasks already stored are skipped
+            .returning(|tasks| Ok(tasks.len() - 1));
+
+        let execution_mock = MockTaskExecutionPort::new();
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+        let id_mock = MockIdGeneratorPort::new();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        let tasks = (0..TRANSFER_PAGE_SIZE).map(|index| Ok(exported(&format!("id-{}", index))))
+            .chain(vec![Err(anyhow!("Invalid line")), Ok(exported("last"))]);
+        // The first page is imported before the invalid task is read
+        assert!(service.import_tasks(tasks).is_err());
+    }
+
+    // Hooks accepting every call, for the tests which don't check them
+    fn hooks_mock() -> MockHookPort {
+        let mut hooks_mock = MockHookPort::new();
+        hooks_mock.expect_run()
+            .returning(|_, _, _| Ok(()));
+        hooks_mock
+    }
+
+    // Event store accepting every event, for the tests which don't check them
+    fn events_mock() -> MockEventStorePort {
+        let mut events_mock = MockEventStorePort::new();
+        events_mock.expect_append()
+            .returning(|_, _| Ok(()));
+        events_mock.expect_events()
+            .returning(|_| Ok(vec![]));
+        events_mock
+    }
+
+    fn exported(id: &str) -> ExportedTask {
+        ExportedTask {
+            task: task(input("backup", "tar czf backup.tgz /data", vec![]), id.to_string()),
+            status: TaskStatus::Scheduled,
+            completed_at: None,
+            artifacts: vec![],
+            hook_failures: vec![],
+        }
+    }
+
+    fn input(name: &str, command: &str, dependencies: Vec<&str>) -> TaskInput {
+        TaskInput {
+            name: Some(name.to_string()),
+            command: command.to_string(),
+            env: None,
+            timeout: None,
+            dependencies: dependencies.into_iter().map(String::from).collect(),
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
+        }
+    }
+
+    #[test]
+    fn test_order_by_dependencies_should_put_dependencies_first() {
+        let ordered = order_by_dependencies(vec![
+            input("report", "make report", vec!["extract", "clean"]),
+            input("extract", "make extract", vec!["clean"]),
+            input("clean", "make clean", vec![]),
+        ]).unwrap();
+        assert_eq!(ordered.iter().map(|input| input.name.clone().unwrap()).collect::<Vec<String>>(), vec!["clean", "extract", "report"]);
+    }
+
+    #[test]
+    fn test_order_by_dependencies_should_reject_invalid_tasks() {
+        assert_eq!(format!("{}", order_by_dependencies(vec![input("a", "ls", vec!["b"]), input("b", "ls", vec!["a"])]).unwrap_err()),
+                   "Invalid task definition : Cyclic dependencies between tasks a, b");
+        assert_eq!(format!("{}", order_by_dependencies(vec![input("a", "ls", vec!["missing"])]).unwrap_err()),
+                   "Invalid task definition : Task a depends on unknown task missing");
+        assert_eq!(format!("{}", order_by_dependencies(vec![input("a", "ls", vec![]), input("a", "pwd", vec![])]).unwrap_err()),
+                   "Invalid task definition : Task name a is defined more than once");
+        assert_eq!(format!("{}", order_by_dependencies(vec![input("a", "  ", vec![])]).unwrap_err()),
+                   "Invalid task definition : Task a has an empty command");
+    }
+
+    #[test]
+    fn test_task_scheduler_schedule_tasks_should_skip_dependents_of_failed_task() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Error("failed".into(), Some(2))));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_save_all()
+            .times(1)
+            .returning(Ok);
+        storage_mock.expect_complete()
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let mut id_mock = MockIdGeneratorPort::new();
+        id_mock.expect_generate_id()
+            .times(2)
+            .returning(|| "test_id".to_string());
+
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        assert_eq!(service.schedule_tasks(vec![input("report", "make report", vec!["extract"]), input("extract", "make extract", vec![])]).unwrap(),
+                   vec![TaskId::Name("extract".to_string()), TaskId::Name("report".to_string())]);
+    }
+
+    #[test]
+    fn test_task_scheduler_schedule_tasks_should_continue_after_execution_error() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .withf(|task| task.command == "make extract")
+            .times(1)
+            .returning(|_| Err(anyhow!("No such file or directory")));
+        execution_mock.expect_execute()
+            .withf(|task| task.command == "make clean")
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Success("cleaned".into())));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_save_all()
+            .times(1)
+            .returning(Ok);
+        storage_mock.expect_complete()
+            .withf(|task, status| task.command == "make extract" && matches!(status, TaskStatus::Error(_, None)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        storage_mock.expect_complete()
+            .withf(|task, status| task.command == "make report" && matches!(status, TaskStatus::Error(_, None)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        storage_mock.expect_complete()
+            .withf(|task, status| task.command == "make clean" && matches!(status, TaskStatus::Success(_)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut id_mock = MockIdGeneratorPort::new();
+        id_mock.expect_generate_id()
+            .times(3)
+            .returning(|| "test_id".to_string());
+
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        assert_eq!(service.schedule_tasks(vec![input("extract", "make extract", vec![]), input("report", "make report", vec!["extract"]), input("clean", "make clean", vec![])]).unwrap(),
+                   vec![TaskId::Name("extract".to_string()), TaskId::Name("report".to_string()), TaskId::Name("clean".to_string())]);
+    }
+
+    fn backup_template() -> TaskTemplate {
+        TaskTemplate {
+            name: "backup".to_string(),
+            command: "pg_dump {{ db }} -f /backup/{{db}}.sql".to_string(),
+            env: Some(HashMap::unit("PGHOST".to_string(), "{{host}}".to_string())),
+            timeout: None,
+        }
+    }
+
+    fn template_input(params: Vec<(&str, &str)>) -> TemplateInput {
+        TemplateInput {
+            template: "backup".to_string(),
+            name: None,
+            params: params.into_iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_instantiate_should_bound_the_timeout_of_the_template() {
+        let template = TaskTemplate { timeout: Some(Duration::from_secs(60)), ..backup_template() };
+        let params = || template_input(vec![("db", "orders"), ("host", "localhost")]);
+        assert_eq!(instantiate(&template, TemplateInput { timeout: Some(Duration::from_secs(5)), ..params() }).unwrap().timeout, Some(Duration::from_secs(5)));
+        assert_eq!(instantiate(&template, TemplateInput { timeout: Some(Duration::from_secs(120)), ..params() }).unwrap().timeout, Some(Duration::from_secs(60)));
+        assert_eq!(instantiate(&backup_template(), TemplateInput { timeout: Some(Duration::from_secs(5)), ..params() }).unwrap().timeout, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_instantiate_should_fill_placeholders() {
+        let input = instantiate(&backup_template(), template_input(vec![("db", "orders"), ("host", "localhost")])).unwrap();
+        assert_eq!(input.command, "pg_dump orders -f /backup/orders.sql");
+        assert_eq!(input.env, Some(HashMap::unit("PGHOST".to_string(), "localhost".to_string())));
+    }
+
+    #[test]
+    fn test_instantiate_should_reject_missing_and_unknown_params() {
+        assert_eq!(format!("{}", instantiate(&backup_template(), template_input(vec![("db", "orders")])).unwrap_err()),
+                   "Invalid task definition : Missing value for parameter host");
+        assert_eq!(format!("{}", instantiate(&backup_template(), template_input(vec![("db", "orders"), ("host", "localhost"), ("user", "admin")])).unwrap_err()),
+                   "Invalid task definition : Parameter user is not used by template backup");
+        assert_eq!(format!("{}", instantiate(&backup_template(), template_input(vec![("db", "orders -f /etc/passwd"), ("host", "localhost")])).unwrap_err()),
+                   "Invalid task definition : Value of parameter db used in the command can't contain whitespace");
+        // The env values are not split
+        assert!(instantiate(&backup_template(), template_input(vec![("db", "orders"), ("host", "db host")])).is_ok());
+    }
+
+    #[test]
+    fn test_validate_template_should_reject_malformed_placeholders() {
+        let mut template = backup_template();
+        template.command = "pg_dump {{db".to_string();
+        assert_eq!(format!("{}", validate_template(&template).unwrap_err()),
+                   "Invalid task definition : Unclosed placeholder in pg_dump {{db");
+        template.command = "pg_dump {{}}".to_string();
+        assert_eq!(format!("{}", validate_template(&template).unwrap_err()),
+                   "Invalid task definition : Empty placeholder in pg_dump {{}}");
+    }
+
+    #[test]
+    fn test_task_scheduler_schedule_template_should_execute_instantiated_task() {
+        let mut execution_mock = MockTaskExecutionPort::new();
+        execution_mock.expect_execute()
+            .withf(|task| task.command == "pg_dump orders -f /backup/orders.sql")
+            .times(1)
+            .returning(|_| Ok(TaskStatus::Success("Coucou".into())));
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_template()
+            .times(1)
+            .returning(|_| Ok(backup_template()));
+        storage_mock.expect_save()
+            .times(1)
+            .returning(Ok);
+        storage_mock.expect_complete()
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let mut id_mock = MockIdGeneratorPort::new();
+        id_mock.expect_generate_id()
+            .times(1)
+            .returning(|| "test_id".to_string());
+
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        assert_eq!(service.schedule_template(template_input(vec![("db", "orders"), ("host", "localhost")])).unwrap(), TaskId::Id("test_id".to_string()));
+    }
+
+    #[test]
+    fn test_task_scheduler_cancel_tasks_should_only_cancel_scheduled_tasks() {
+        let execution_mock = MockTaskExecutionPort::new();
+
+        let mut storage_mock = MockTaskStoragePort::new();
+        storage_mock.expect_find()
+            .times(1)
+            .returning(|_| {
+                let task = |id: &str| Task {
+                    id: id.to_string(),
+                    name: None,
+                    command: "ls /home".to_string(),
+                    env: None,
+                    timeout: None,
+                    labels: HashMap::unit("batch".to_string(), "42".to_string()),
+                    working_dir: None,
+                    stdin: None,
+                    umask: None,
+                    limits: ResourceLimits::default(),
+                    sandboxed: false,
+                    credentials: None,
+                    container: None,
+                    host: None,
+                    wasm: None,
+                    executor: None,
+                    artifacts: vec![],
+                    hooks: TaskHooks::default(),
+                };
+                Ok(vec![(task("scheduled"), TaskStatus::Scheduled), (task("done"), TaskStatus::Success("Coucou".into()))])
+            });
+        storage_mock.expect_complete()
+            .withf(|task, status| task.id == "scheduled" && matches!(status, TaskStatus::Cancelled))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let id_mock = MockIdGeneratorPort::new();
+
+        let artifacts_mock = MockArtifactStorePort::new();
+        let hooks_mock = hooks_mock();
+        let mut events_mock = events_mock();
+
+        let mut service = TaskScheduler::new(&mut storage_mock, &mut events_mock, &execution_mock, &artifacts_mock, &hooks_mock, &id_mock);
+
+        assert_eq!(service.cancel_tasks(&"batch=42".parse().unwrap()).unwrap(), vec![TaskId::Id("scheduled".to_string())]);
+        assert!(service.cancel_tasks(&LabelSelector::default()).is_err());
+    }
+
+    #[test]
+    fn test_validate_input_should_reject_invalid_process_settings() {
+        let mut task_input = input("a", "ls", vec![]);
+        task_input.working_dir = Some(PathBuf::from("relative/dir"));
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a working directory relative/dir is not an absolute path");
+        task_input.working_dir = Some(PathBuf::from("/tmp"));
+        task_input.umask = Some(0o1777);
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a umask 1777 is not a valid permission mask");
+        task_input.umask = Some(0o022);
+        assert!(validate_input(&task_input).is_ok());
+        task_input.limits.open_files = Some(0);
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a resource limits must be greater than 0");
+        task_input.limits.open_files = Some(64);
+        task_input.host = Some(" ".to_string());
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a has an empty host");
+        task_input.host = None;
+        task_input.executor = Some(String::new());
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a has an empty executor");
+        task_input.executor = None;
+        task_input.hooks.post = Some("".to_string());
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a has an empty hook");
+        task_input.hooks.post = Some("umount /mnt/data".to_string());
+        task_input.sandboxed = true;
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a hooks can't be combined with a sandbox or a user");
+    }
+
+    #[test]
+    fn test_validate_input_should_reject_relative_container_paths() {
+        let mut task_input = input("a", "ls", vec![]);
+        task_input.container = Some(Container {
+            rootfs: PathBuf::from("/images/busybox"),
+            mounts: vec![Mount { source: PathBuf::from("data"), destination: PathBuf::from("/data"), read_only: true }],
+        });
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a container path data is not an absolute path");
+        task_input.container.as_mut().unwrap().mounts[0].source = PathBuf::from("/srv/data");
+        assert!(validate_input(&task_input).is_ok());
+        task_input.sandboxed = true;
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a container can't be sandboxed");
+    }
+
+    #[test]
+    fn test_validate_input_should_reject_wasm_module_with_other_isolation() {
+        let mut task_input = input("a", "module.wasm", vec![]);
+        task_input.wasm = Some(WasmModule { source: WasmSource::Blob(vec![]), dir: None, fuel: Some(1_000) });
+        assert!(validate_input(&task_input).is_ok());
+        task_input.sandboxed = true;
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a WASI module can't be combined with a container, a host, a sandbox or a user");
+        task_input.sandboxed = false;
+        task_input.wasm = Some(WasmModule { source: WasmSource::Path(PathBuf::from("module.wasm")), dir: None, fuel: None });
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a WASI module module.wasm is not an absolute path");
+    }
+
+    #[test]
+    fn test_validate_input_should_reject_invalid_artifacts() {
+        let mut task_input = input("a", "make report", vec![]);
+        task_input.artifacts = vec!["out/*.pdf".to_string()];
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a needs a working directory to collect artifacts");
+        task_input.working_dir = Some(PathBuf::from("/tmp/reports"));
+        assert!(validate_input(&task_input).is_ok());
+        task_input.artifacts.push("../secrets/*".to_string());
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a artifact pattern ../secrets/* must stay in the working directory");
+        task_input.artifacts = vec!["/etc/passwd".to_string()];
+        assert!(validate_input(&task_input).is_err());
+        task_input.artifacts = vec!["*.pdf".to_string()];
+        task_input.host = Some("build1".to_string());
+        assert_eq!(format!("{}", validate_input(&task_input).unwrap_err()),
+                   "Invalid task definition : Task a artifacts can't be collected from a container or a remote host");
+    }
 }
\ No newline at end of file
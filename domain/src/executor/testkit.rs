@@ -0,0 +1,358 @@
+use im::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::executor::model::model::{Artifact, Container, Credentials, ExportedTask, HookFailure, HookStage, LabelSelector, Mount, ResourceLimits, SearchQuery, Task, TaskHooks, TaskId, TaskOutcome, TaskOutput, TaskStatus, WasmModule, WasmSource};
+use crate::executor::ports::secondary::TaskStoragePort;
+
+// Behaviour expected from every TaskStoragePort implementation, the adapters run it from their tests
+//
+// Ids are unique, a name resolves to the last task saved with it and a missing task is an error
+
+const CONCURRENT_WRITERS: usize = 8;
+const TASKS_BY_WRITER: usize = 10;
+
+// Every check is run on a new empty storage given by the factory
+pub fn storage_conformance<S, F>(new_storage: F)
+    where S: TaskStoragePort + Send + 'static, F: Fn() -> S {
+    save_should_schedule_task(&mut new_storage());
+    complete_should_update_status(&mut new_storage());
+    complete_should_only_update_given_task(&mut new_storage());
+    name_should_resolve_to_last_saved_task(&mut new_storage());
+    save_should_reject_duplicate_id(&mut new_storage());
+    save_all_should_reject_duplicate_ids_without_saving(&mut new_storage());
+    missing_task_should_be_an_error(&mut new_storage());
+    completed_should_list_tasks_with_completion_time(&mut new_storage());
+    delete_should_remove_tasks(&mut new_storage());
+    delete_should_return_digests_no_longer_referred_to(&mut new_storage());
+    export_should_page_tasks_in_saved_order(&mut new_storage());
+    import_should_restore_exported_tasks(&mut new_storage());
+    import_should_skip_stored_ids(&mut new_storage());
+    search_should_find_words_in_names_commands_and_outputs(&mut new_storage());
+    concurrent_updates_should_not_be_lost(new_storage());
+}
+
+pub fn save_should_schedule_task(storage: &mut dyn TaskStoragePort) {
+    let saved = storage.save(task("first", Some("report"))).expect("Error saving task");
+    assert_eq!(saved.id, "first");
+    assert_status(storage.status(TaskId::Id("first".to_string())), TaskStatus::Scheduled);
+    assert_status(storage.status(TaskId::Name("report".to_string())), TaskStatus::Scheduled);
+    let found = storage.find(&LabelSelector::default()).expect("Error finding tasks");
+    assert_eq!(found.iter().map(|(task, _)| (task.id.as_str(), task.command.as_str())).collect::<Vec<(&str, &str)>>(), vec![("first", "echo first")]);
+}
+
+pub fn complete_should_update_status(storage: &mut dyn TaskStoragePort) {
+    let saved = storage.save(task("first", Some("report"))).expect("Error saving task");
+    let truncated = TaskOutput { content: b"head tail".to_vec(), truncated: 12, log: Some("/var/log/tasc/first.log".into()) };
+    for status in [TaskStatus::Success("done".into()), TaskStatus::Error(truncated.clone(), Some(2)), TaskStatus::Success(truncated), TaskStatus::Cancelled] {
+        storage.complete(&saved, status.clone()).expect("Error completing task");
+        assert_status(storage.status(TaskId::Id("first".to_string())), status.clone());
+        assert_status(storage.status(TaskId::Name("report".to_string())), status);
+    }
+}
+
+// Tasks sharing a name are still completed by id
+pub fn complete_should_only_update_given_task(storage: &mut dyn TaskStoragePort) {
+    let tasks = storage.save_all(vec![task("first", Some("report")), task("second", Some("report")), task("third", None)]).expect("Error saving tasks");
+    storage.complete(&tasks[1], TaskStatus::Success("second".into())).expect("Error completing task");
+    assert_status(storage.status(TaskId::Id("first".to_string())), TaskStatus::Scheduled);
+    assert_status(storage.status(TaskId::Id("second".to_string())), TaskStatus::Success("second".into()));
+    assert_status(storage.status(TaskId::Id("third".to_string())), TaskStatus::Scheduled);
+    storage.complete(&tasks[0], TaskStatus::Cancelled).expect("Error completing task");
+    assert_status(storage.status(TaskId::Id("first".to_string())), TaskStatus::Cancelled);
+    assert_status(storage.status(TaskId::Id("second".to_string())), TaskStatus::Success("second".into()));
+}
+
+pub fn name_should_resolve_to_last_saved_task(storage: &mut dyn TaskStoragePort) {
+    let first = storage.save(task("first", Some("report"))).expect("Error saving task");
+    storage.complete(&first, TaskStatus::Success("first".into())).expect("Error completing task");
+    storage.save(task("second", Some("report"))).expect("Error saving task");
+    assert_status(storage.status(TaskId::Name("report".to_string())), TaskStatus::Scheduled);
+    let tasks = storage.save_all(vec![task("third", Some("backup")), task("fourth", Some("backup"))]).expect("Error saving tasks");
+    storage.complete(&tasks[1], TaskStatus::Cancelled).expect("Error completing task");
+    assert_status(storage.status(TaskId::Name("backup".to_string())), TaskStatus::Cancelled);
+    assert_eq!(storage.resolve(TaskId::Name("report".to_string())).expect("Error resolving name"), "second");
+    assert_eq!(storage.resolve(TaskId::Name("backup".to_string())).expect("Error resolving name"), "fourth");
+    assert!(storage.resolve(TaskId::Name("missing".to_string())).is_err(), "A name without task must not be resolved");
+}
+
+pub fn save_should_reject_duplicate_id(storage: &mut dyn TaskStoragePort) {
+    storage.save(task("first", Some("report"))).expect("Error saving task");
+    let mut duplicate = task("first", Some("backup"));
+    duplicate.command = "echo duplicate".to_string();
+    assert!(storage.save(duplicate).is_err(), "A task with the id of a saved one must be rejected");
+    assert_status(storage.status(TaskId::Name("report".to_string())), TaskStatus::Scheduled);
+    assert!(storage.status(TaskId::Name("backup".to_string())).is_err(), "A rejected task must not be found by its name");
+    let found = storage.find(&LabelSelector::default()).expect("Error finding tasks");
+    assert_eq!(found.iter().map(|(task, _)| task.command.as_str()).collect::<Vec<&str>>(), vec!["echo first"]);
+}
+
+// A batch is saved entirely or not at all
+pub fn save_all_should_reject_duplicate_ids_without_saving(storage: &mut dyn TaskStoragePort) {
+    storage.save(task("first", None)).expect("Error saving task");
+    assert!(storage.save_all(vec![task("second", None), task("first", None)]).is_err(), "A batch with the id of a saved task must be rejected");
+    assert!(storage.save_all(vec![task("third", None), task("third", None)]).is_err(), "A batch with a duplicated id must be rejected");
+    assert!(storage.status(TaskId::Id("second".to_string())).is_err(), "No task of a rejected batch must be saved");
+    assert!(storage.status(TaskId::Id("third".to_string())).is_err(), "No task of a rejected batch must be saved");
+    assert_eq!(storage.find(&LabelSelector::default()).expect("Error finding tasks").len(), 1);
+}
+
+pub fn missing_task_should_be_an_error(storage: &mut dyn TaskStoragePort) {
+    storage.save(task("first", Some("report"))).expect("Error saving task");
+    assert!(storage.status(TaskId::Id("missing".to_string())).is_err(), "The status of a missing id must be an error");
+    assert!(storage.status(TaskId::Name("missing".to_string())).is_err(), "The status of a missing name must be an error");
+    assert!(storage.complete(&task("missing", Some("report")), TaskStatus::Cancelled).is_err(), "Completing a missing task must be an error");
+    assert_status(storage.status(TaskId::Name("report".to_string())), TaskStatus::Scheduled);
+}
+
+pub fn completed_should_list_tasks_with_completion_time(storage: &mut dyn TaskStoragePort) {
+    let tasks = storage.save_all(vec![task("first", Some("report")), task("second", None)]).expect("Error saving tasks");
+    // Storages may keep the time in milliseconds
+    let before = SystemTime::now() - Duration::from_secs(1);
+    storage.complete(&tasks[0], TaskStatus::Error("failed".into(), Some(1))).expect("Error completing task");
+    let after = SystemTime::now() + Duration::from_secs(1);
+    let completed = storage.completed().expect("Error listing completed tasks");
+    assert_eq!(completed.len(), 1, "Only the completed tasks must be listed");
+    assert_eq!((completed[0].id.as_str(), completed[0].name.as_deref(), completed[0].outcome), ("first", Some("report"), TaskOutcome::Error));
+    let completed_at = completed[0].completed_at.expect("The completion time must be recorded");
+    assert!(before <= completed_at && completed_at <= after, "The completion time must be the one of the update");
+}
+
+// Names resolve to the last task left with them
+pub fn delete_should_remove_tasks(storage: &mut dyn TaskStoragePort) {
+    let tasks = storage.save_all(vec![task("first", Some("report")), task("second", Some("report")), task("third", None)]).expect("Error saving tasks");
+    storage.complete(&tasks[1], TaskStatus::Success("second".into())).expect("Error completing task");
+    storage.delete(&["second".to_string(), "missing".to_string()]).expect("Error deleting tasks");
+    assert!(storage.status(TaskId::Id("second".to_string())).is_err(), "A deleted task must not be found");
+    assert_status(storage.status(TaskId::Name("report".to_string())), TaskStatus::Scheduled);
+    assert!(storage.completed().expect("Error listing completed tasks").is_empty());
+    let found = storage.find(&LabelSelector::default()).expect("Error finding tasks");
+    let mut ids: Vec<&str> = found.iter().map(|(task, _)| task.id.as_str()).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec!["first", "third"]);
+    storage.delete(&["first".to_string()]).expect("Error deleting tasks");
+    assert!(storage.status(TaskId::Name("report".to_string())).is_err(), "A name without task must not be found");
+}
+
+// Contents are stored once for identical artifacts, they can be removed once no task refers to them
+pub fn delete_should_return_digests_no_longer_referred_to(storage: &mut dyn TaskStoragePort) {
+    let tasks = storage.save_all(vec![task("first", None), task("second", None), task("third", None)]).expect("Error saving tasks");
+    let artifact = |path: &str, digest: &str| Artifact { path: PathBuf::from(path), digest: digest.repeat(32), size: 12 };
+    storage.save_artifacts(&tasks[0], vec![artifact("report.txt", "0a"), artifact("shared.txt", "1b")]).expect("Error saving artifacts");
+    storage.save_artifacts(&tasks[1], vec![artifact("copy.txt", "1b")]).expect("Error saving artifacts");
+    storage.save_artifacts(&tasks[2], vec![artifact("other.txt", "2c")]).expect("Error saving artifacts");
+    let mut digests = storage.delete(&["first".to_string(), "missing".to_string()]).expect("Error deleting tasks");
+    digests.sort_unstable();
+    assert_eq!(digests, vec!["0a".repeat(32)], "The content shared with a stored task must be kept");
+    let mut digests = storage.delete(&["second".to_string(), "third".to_string()]).expect("Error deleting tasks");
+    digests.sort_unstable();
+    assert_eq!(digests, vec!["1b".repeat(32), "2c".repeat(32)]);
+}
+
+pub fn export_should_page_tasks_in_saved_order(storage: &mut dyn TaskStoragePort) {
+    storage.save_all(vec![task("first", Some("report")), task("second", None)]).expect("Error saving tasks");
+    let third = storage.save(task("third", Some("report"))).expect("Error saving task");
+    storage.complete(&third, TaskStatus::Error("failed".into(), Some(3))).expect("Error completing task");
+    let artifact = Artifact { path: PathBuf::from("out/report.txt"), digest: "0a1b2c".to_string(), size: 12 };
+    storage.save_artifacts(&third, vec![artifact.clone()]).expect("Error saving artifacts");
+    let failure = HookFailure { stage: HookStage::Post, message: "exit status 1".to_string() };
+    storage.save_hook_failure(&third, failure.clone()).expect("Error saving hook failure");
+
+    let first_page = storage.export(0, 2).expect("Error exporting tasks");
+    assert_eq!(first_page.iter().map(|exported| exported.task.id.as_str()).collect::<Vec<&str>>(), vec!["first", "second"]);
+    assert_status(Ok(first_page[0].status.clone()), TaskStatus::Scheduled);
+    assert_eq!(first_page[0].completed_at, None);
+    let last_page = storage.export(2, 2).expect("Error exporting tasks");
+    assert_eq!(last_page.len(), 1);
+    assert_eq!(last_page[0].task, third);
+    assert_status(Ok(last_page[0].status.clone()), TaskStatus::Error("failed".into(), Some(3)));
+    assert!(last_page[0].completed_at.is_some(), "The completion time must be exported");
+    assert_eq!(last_page[0].artifacts, vec![artifact]);
+    assert_eq!(last_page[0].hook_failures, vec![failure]);
+    assert!(storage.export(3, 2).expect("Error exporting tasks").is_empty());
+}
+
+// The status, output and completion time are the exported ones, not the ones of a new task
+pub fn import_should_restore_exported_tasks(storage: &mut dyn TaskStoragePort) {
+    let imported = vec![exported_task(), ExportedTask {
+        task: task("second", Some("archive")),
+        status: TaskStatus::Cancelled,
+        completed_at: Some(UNIX_EPOCH + Duration::from_millis(1_600_000_100_000)),
+        artifacts: vec![],
+        hook_failures: vec![],
+    }];
+    assert_eq!(storage.import(imported.clone()).expect("Error importing tasks"), 2);
+
+    let exported = storage.export(0, 10).expect("Error exporting tasks");
+    assert_eq!(exported.len(), 2);
+    for (actual, expected) in exported.into_iter().zip(imported) {
+        assert_eq!(actual.task, expected.task);
+        assert_status(Ok(actual.status), expected.status);
+        assert_eq!(actual.completed_at, expected.completed_at);
+        assert_eq!(actual.artifacts, expected.artifacts);
+        assert_eq!(actual.hook_failures, expected.hook_failures);
+    }
+    assert_status(storage.status(TaskId::Name("archive".to_string())), TaskStatus::Cancelled);
+    let completed = storage.completed().expect("Error listing completed tasks");
+    assert_eq!(completed.iter().map(|task| (task.id.as_str(), task.completed_at)).collect::<Vec<(&str, Option<SystemTime>)>>(),
+               vec![("first", Some(UNIX_EPOCH + Duration::from_millis(1_600_000_000_123))), ("second", Some(UNIX_EPOCH + Duration::from_millis(1_600_000_100_000)))]);
+}
+
+// Importing the same export twice keeps the tasks stored the first time
+pub fn import_should_skip_stored_ids(storage: &mut dyn TaskStoragePort) {
+    storage.save(task("first", Some("report"))).expect("Error saving task");
+    let mut duplicate = exported_task();
+    duplicate.task.name = Some("duplicate".to_string());
+    let new = ExportedTask { task: task("second", Some("report")), status: TaskStatus::Scheduled, completed_at: None, artifacts: vec![], hook_failures: vec![] };
+    assert_eq!(storage.import(vec![duplicate.clone(), new.clone()]).expect("Error importing tasks"), 1);
+    assert_eq!(storage.import(vec![duplicate, new]).expect("Error importing tasks"), 0);
+    assert_status(storage.status(TaskId::Id("first".to_string())), TaskStatus::Scheduled);
+    assert!(storage.status(TaskId::Name("duplicate".to_string())).is_err(), "A skipped task must not be found by its name");
+    assert!(storage.artifacts(TaskId::Id("first".to_string())).expect("Error loading artifacts").is_empty());
+    assert_eq!(storage.export(0, 10).expect("Error exporting tasks").iter().map(|exported| exported.task.id.as_str()).collect::<Vec<&str>>(), vec!["first", "second"]);
+}
+
+// The index of the storage must follow the outputs as the tasks complete and are deleted
+pub fn search_should_find_words_in_names_commands_and_outputs(storage: &mut dyn TaskStoragePort) {
+    let tasks = storage.save_all(vec![task("first", Some("nightly-backup")), task("second", None), task("third", None), task("fourth", None)]).expect("Error saving tasks");
+    storage.complete(&tasks[1], TaskStatus::Error("curl: (7) Failed to connect: Connection refused".into(), Some(7))).expect("Error completing task");
+    storage.complete(&tasks[2], TaskStatus::Success("refused connection".into())).expect("Error completing task");
+    let mut search = |query: &str| storage.search(&query.parse::<SearchQuery>().unwrap()).expect("Error searching tasks")
+        .into_iter()
+        .map(|(task, _)| task.id)
+        .collect::<Vec<String>>();
+    assert_eq!(search("connection refused"), vec!["second"]);
+    assert_eq!(search("NIGHTLY backup"), vec!["first"]);
+    assert_eq!(search("echo fourth"), vec!["fourth"]);
+    assert_eq!(search("echo"), vec!["first", "second", "third", "fourth"]);
+    assert!(search("refused again").is_empty());
+
+    storage.complete(&tasks[3], TaskStatus::Success("connection refused again".into())).expect("Error completing task");
+    storage.delete(&["second".to_string()]).expect("Error deleting tasks");
+    let found = storage.search(&"connection refused".parse::<SearchQuery>().unwrap()).expect("Error searching tasks");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0, tasks[3]);
+    assert_status(Ok(found[0].1.clone()), TaskStatus::Success("connection refused again".into()));
+}
+
+// Storages opened once per process are shared by the writers behind a lock, their interleaved updates must all be kept
+pub fn concurrent_updates_should_not_be_lost<S>(storage: S)
+    where S: TaskStoragePort + Send + 'static {
+    let storage = Arc::new(Mutex::new(storage));
+    let shared = storage.lock().unwrap().save(task("shared", Some("shared"))).expect("Error saving task");
+    let writers: Vec<thread::JoinHandle<()>> = (0..CONCURRENT_WRITERS)
+        .map(|writer| {
+            let storage = Arc::clone(&storage);
+            let shared = shared.clone();
+            thread::spawn(move || (0..TASKS_BY_WRITER).for_each(|index| {
+                let id = format!("writer-{}-{}", writer, index);
+                let saved = storage.lock().unwrap().save(task(&id, None)).expect("Error saving task");
+                storage.lock().unwrap().complete(&saved, TaskStatus::Success(id.clone().into())).expect("Error completing task");
+                storage.lock().unwrap().complete(&shared, TaskStatus::Error(id.into(), Some(writer as i32))).expect("Error completing task");
+            }))
+        })
+        .collect();
+    writers.into_iter().for_each(|writer| writer.join().expect("Writer failed"));
+    assert_concurrent_updates(&mut *storage.lock().unwrap());
+}
+
+// Storages shared by several processes are written through independent handles on the same store, as each process opens its own
+pub fn concurrent_handles_should_not_lose_updates<S, F>(open_handle: F)
+    where S: TaskStoragePort, F: Fn() -> S + Send + Sync + 'static {
+    let open_handle = Arc::new(open_handle);
+    let shared = open_handle().save(task("shared", Some("shared"))).expect("Error saving task");
+    let writers: Vec<thread::JoinHandle<()>> = (0..CONCURRENT_WRITERS)
+        .map(|writer| {
+            let open_handle = Arc::clone(&open_handle);
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let mut storage = open_handle();
+                (0..TASKS_BY_WRITER).for_each(|index| {
+                    let id = format!("writer-{}-{}", writer, index);
+                    let saved = storage.save(task(&id, None)).expect("Error saving task");
+                    storage.complete(&saved, TaskStatus::Success(id.clone().into())).expect("Error completing task");
+                    storage.complete(&shared, TaskStatus::Error(id.into(), Some(writer as i32))).expect("Error completing task");
+                })
+            })
+        })
+        .collect();
+    writers.into_iter().for_each(|writer| writer.join().expect("Writer failed"));
+    assert_concurrent_updates(&mut open_handle());
+}
+
+fn assert_concurrent_updates(storage: &mut dyn TaskStoragePort) {
+    for writer in 0..CONCURRENT_WRITERS {
+        for index in 0..TASKS_BY_WRITER {
+            let id = format!("writer-{}-{}", writer, index);
+            assert_status(storage.status(TaskId::Id(id.clone())), TaskStatus::Success(id.into()));
+        }
+    }
+    match storage.status(TaskId::Name("shared".to_string())).expect("Error loading status") {
+        TaskStatus::Error(output, Some(code)) => assert_eq!(String::from_utf8_lossy(&output.content), format!("writer-{}-{}", code, TASKS_BY_WRITER - 1)),
+        other => panic!("The shared task must keep the last update of a writer, got {:?}", other),
+    }
+    assert_eq!(storage.find(&LabelSelector::default()).expect("Error finding tasks").len(), CONCURRENT_WRITERS * TASKS_BY_WRITER + 1);
+}
+
+// Task using every field of the model, completed at a time the storages keep to the millisecond
+fn exported_task() -> ExportedTask {
+    let mut exported = task("first", Some("backup"));
+    exported.env = Some(HashMap::unit("TARGET".to_string(), "/mnt/backup".to_string()));
+    exported.timeout = Some(Duration::from_secs(60));
+    exported.working_dir = Some(PathBuf::from("/data"));
+    exported.stdin = Some(b"yes\n\xff".to_vec());
+    exported.umask = Some(0o027);
+    exported.limits = ResourceLimits { cpu_time: Some(Duration::from_secs(30)), memory: Some(1 << 20), open_files: Some(64), processes: Some(8) };
+    exported.credentials = Some(Credentials { uid: 1000, gid: 1000, groups: vec![100, 101] });
+    exported.container = Some(Container { rootfs: PathBuf::from("/var/lib/rootfs"), mounts: vec![Mount { source: PathBuf::from("/data"), destination: PathBuf::from("/mnt"), read_only: true }] });
+    exported.wasm = Some(WasmModule { source: WasmSource::Blob(b"\0asm".to_vec()), dir: Some(PathBuf::from("/data")), fuel: Some(1000) });
+    exported.executor = Some("wasm".to_string());
+    exported.artifacts = vec!["*.tgz".to_string()];
+    exported.hooks = TaskHooks { pre: Some("mount /data".to_string()), post: Some("umount /data".to_string()) };
+    ExportedTask {
+        task: exported,
+        status: TaskStatus::Success(TaskOutput { content: b"head\n\xfftail".to_vec(), truncated: 42, log: Some(PathBuf::from("/var/log/tasc/first.log")) }),
+        completed_at: Some(UNIX_EPOCH + Duration::from_millis(1_600_000_000_123)),
+        artifacts: vec![Artifact { path: PathBuf::from("backup.tgz"), digest: "0a1b2c".to_string(), size: 12 }],
+        hook_failures: vec![HookFailure { stage: HookStage::Pre, message: "mount failed".to_string() }],
+    }
+}
+
+fn task(id: &str, name: Option<&str>) -> Task {
+    Task {
+        id: id.to_string(),
+        name: name.map(String::from),
+        command: format!("echo {}", id),
+        env: None,
+        timeout: None,
+        labels: HashMap::unit("suite".to_string(), "conformance".to_string())
+            .update("query".to_string(), "a=1;b=2".to_string()),
+        working_dir: None,
+        stdin: None,
+        umask: None,
+        limits: ResourceLimits::default(),
+        sandboxed: false,
+        credentials: None,
+        container: None,
+        host: None,
+        wasm: None,
+        executor: None,
+        artifacts: vec![],
+        hooks: TaskHooks::default(),
+    }
+}
+
+// TaskStatus has no equality, the outputs are compared with their metadata
+fn assert_status(actual: Result<TaskStatus, anyhow::Error>, expected: TaskStatus) {
+    match (actual.expect("Error loading status"), expected) {
+        (TaskStatus::Scheduled, TaskStatus::Scheduled) | (TaskStatus::Cancelled, TaskStatus::Cancelled) => (),
+        (TaskStatus::Success(actual), TaskStatus::Success(expected)) => assert_eq!(actual, expected),
+        (TaskStatus::Error(actual, actual_code), TaskStatus::Error(expected, expected_code)) => {
+            assert_eq!(actual, expected);
+            assert_eq!(actual_code, expected_code);
+        }
+        (actual, expected) => panic!("Expected status {:?}, got {:?}", expected, actual),
+    }
+}
\ No newline at end of file
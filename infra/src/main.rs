@@ -1,67 +1,348 @@
 extern crate blueprint_hexagonal_domain as domain;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
 #[macro_use]
 extern crate diesel;
-#[macro_use]
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
 extern crate diesel_migrations;
 
 use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::Error;
+use anyhow::{anyhow, Context, Error};
 use itertools::Itertools;
 use itertools::Chunks;
 
-use domain::executor::model::model::{TaskId, TaskStatus};
+use domain::executor::model::model::{CompletedTask, HookFailure, RecordedEvent, RetentionPolicy, Task, TaskId, TaskStatus};
 use domain::executor::ports::primary::TaskSchedulerPort;
 use domain::executor::service::task_execution::TaskScheduler;
 
-use crate::primary::cli::{CliOpt, parse_cli_opts, TaskRunOpt, TaskStatusOpt};
-use crate::secondary::adapter::execution::LocalExecutionAdapter;
+use crate::primary::cli::{CliOpt, DbOpt, parse_cli_opts, TaskRunOpt, TaskTemplateOpt};
+use crate::primary::export::{format_utc, read_tasks, TaskWriter};
+use crate::primary::manifest::load_manifest;
+use crate::primary::settings::RetentionConfiguration;
+use crate::secondary::adapter::artifacts::FileArtifactStoreAdapter;
+use crate::secondary::adapter::events::new_event_store_adapter;
+use crate::secondary::adapter::execution::{new_execution_adapter, new_task_hook_executor};
+use crate::secondary::adapter::hooks::ShellHookAdapter;
 use crate::secondary::adapter::id_generator::UUIDGeneratorAdapter;
-use crate::secondary::adapter::storage::new_storage_adapter;
-use diesel::IntoSql;
+use crate::secondary::adapter::storage::{new_schema_adapters, new_storage_adapter, SchemaAdapter};
 
 
 mod secondary;
 mod primary;
 
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const WAIT_TIMEOUT_EXIT_CODE: i32 = 124;
+
 fn main() -> Result<(), Error> {
     let configuration = primary::settings::load_settings()?;
+    // The adapters refuse a schema behind tasc, so it is managed before they are created
+    let opts = match parse_cli_opts() {
+        CliOpt::Db(db_input) => process::exit(manage_schemas(new_schema_adapters(&configuration.storage, &configuration.events)?, db_input)?),
+        opts => opts,
+    };
 
     let mut storage = new_storage_adapter(configuration.storage)?;
-    let execution = LocalExecutionAdapter::new();
+    let mut events = new_event_store_adapter(configuration.events)?;
+    let task_hooks = new_task_hook_executor(&configuration.execution, configuration.hooks.shell.clone());
+    let hooks = ShellHookAdapter::new(configuration.hooks.shell, configuration.hooks.pre, configuration.hooks.post, task_hooks);
+    let artifacts = FileArtifactStoreAdapter::new(configuration.artifacts, configuration.execution.output_logs.clone());
+    let execution = new_execution_adapter(configuration.execution)?;
     let id_generator = UUIDGeneratorAdapter::new();
     let service = TaskScheduler::new(
         storage.as_mut(),
+        events.as_mut(),
         execution.borrow(),
+        artifacts.borrow(),
+        hooks.borrow(),
         id_generator.borrow(),
     );
-    run(service)
+    let exit_code = run(service, opts, configuration.retention)?;
+    drop(storage);
+    drop(events);
+    process::exit(exit_code)
 }
 
-fn run(mut port: impl TaskSchedulerPort) -> Result<(), Error> {
-    match parse_cli_opts() {
-        CliOpt::Run(ref task_run_input) => port.schedule_task::<TaskRunOpt>(task_run_input.clone())
-            .map(|result|
-                if task_run_input.wait {
-                    if let Err(err) = port.task_status(result).map(display_task_status) {
-                        eprintln!("Error waiting status of task : {:?}", err)
-                    };
-                } else {
-                    match result {
-                        TaskId::Id(id) => println!("Task with id {} scheduled", id),
-                        TaskId::Name(name) => println!("Task with name {} scheduled", name),
+fn run(mut port: impl TaskSchedulerPort, opts: CliOpt, retention: RetentionConfiguration) -> Result<i32, Error> {
+    match opts {
+        CliOpt::Run(ref task_run_input) => {
+            // The task is run before its scheduling returns, the wait timeout bounds its timeout
+            let deadline = task_run_input.wait_timeout.map(|timeout| Instant::now() + Duration::from_secs(timeout));
+            match task_run_input.template {
+                Some(_) => port.schedule_template::<TaskRunOpt>(TaskRunOpt::clone(task_run_input)),
+                None => port.schedule_task::<TaskRunOpt>(TaskRunOpt::clone(task_run_input)),
+            }
+                .and_then(|result|
+                    if task_run_input.wait {
+                        wait_task_status(&mut port, result.clone(), deadline)
+                            .context("Error waiting status of task")
+                            .and_then(|status| match status {
+                                Some(status) => {
+                                    let exit_code = task_exit_code(&status);
+                                    display_task_status(status);
+                                    port.task_hook_failures(result)
+                                        .map(display_hook_failures)
+                                        .map(|_| exit_code)
+                                }
+                                None => {
+                                    eprintln!("Timeout waiting for the task completion");
+                                    Ok(WAIT_TIMEOUT_EXIT_CODE)
+                                }
+                            })
+                    } else {
+                        display_task_scheduled(result);
+                        Ok(0)
                     }
+                )
+        }
+        CliOpt::Status(task_status_input) => {
+            let id: TaskId = task_status_input.into();
+            port.task_status(id.clone())
+                .map(display_task_status)
+                .and_then(|_| port.task_hook_failures(id))
+                .map(display_hook_failures)
+                .map(|_| 0)
+        }
+        CliOpt::List(task_list_input) => port.list_tasks(&task_list_input.selector.unwrap_or_default())
+            .map(|tasks| {
+                tasks.into_iter().for_each(display_task_line);
+                0
+            }),
+        CliOpt::Search(task_search_input) => port.search_tasks(&task_search_input.query)
+            .map(|tasks| {
+                tasks.into_iter().for_each(display_task_line);
+                0
+            }),
+        CliOpt::Cancel(task_cancel_input) => port.cancel_tasks(&task_cancel_input.selector)
+            .map(|ids| {
+                ids.into_iter().for_each(|id| match id {
+                    TaskId::Id(id) => println!("Task with id {} cancelled", id),
+                    TaskId::Name(name) => println!("Task with name {} cancelled", name),
+                });
+                0
+            }),
+        CliOpt::Apply(task_apply_input) => load_manifest(&task_apply_input.file)
+            .and_then(|tasks| port.schedule_tasks(tasks))
+            .map(|ids| {
+                ids.into_iter().for_each(display_task_scheduled);
+                0
+            }),
+        CliOpt::Artifacts(task_artifacts_input) => match task_artifacts_input.get {
+            Some(ref path) => {
+                let destination = task_artifacts_input.output.clone()
+                    .unwrap_or_else(|| PathBuf::from(path.file_name().unwrap_or(path.as_os_str())));
+                port.extract_artifact(task_artifacts_input.task_id(), path, &destination)
+                    .map(|_| {
+                        println!("Artifact {} extracted to {}", path.display(), destination.display());
+                        0
+                    })
+            }
+            None => port.task_artifacts(task_artifacts_input.task_id())
+                .map(|artifacts| {
+                    artifacts.into_iter().for_each(|artifact| println!("{}\t{}\t{}", artifact.path.display(), artifact.size, artifact.digest));
+                    0
+                }),
+        },
+        CliOpt::Template(TaskTemplateOpt::Add(template_add_input)) => {
+            let name = template_add_input.name.clone();
+            port.create_template(template_add_input)
+                .map(|_| {
+                    println!("Template {} saved", name);
+                    0
+                })
+        }
+        CliOpt::Events(task_events_input) => port.task_events(&task_events_input.id)
+            .map(|events| {
+                events.into_iter().for_each(display_task_event);
+                0
+            }),
+        CliOpt::Purge(task_purge_input) => {
+            let policy = task_purge_input.policy().unwrap_or(retention.policy);
+            if policy.is_empty() {
+                return Err(anyhow!("No retention to apply, give --older-than or --max-per-name or set retention.max_age or retention.max_per_name in the settings"));
+            }
+            if task_purge_input.daemon {
+                purge_periodically(&mut port, &policy, retention.interval)
+            } else {
+                port.purge_tasks(&policy, task_purge_input.dry_run)
+                    .map(|purged| {
+                        let count = purged.len();
+                        purged.into_iter().for_each(display_completed_task);
+                        if task_purge_input.dry_run {
+                            println!("{} tasks would be purged", count);
+                        } else {
+                            println!("{} tasks purged", count);
+                        }
+                        0
+                    })
+            }
+        }
+        CliOpt::Export(task_export_input) => {
+            let output: Box<dyn Write> = match task_export_input.output {
+                Some(ref path) => Box::new(File::create(path).context(format!("Error creating {}", path.display()))?),
+                None => Box::new(io::stdout()),
+            };
+            let mut writer = TaskWriter::new(task_export_input.format, BufWriter::new(output))?;
+            port.export_tasks(&mut |task| writer.write(task))
+                .and_then(|count| writer.finish().map(|_| count))
+                .map(|count| {
+                    // The standard output may be the export itself
+                    eprintln!("{} tasks exported", count);
+                    0
+                })
+        }
+        CliOpt::Import(task_import_input) => {
+            let input: Box<dyn Read> = match task_import_input.file {
+                Some(ref path) => Box::new(File::open(path).context(format!("Error opening {}", path.display()))?),
+                None => Box::new(io::stdin()),
+            };
+            port.import_tasks(read_tasks(task_import_input.format, input))
+                .map(|count| {
+                    println!("{} tasks imported", count);
+                    0
+                })
+        }
+        CliOpt::Db(_) => unreachable!("tasc db is run before the adapters are created"),
+    }
+}
+
+fn manage_schemas(schemas: Vec<Box<dyn SchemaAdapter>>, command: DbOpt) -> Result<i32, Error> {
+    for schema in schemas {
+        match command {
+            DbOpt::Migrate => {
+                let applied = schema.migrate()?;
+                if applied.is_empty() {
+                    println!("Schema of the {} database is up to date", schema.database());
                 }
-            ),
-        CliOpt::Status(task_status_input) => port.task_status::<TaskStatusOpt>(task_status_input)
-            .map(display_task_status)
+                applied.into_iter().for_each(|name| println!("Migration {} applied to the {} database", name, schema.database()));
+            }
+            DbOpt::Status => schema.status()?.into_iter()
+                .for_each(|(name, applied)| println!("{}\t{}\t{}", schema.database(), name, if applied { "APPLIED" } else { "PENDING" })),
+            DbOpt::Rollback => println!("Migration {} reverted on the {} database", schema.rollback()?, schema.database()),
+        }
+    }
+    Ok(0)
+}
+
+// None once the deadline elapsed, a task killed at the deadline being completed with an error
+fn wait_task_status(port: &mut impl TaskSchedulerPort, id: TaskId, deadline: Option<Instant>) -> Result<Option<TaskStatus>, Error> {
+    loop {
+        let status = port.task_status(id.clone())?;
+        let elapsed = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        match status {
+            TaskStatus::Success(_) => return Ok(Some(status)),
+            _ if elapsed => return Ok(None),
+            _ if status.is_terminal() => return Ok(Some(status)),
+            _ => thread::sleep(WAIT_POLL_INTERVAL),
+        }
+    }
+}
+
+// Stands in for a daemon until tasc has one, a failed purge is retried at the next interval
+fn purge_periodically(port: &mut impl TaskSchedulerPort, policy: &RetentionPolicy, interval: Duration) -> Result<i32, Error> {
+    loop {
+        match port.purge_tasks(policy, false) {
+            Ok(purged) => println!("{} tasks purged", purged.len()),
+            Err(error) => eprintln!("Error purging tasks : {:#}", error),
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn task_exit_code(status: &TaskStatus) -> i32 {
+    match status {
+        TaskStatus::Success(_) => 0,
+        TaskStatus::Error(_, Some(exit_code)) => *exit_code,
+        TaskStatus::Error(_, None) | TaskStatus::Scheduled | TaskStatus::Cancelled => 1,
+    }
+}
+
+fn display_task_scheduled(id: TaskId) {
+    match id {
+        TaskId::Id(id) => println!("Task with id {} scheduled", id),
+        TaskId::Name(name) => println!("Task with name {} scheduled", name),
     }
 }
 
 fn display_task_status(status: TaskStatus) {
     match status {
-        TaskStatus::Success(stdout) => println!("Task was successfully run :\n {}", stdout.lines().into_iter().map(|line| format!("\t{}", line)).join("\n")),
+        TaskStatus::Success(stdout) => {
+            println!("Task was successfully run :\n {}", stdout.to_string().lines().into_iter().map(|line| format!("\t{}", line)).join("\n"));
+            if let Some(log) = stdout.log {
+                println!("Full output in {}", log.display());
+            }
+        }
         TaskStatus::Scheduled => println!("Task is scheduled"),
-        TaskStatus::Error(stderr) => eprintln!("Task was in error  :\n {}", stderr.lines().map(|line| format!("\t{}", line)).join("\n"))
+        TaskStatus::Error(stderr, _) => {
+            eprintln!("Task was in error  :\n {}", stderr.to_string().lines().map(|line| format!("\t{}", line)).join("\n"));
+            if let Some(log) = stderr.log {
+                eprintln!("Full output in {}", log.display());
+            }
+        }
+        TaskStatus::Cancelled => println!("Task was cancelled"),
+    }
+}
+
+// Hooks don't change the status of the task, their failures are shown beside it
+fn display_hook_failures(failures: Vec<HookFailure>) {
+    failures.into_iter().for_each(|failure| eprintln!("Hook {} failed : {}", failure.stage, failure.message));
+}
+
+fn display_task_line((task, status): (Task, TaskStatus)) {
+    let status = match status {
+        TaskStatus::Scheduled => "SCHEDULED",
+        TaskStatus::Success(_) => "SUCCESS",
+        TaskStatus::Error(_, _) => "ERROR",
+        TaskStatus::Cancelled => "CANCELLED",
+    };
+    let labels = task.labels.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .sorted()
+        .join(",");
+    println!("{}\t{}\t{}\t{}", task.id, task.name.unwrap_or_else(|| "-".to_string()), status, labels);
+}
+
+fn display_completed_task(task: CompletedTask) {
+    let completed_at = task.completed_at.map(format_utc).unwrap_or_else(|| "-".to_string());
+    println!("{}\t{}\t{}\t{}", task.id, task.name.unwrap_or_else(|| "-".to_string()), task.outcome.to_string().to_uppercase(), completed_at);
+}
+
+fn display_task_event(recorded: RecordedEvent) {
+    println!("{}\t{}\t{}\t{}", recorded.sequence, format_utc(recorded.at), recorded.actor, recorded.event);
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::executor::model::model::RetentionPolicy;
+    use std::env;
+    use structopt::StructOpt;
+    use crate::secondary::adapter::events::memory::InMemoryEventStoreAdapter;
+    use crate::secondary::adapter::execution::{LocalExecutionAdapter, OutputCapture};
+    use crate::secondary::adapter::storage::memory::InMemoryStorageAdapter;
+
+    fn run_cli(args: &[&str]) -> i32 {
+        let mut storage = InMemoryStorageAdapter::new();
+        let mut events = InMemoryEventStoreAdapter::new(String::from("tasc@host"));
+        let execution = LocalExecutionAdapter::new(None, None, OutputCapture::new(1024, None));
+        let artifacts = FileArtifactStoreAdapter::new(env::temp_dir().join("tasc-artifacts"), None);
+        let hooks = ShellHookAdapter::new(PathBuf::from("/bin/sh"), None, None, None);
+        let id_generator = UUIDGeneratorAdapter::new();
+        let service = TaskScheduler::new(&mut storage, &mut events, &execution, &artifacts, &hooks, &id_generator);
+        let retention = RetentionConfiguration { policy: RetentionPolicy::default(), interval: Duration::from_secs(3600) };
+        run(service, CliOpt::from_iter([&["tasc"], args].concat()), retention).unwrap()
+    }
+
+    #[test]
+    fn test_run_should_kill_the_waited_task_at_the_wait_timeout() {
+        let start = Instant::now();
+        assert_eq!(run_cli(&["run", "--wait", "--wait-timeout", "1", "sleep", "3"]), WAIT_TIMEOUT_EXIT_CODE);
+        assert!(start.elapsed() < Duration::from_secs(3), "The task must be killed at the deadline");
+        assert_eq!(run_cli(&["run", "--wait", "--wait-timeout", "5", "false"]), 1);
+        assert_eq!(run_cli(&["run", "--wait", "--wait-timeout", "5", "true"]), 0);
     }
-}
\ No newline at end of file
+}
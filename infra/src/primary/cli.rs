@@ -1,18 +1,110 @@
-use domain::executor::ports::primary::TaskInput;
-use domain::executor::model::model::TaskId;
+use domain::executor::ports::primary::{TaskInput, TemplateInput};
+use domain::executor::model::model::{Container, Credentials, LabelSelector, Mount, ResourceLimits, RetentionPolicy, SearchQuery, TaskHooks, TaskId, TaskOutcome, TaskTemplate, WasmModule, WasmSource};
+use structopt::clap::ArgGroup;
 use structopt::StructOpt;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::primary::export::ExportFormat;
+
+// Content of a file, structopt would read a Vec as several values
+type FileContent = Vec<u8>;
 
 #[derive(StructOpt, Debug, Clone)]
+#[structopt(group = ArgGroup::with_name("module"))]
 pub struct TaskRunOpt {
     /// Command to be executed by the task
-    #[structopt(required = true)]
+    #[structopt(required_unless = "template")]
     command: Vec<String>,
     /// Name of the task for later querying
     #[structopt(short, long)]
     name: Option<String>,
+    /// Template used to create the task instead of a command
+    #[structopt(short, long, conflicts_with = "command")]
+    pub template: Option<String>,
+    /// Parameter of the template as KEY=VALUE
+    #[structopt(short, long = "param", requires = "template", parse(try_from_str = parse_key_value), number_of_values = 1)]
+    params: Vec<(String, String)>,
+    /// Label of the task as KEY=VALUE
+    #[structopt(short, long = "label", parse(try_from_str = parse_key_value), number_of_values = 1)]
+    labels: Vec<(String, String)>,
+    /// Working directory of the command, relative to the current directory, or an absolute path of the image with --image
+    #[structopt(long)]
+    workdir: Option<PathBuf>,
+    /// Payload written on the standard input of the command
+    #[structopt(long, conflicts_with = "stdin-file")]
+    stdin: Option<String>,
+    /// File whose content is written on the standard input of the command
+    #[structopt(long, parse(try_from_str = read_file))]
+    stdin_file: Option<FileContent>,
+    /// Umask of the command in octal (e.g. 022)
+    #[structopt(long, parse(try_from_str = parse_umask))]
+    umask: Option<u32>,
+    /// Maximum CPU time of the command in seconds
+    #[structopt(long)]
+    cpu_time: Option<u64>,
+    /// Maximum memory of the command in bytes, with an optional K, M or G suffix
+    #[structopt(long, parse(try_from_str = parse_size))]
+    memory: Option<u64>,
+    /// Maximum number of files opened by the command
+    #[structopt(long)]
+    open_files: Option<u64>,
+    /// Maximum number of processes of the command
+    #[structopt(long)]
+    processes: Option<u64>,
+    /// Run the command in a sandbox with a read-only root and no network
+    #[structopt(long)]
+    sandbox: bool,
+    /// User id the command is run as, requires the executor to run as root
+    #[structopt(long, requires = "gid")]
+    uid: Option<u32>,
+    /// Group id the command is run as
+    #[structopt(long, requires = "uid")]
+    gid: Option<u32>,
+    /// Supplementary group id of the command
+    #[structopt(long = "group", requires = "uid", number_of_values = 1)]
+    groups: Vec<u32>,
+    /// Unpacked image root filesystem the command is run in through the OCI runtime
+    #[structopt(long, parse(try_from_str = absolute_path))]
+    image: Option<PathBuf>,
+    /// Host directory mounted in the image as SOURCE:DESTINATION[:ro]
+    #[structopt(long = "mount", requires = "image", parse(try_from_str = parse_mount), number_of_values = 1)]
+    mounts: Vec<Mount>,
+    /// Remote host the command is run on through ssh as HOST[:PORT]
+    #[structopt(long)]
+    host: Option<String>,
+    /// WASI module run by the embedded runtime, the command gives its arguments
+    #[structopt(long, group = "module", parse(try_from_str = absolute_path))]
+    wasm: Option<PathBuf>,
+    /// WASI module whose content is stored with the task, instead of its path with --wasm
+    #[structopt(long, group = "module", parse(try_from_str = read_wasm_blob))]
+    wasm_blob: Option<WasmSource>,
+    /// Host directory preopened as / for the WASI module
+    #[structopt(long, requires = "module", parse(try_from_str = absolute_path))]
+    wasm_dir: Option<PathBuf>,
+    /// Maximum number of instructions executed by the WASI module
+    #[structopt(long, requires = "module")]
+    fuel: Option<u64>,
+    /// Executor declared in the settings running the command, as NAME or ssh:HOST[:PORT]
+    #[structopt(short = "x", long)]
+    executor: Option<String>,
+    /// Glob pattern of the files of the working directory collected after the execution
+    #[structopt(long = "artifact", requires = "workdir", number_of_values = 1)]
+    artifacts: Vec<String>,
+    /// Shell command run before the task, the task is not run if it fails
+    #[structopt(long)]
+    pre_hook: Option<String>,
+    /// Shell command run once the task is completed, even if it failed
+    #[structopt(long)]
+    post_hook: Option<String>,
     /// Wait the execution of the task and print status
     #[structopt(short, long)]
     pub wait: bool,
+    /// Maximum time to wait for the task completion in seconds, the task is killed once it elapsed
+    #[structopt(long, requires = "wait")]
+    pub wait_timeout: Option<u64>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -27,13 +119,164 @@ pub enum TaskStatusOpt {
     },
 }
 
+#[derive(Debug, StructOpt)]
+pub struct TaskListOpt {
+    /// Label selector of the tasks (e.g. team=data,env!=prod)
+    #[structopt(short = "l", long)]
+    pub selector: Option<LabelSelector>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskSearchOpt {
+    /// Words searched in sequence in the names, commands and outputs of the tasks, ignoring case
+    pub query: SearchQuery,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskCancelOpt {
+    /// Label selector of the tasks to cancel (e.g. batch=42)
+    #[structopt(short = "l", long)]
+    pub selector: LabelSelector,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(group = ArgGroup::with_name("task").required(true))]
+pub struct TaskArtifactsOpt {
+    /// Id of the task
+    #[structopt(long, group = "task")]
+    id: Option<String>,
+    /// Name of the task
+    #[structopt(long, group = "task")]
+    name: Option<String>,
+    /// Path of the artifact to extract instead of listing them
+    #[structopt(long, parse(from_os_str))]
+    pub get: Option<PathBuf>,
+    /// File the artifact is extracted to, its file name in the current directory by default
+    #[structopt(short, long, requires = "get", parse(from_os_str))]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskEventsOpt {
+    /// Id of the task
+    #[structopt(long)]
+    pub id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskPurgeOpt {
+    /// Purge the tasks completed for longer, with a s, m, h, d or w suffix (e.g. 30d)
+    #[structopt(long, parse(try_from_str = parse_duration))]
+    older_than: Option<Duration>,
+    /// Number of completed tasks kept for each name, the older ones are purged
+    #[structopt(long)]
+    max_per_name: Option<usize>,
+    /// Number of the last failed tasks kept whatever the other rules
+    #[structopt(long)]
+    keep_failures: Option<usize>,
+    /// Status of the tasks which can be purged (success, error or cancelled), all of them by default
+    #[structopt(long = "status", number_of_values = 1)]
+    statuses: Vec<TaskOutcome>,
+    /// List the tasks which would be purged without deleting them
+    #[structopt(long)]
+    pub dry_run: bool,
+    /// Keep running and purge with the retention of the settings every retention.interval
+    #[structopt(long, conflicts_with_all = &["older-than", "max-per-name", "keep-failures", "status", "dry-run"])]
+    pub daemon: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskExportOpt {
+    /// Format of the exported tasks, jsonl or csv
+    #[structopt(long, default_value = "jsonl")]
+    pub format: ExportFormat,
+    /// File written instead of the standard output
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskImportOpt {
+    /// Format of the imported tasks, jsonl or csv
+    #[structopt(long, default_value = "jsonl")]
+    pub format: ExportFormat,
+    /// File of a tasc export, the standard input is read without it
+    #[structopt(parse(from_os_str))]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TaskApplyOpt {
+    /// Manifest file declaring the tasks to schedule (toml or yaml)
+    #[structopt(short, long, parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TemplateAddOpt {
+    /// Command of the template, can contain {{param}} placeholders
+    #[structopt(required = true)]
+    command: Vec<String>,
+    /// Name of the template
+    #[structopt(short, long)]
+    pub name: String,
+    /// Default env var of the tasks as KEY=VALUE, can contain {{param}} placeholders
+    #[structopt(short, long, parse(try_from_str = parse_key_value), number_of_values = 1)]
+    env: Vec<(String, String)>,
+    /// Timeout of the tasks in seconds
+    #[structopt(long)]
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum TaskTemplateOpt {
+    #[structopt(name = "add")]
+    Add(TemplateAddOpt),
+}
+
+// Schema of the storage database, and of the events one when it is separate
+#[derive(Debug, StructOpt)]
+pub enum DbOpt {
+    /// Apply the pending migrations
+    #[structopt(name = "migrate")]
+    Migrate,
+    /// List the migrations with whether they are applied
+    #[structopt(name = "status")]
+    Status,
+    /// Revert the last applied migration
+    #[structopt(name = "rollback")]
+    Rollback,
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "tasc")]
 pub enum CliOpt {
     #[structopt(name = "run")]
-    Run(TaskRunOpt),
+    Run(Box<TaskRunOpt>),
     #[structopt(name = "status")]
     Status(TaskStatusOpt),
+    #[structopt(name = "list")]
+    List(TaskListOpt),
+    #[structopt(name = "search")]
+    Search(TaskSearchOpt),
+    #[structopt(name = "cancel")]
+    Cancel(TaskCancelOpt),
+    #[structopt(name = "apply")]
+    Apply(TaskApplyOpt),
+    #[structopt(name = "artifacts")]
+    Artifacts(TaskArtifactsOpt),
+    #[structopt(name = "template")]
+    Template(TaskTemplateOpt),
+    #[structopt(name = "events")]
+    Events(TaskEventsOpt),
+    #[structopt(name = "purge")]
+    Purge(TaskPurgeOpt),
+    #[structopt(name = "export")]
+    Export(TaskExportOpt),
+    #[structopt(name = "import")]
+    Import(TaskImportOpt),
+    #[structopt(name = "db")]
+    Db(DbOpt),
 }
 
 
@@ -43,10 +286,107 @@ pub fn parse_cli_opts() -> CliOpt {
 
 impl Into<TaskInput> for TaskRunOpt {
     fn into(self) -> TaskInput {
+        let limits = self.limits();
+        let credentials = self.credentials();
+        let container = self.container();
+        let working_dir = self.working_dir();
+        let wasm = self.wasm_module();
         TaskInput {
             command: self.command.join(" "),
             name: self.name,
             env: None,
+            // Waiting the task kills it at the deadline
+            timeout: self.wait_timeout.map(Duration::from_secs),
+            dependencies: vec![],
+            labels: self.labels.into_iter().collect(),
+            working_dir,
+            stdin: self.stdin.map(String::into_bytes).or(self.stdin_file),
+            umask: self.umask,
+            limits,
+            sandboxed: self.sandbox,
+            credentials,
+            container,
+            host: self.host,
+            wasm,
+            executor: self.executor,
+            artifacts: self.artifacts,
+            hooks: TaskHooks { pre: self.pre_hook, post: self.post_hook },
+        }
+    }
+}
+
+impl From<TaskRunOpt> for TemplateInput {
+    fn from(opt: TaskRunOpt) -> Self {
+        let limits = opt.limits();
+        let credentials = opt.credentials();
+        let container = opt.container();
+        let working_dir = opt.working_dir();
+        let wasm = opt.wasm_module();
+        TemplateInput {
+            template: opt.template.unwrap_or_default(),
+            name: opt.name,
+            params: opt.params.into_iter().collect(),
+            labels: opt.labels.into_iter().collect(),
+            working_dir,
+            stdin: opt.stdin.map(String::into_bytes).or(opt.stdin_file),
+            umask: opt.umask,
+            limits,
+            sandboxed: opt.sandbox,
+            credentials,
+            container,
+            host: opt.host,
+            wasm,
+            executor: opt.executor,
+            artifacts: opt.artifacts,
+            hooks: TaskHooks { pre: opt.pre_hook, post: opt.post_hook },
+            timeout: opt.wait_timeout.map(Duration::from_secs),
+        }
+    }
+}
+
+impl TaskRunOpt {
+    fn limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            cpu_time: self.cpu_time.map(Duration::from_secs),
+            memory: self.memory,
+            open_files: self.open_files,
+            processes: self.processes,
+        }
+    }
+
+    fn credentials(&self) -> Option<Credentials> {
+        match (self.uid, self.gid) {
+            (Some(uid), Some(gid)) => Some(Credentials { uid, gid, groups: self.groups.clone() }),
+            _ => None,
+        }
+    }
+
+    // A host directory is resolved against the current one, the directory of an image is kept as is
+    fn working_dir(&self) -> Option<PathBuf> {
+        self.workdir.clone().map(|workdir| match self.image {
+            Some(_) => workdir,
+            None => absolute_path(&workdir.to_string_lossy()).unwrap_or(workdir),
+        })
+    }
+
+    fn container(&self) -> Option<Container> {
+        self.image.clone().map(|rootfs| Container { rootfs, mounts: self.mounts.clone() })
+    }
+
+    fn wasm_module(&self) -> Option<WasmModule> {
+        self.wasm.clone().map(WasmSource::Path)
+            .or_else(|| self.wasm_blob.clone())
+            .map(|source| WasmModule { source, dir: self.wasm_dir.clone(), fuel: self.fuel })
+    }
+}
+
+impl From<TemplateAddOpt> for TaskTemplate {
+    fn from(opt: TemplateAddOpt) -> Self {
+        TaskTemplate {
+            name: opt.name,
+            command: opt.command.join(" "),
+            env: if opt.env.is_empty() { None } else { Some(opt.env.into_iter().collect()) },
+            timeout: opt.timeout.map(Duration::from_secs),
         }
     }
 }
@@ -58,4 +398,111 @@ impl Into<TaskId> for TaskStatusOpt {
             TaskStatusOpt::Name { name } => TaskId::Name(name)
         }
     }
+}
+
+impl TaskPurgeOpt {
+    // None when no rule is given, the retention of the settings applies
+    pub fn policy(&self) -> Option<RetentionPolicy> {
+        let policy = RetentionPolicy {
+            max_age: self.older_than,
+            max_per_name: self.max_per_name,
+            keep_failures: self.keep_failures,
+            outcomes: self.statuses.clone(),
+        };
+        if policy == RetentionPolicy::default() { None } else { Some(policy) }
+    }
+}
+
+impl TaskArtifactsOpt {
+    // The arg group ensures that either the id or the name is given
+    pub fn task_id(&self) -> TaskId {
+        match (&self.id, &self.name) {
+            (Some(id), _) => TaskId::Id(id.clone()),
+            (None, name) => TaskId::Name(name.clone().unwrap_or_default()),
+        }
+    }
+}
+
+fn parse_key_value(source: &str) -> Result<(String, String), String> {
+    match source.splitn(2, '=').collect::<Vec<&str>>().as_slice() {
+        [key, value] if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("{} is not a valid KEY=VALUE pair", source)),
+    }
+}
+
+pub fn parse_umask(source: &str) -> Result<u32, String> {
+    u32::from_str_radix(source, 8)
+        .map_err(|_| format!("{} is not a valid octal umask", source))
+}
+
+pub fn parse_size(source: &str) -> Result<u64, String> {
+    let (number, unit) = match source.char_indices().last() {
+        Some((index, 'K')) | Some((index, 'k')) => (&source[..index], 1 << 10),
+        Some((index, 'M')) | Some((index, 'm')) => (&source[..index], 1 << 20),
+        Some((index, 'G')) | Some((index, 'g')) => (&source[..index], 1 << 30),
+        _ => (source, 1),
+    };
+    number.parse::<u64>().ok()
+        .and_then(|number| number.checked_mul(unit))
+        .ok_or_else(|| format!("{} is not a valid size", source))
+}
+
+// Seconds unless suffixed with m, h, d or w
+pub fn parse_duration(source: &str) -> Result<Duration, String> {
+    let (number, unit) = match source.char_indices().last() {
+        Some((index, 's')) => (&source[..index], 1),
+        Some((index, 'm')) => (&source[..index], 60),
+        Some((index, 'h')) => (&source[..index], 3600),
+        Some((index, 'd')) => (&source[..index], 86400),
+        Some((index, 'w')) => (&source[..index], 7 * 86400),
+        _ => (source, 1),
+    };
+    number.parse::<u64>().ok()
+        .and_then(|number| number.checked_mul(unit))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("{} is not a valid duration", source))
+}
+
+fn parse_mount(source: &str) -> Result<Mount, String> {
+    let mount = |host: &str, destination: &str, read_only| absolute_path(host)
+        .map(|host| Mount { source: host, destination: PathBuf::from(destination), read_only });
+    match source.split(':').collect::<Vec<&str>>().as_slice() {
+        [host, destination] => mount(host, destination, false),
+        [host, destination, "ro"] => mount(host, destination, true),
+        [host, destination, "rw"] => mount(host, destination, false),
+        _ => Err(format!("{} is not a valid SOURCE:DESTINATION[:ro] mount", source)),
+    }
+}
+
+fn absolute_path(source: &str) -> Result<PathBuf, String> {
+    env::current_dir()
+        .map(|current_dir| current_dir.join(source))
+        .map_err(|err| format!("Error resolving {} from current directory : {}", source, err))
+}
+
+fn read_wasm_blob(source: &str) -> Result<WasmSource, String> {
+    fs::read(source)
+        .map(WasmSource::Blob)
+        .map_err(|err| format!("Error reading {} : {}", source, err))
+}
+
+fn read_file(source: &str) -> Result<FileContent, String> {
+    fs::read(source)
+        .map_err(|err| format!("Error reading {} : {}", source, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size_should_apply_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("4k"), Ok(4 << 10));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("M"), Err("M is not a valid size".to_string()));
+        assert_eq!(parse_size("-1M"), Err("-1M is not a valid size".to_string()));
+        assert_eq!(parse_size("20000000000G"), Err("20000000000G is not a valid size".to_string()));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,543 @@
+use anyhow::{anyhow, Context, Error};
+use domain::executor::model::model::{Artifact, Container, Credentials, ExportedTask, HookFailure, Mount, ResourceLimits, Task, TaskHooks, TaskOutput, TaskStatus, WasmModule, WasmSource};
+use im::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Tasks as written by tasc export and read by tasc import, independent of the storage they come from
+
+const SCHEDULED: &str = "scheduled";
+const SUCCESS: &str = "success";
+const ERROR: &str = "error";
+const CANCELLED: &str = "cancelled";
+
+// Columns of the CSV rows, the nested values are written as JSON in their cell
+const CSV_COLUMNS: [&str; 32] = [
+    "id", "name", "command", "env", "timeout", "labels", "working_dir", "stdin", "stdin_base64", "umask",
+    "cpu_time_limit", "memory_limit", "open_files_limit", "processes_limit", "sandboxed", "user", "container",
+    "host", "wasm", "executor", "artifact_patterns", "pre_hook", "post_hook",
+    "status", "exit_code", "output", "output_base64", "output_truncated", "output_log", "completed_at",
+    "artifacts", "hook_failures",
+];
+// Columns whose cell is the text itself instead of its JSON value
+const CSV_TEXT_COLUMNS: [&str; 15] = [
+    "id", "name", "command", "working_dir", "stdin", "stdin_base64", "host", "executor", "pre_hook", "post_hook",
+    "status", "output", "output_base64", "output_log", "completed_at",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    // One JSON object by line
+    Jsonl,
+    // One row by task after a header naming the columns
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("{} is not a valid export format, expected jsonl or csv", source)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportEntry {
+    id: String,
+    name: Option<String>,
+    command: String,
+    env: Option<HashMap<String, String>>,
+    // Seconds
+    timeout: Option<u64>,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    working_dir: Option<PathBuf>,
+    // Encoded in base64 when it is not UTF-8, like the output
+    stdin: Option<String>,
+    stdin_base64: Option<String>,
+    umask: Option<u32>,
+    // Seconds
+    cpu_time_limit: Option<u64>,
+    // Bytes
+    memory_limit: Option<u64>,
+    open_files_limit: Option<u64>,
+    processes_limit: Option<u64>,
+    #[serde(default)]
+    sandboxed: bool,
+    user: Option<TaskExportUser>,
+    container: Option<TaskExportContainer>,
+    host: Option<String>,
+    wasm: Option<TaskExportWasm>,
+    executor: Option<String>,
+    #[serde(default)]
+    artifact_patterns: Vec<String>,
+    pre_hook: Option<String>,
+    post_hook: Option<String>,
+    // scheduled, success, error or cancelled
+    status: String,
+    exit_code: Option<i32>,
+    // Output of a completed task, encoded in base64 when it is not UTF-8
+    output: Option<String>,
+    output_base64: Option<String>,
+    #[serde(default)]
+    output_truncated: u64,
+    output_log: Option<PathBuf>,
+    // ISO 8601 UTC timestamp
+    completed_at: Option<String>,
+    #[serde(default)]
+    artifacts: Vec<TaskExportArtifact>,
+    #[serde(default)]
+    hook_failures: Vec<TaskExportHookFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportUser {
+    uid: u32,
+    gid: u32,
+    #[serde(default)]
+    groups: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportContainer {
+    rootfs: PathBuf,
+    #[serde(default)]
+    mounts: Vec<TaskExportMount>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportMount {
+    source: PathBuf,
+    destination: PathBuf,
+    #[serde(default)]
+    read_only: bool,
+}
+
+// A module stored with the task is exported in base64
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportWasm {
+    path: Option<PathBuf>,
+    module_base64: Option<String>,
+    dir: Option<PathBuf>,
+    fuel: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportArtifact {
+    path: PathBuf,
+    digest: String,
+    size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskExportHookFailure {
+    stage: String,
+    message: String,
+}
+
+pub enum TaskWriter<W: Write> {
+    Jsonl(W),
+    // Boxed as it holds its buffer
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> TaskWriter<W> {
+    pub fn new(format: ExportFormat, writer: W) -> Result<TaskWriter<W>, Error> {
+        match format {
+            ExportFormat::Jsonl => Ok(TaskWriter::Jsonl(writer)),
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record(CSV_COLUMNS.iter())?;
+                Ok(TaskWriter::Csv(Box::new(writer)))
+            }
+        }
+    }
+
+    pub fn write(&mut self, task: ExportedTask) -> Result<(), Error> {
+        let entry = TaskExportEntry::from(task);
+        match self {
+            TaskWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, &entry)?;
+                writer.write_all(b"\n").map_err(Error::from)
+            }
+            TaskWriter::Csv(writer) => writer.write_record(csv_row(entry)?).map_err(Error::from),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        match self {
+            TaskWriter::Jsonl(mut writer) => writer.flush(),
+            TaskWriter::Csv(mut writer) => writer.flush(),
+        }.context("Error writing exported tasks")
+    }
+}
+
+// Tasks are read one after the other, an invalid one ends the reading with its line in the error
+pub fn read_tasks<'a, R: Read + 'a>(format: ExportFormat, reader: R) -> Box<dyn Iterator<Item = Result<ExportedTask, Error>> + 'a> {
+    match format {
+        ExportFormat::Jsonl => Box::new(BufReader::new(reader).lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .map(|(number, line)| line.map_err(Error::from)
+                .and_then(|line| serde_json::from_str::<TaskExportEntry>(&line).map_err(Error::from))
+                .and_then(ExportedTask::try_from)
+                .context(format!("Error reading task at line {}", number + 1)))),
+        ExportFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(reader);
+            let headers = reader.headers().cloned();
+            match headers {
+                Ok(headers) => Box::new(reader.into_records()
+                    .map(move |record| {
+                        let line = record.as_ref().ok().and_then(|record| record.position()).map_or(0, |position| position.line());
+                        record.map_err(Error::from)
+                            .and_then(|record| csv_entry(&headers, &record))
+                            .and_then(ExportedTask::try_from)
+                            .context(format!("Error reading task at line {}", line))
+                    })),
+                Err(err) => Box::new(std::iter::once(Err(Error::from(err).context("Error reading the CSV header")))),
+            }
+        }
+    }
+}
+
+fn csv_row(entry: TaskExportEntry) -> Result<Vec<String>, Error> {
+    let mut values = match serde_json::to_value(entry)? {
+        Value::Object(values) => values,
+        _ => return Err(anyhow!("A task is exported as an object")),
+    };
+    CSV_COLUMNS.iter()
+        .map(|column| match values.remove(*column).unwrap_or(Value::Null) {
+            Value::Null => Ok(String::new()),
+            Value::String(text) if CSV_TEXT_COLUMNS.contains(column) => Ok(text),
+            value => serde_json::to_string(&value).map_err(Error::from),
+        })
+        .collect()
+}
+
+// Empty cells are missing values
+fn csv_entry(headers: &csv::StringRecord, record: &csv::StringRecord) -> Result<TaskExportEntry, Error> {
+    let values = headers.iter().zip(record.iter())
+        .filter(|(_, cell)| !cell.is_empty())
+        .map(|(column, cell)| if CSV_TEXT_COLUMNS.contains(&column) {
+            Ok((column.to_string(), Value::String(cell.to_string())))
+        } else {
+            serde_json::from_str(cell)
+                .map(|value| (column.to_string(), value))
+                .context(format!("Column {} is not a valid JSON value", column))
+        })
+        .collect::<Result<Map<String, Value>, Error>>()?;
+    serde_json::from_value(Value::Object(values)).map_err(Error::from)
+}
+
+impl From<ExportedTask> for TaskExportEntry {
+    fn from(exported: ExportedTask) -> Self {
+        let task = exported.task;
+        let (status, output, exit_code) = match exported.status {
+            TaskStatus::Scheduled => (SCHEDULED, None, None),
+            TaskStatus::Success(stdout) => (SUCCESS, Some(stdout), Some(0)),
+            TaskStatus::Error(stderr, code) => (ERROR, Some(stderr), code),
+            TaskStatus::Cancelled => (CANCELLED, None, None),
+        };
+        let output_truncated = output.as_ref().map_or(0, |output| output.truncated);
+        let output_log = output.as_ref().and_then(|output| output.log.clone());
+        let (output, output_base64) = encode_bytes(output.map(|output| output.content));
+        let (stdin, stdin_base64) = encode_bytes(task.stdin);
+        TaskExportEntry {
+            id: task.id,
+            name: task.name,
+            command: task.command,
+            env: task.env,
+            timeout: task.timeout.map(|timeout| timeout.as_secs()),
+            labels: task.labels,
+            working_dir: task.working_dir,
+            stdin,
+            stdin_base64,
+            umask: task.umask,
+            cpu_time_limit: task.limits.cpu_time.map(|cpu_time| cpu_time.as_secs()),
+            memory_limit: task.limits.memory,
+            open_files_limit: task.limits.open_files,
+            processes_limit: task.limits.processes,
+            sandboxed: task.sandboxed,
+            user: task.credentials.map(|credentials| TaskExportUser { uid: credentials.uid, gid: credentials.gid, groups: credentials.groups }),
+            container: task.container.map(|container| TaskExportContainer {
+                rootfs: container.rootfs,
+                mounts: container.mounts.into_iter()
+                    .map(|mount| TaskExportMount { source: mount.source, destination: mount.destination, read_only: mount.read_only })
+                    .collect(),
+            }),
+            host: task.host,
+            wasm: task.wasm.map(|wasm| {
+                let (path, module_base64) = match wasm.source {
+                    WasmSource::Path(path) => (Some(path), None),
+                    WasmSource::Blob(blob) => (None, Some(base64::encode(blob))),
+                };
+                TaskExportWasm { path, module_base64, dir: wasm.dir, fuel: wasm.fuel }
+            }),
+            executor: task.executor,
+            artifact_patterns: task.artifacts,
+            pre_hook: task.hooks.pre,
+            post_hook: task.hooks.post,
+            status: status.to_string(),
+            exit_code,
+            output,
+            output_base64,
+            output_truncated,
+            output_log,
+            completed_at: exported.completed_at.map(format_utc),
+            artifacts: exported.artifacts.into_iter()
+                .map(|artifact| TaskExportArtifact { path: artifact.path, digest: artifact.digest, size: artifact.size })
+                .collect(),
+            hook_failures: exported.hook_failures.into_iter()
+                .map(|failure| TaskExportHookFailure { stage: failure.stage.to_string(), message: failure.message })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<TaskExportEntry> for ExportedTask {
+    type Error = Error;
+
+    fn try_from(entry: TaskExportEntry) -> Result<Self, Self::Error> {
+        let completed_at = entry.completed_at.as_deref()
+            .map(|completed_at| parse_utc(completed_at).ok_or_else(|| anyhow!("{} is not a valid completion time of task {}", completed_at, entry.id)))
+            .transpose()?;
+        let content = decode_bytes(entry.output, entry.output_base64).context(format!("Invalid output of task {}", entry.id))?
+            .unwrap_or_default();
+        let stdin = decode_bytes(entry.stdin, entry.stdin_base64).context(format!("Invalid stdin of task {}", entry.id))?;
+        let output = TaskOutput { content, truncated: entry.output_truncated, log: entry.output_log };
+        let status = match entry.status.as_str() {
+            SCHEDULED => TaskStatus::Scheduled,
+            SUCCESS => TaskStatus::Success(output),
+            ERROR => TaskStatus::Error(output, entry.exit_code),
+            CANCELLED => TaskStatus::Cancelled,
+            other => return Err(anyhow!("Unknown status {} of task {}", other, entry.id)),
+        };
+        let wasm = match entry.wasm {
+            Some(wasm) => Some(WasmModule {
+                source: match (wasm.path, wasm.module_base64) {
+                    (Some(path), _) => WasmSource::Path(path),
+                    (None, Some(encoded)) => WasmSource::Blob(base64::decode(encoded).context(format!("Invalid WASI module of task {}", entry.id))?),
+                    (None, None) => return Err(anyhow!("Missing WASI module of task {}", entry.id)),
+                },
+                dir: wasm.dir,
+                fuel: wasm.fuel,
+            }),
+            None => None,
+        };
+        let hook_failures = entry.hook_failures.into_iter()
+            .map(|failure| failure.stage.parse()
+                .map(|stage| HookFailure { stage, message: failure.message })
+                .map_err(Error::from))
+            .collect::<Result<Vec<HookFailure>, Error>>()?;
+        // The digests name the contents of the artifact store, they must not lead out of it
+        if let Some(artifact) = entry.artifacts.iter().find(|artifact| !Artifact::is_digest(&artifact.digest)) {
+            return Err(anyhow!("{:?} is not a SHA-256 digest of artifact {} of task {}", artifact.digest, artifact.path.display(), entry.id));
+        }
+        let task = Task {
+            id: entry.id,
+            name: entry.name,
+            command: entry.command,
+            env: entry.env,
+            timeout: entry.timeout.map(Duration::from_secs),
+            labels: entry.labels,
+            working_dir: entry.working_dir,
+            stdin,
+            umask: entry.umask,
+            limits: ResourceLimits {
+                cpu_time: entry.cpu_time_limit.map(Duration::from_secs),
+                memory: entry.memory_limit,
+                open_files: entry.open_files_limit,
+                processes: entry.processes_limit,
+            },
+            sandboxed: entry.sandboxed,
+            credentials: entry.user.map(|user| Credentials { uid: user.uid, gid: user.gid, groups: user.groups }),
+            container: entry.container.map(|container| Container {
+                rootfs: container.rootfs,
+                mounts: container.mounts.into_iter()
+                    .map(|mount| Mount { source: mount.source, destination: mount.destination, read_only: mount.read_only })
+                    .collect(),
+            }),
+            host: entry.host,
+            wasm,
+            executor: entry.executor,
+            artifacts: entry.artifact_patterns,
+            hooks: TaskHooks { pre: entry.pre_hook, post: entry.post_hook },
+        };
+        Ok(ExportedTask {
+            task,
+            status,
+            completed_at,
+            artifacts: entry.artifacts.into_iter()
+                .map(|artifact| Artifact { path: artifact.path, digest: artifact.digest, size: artifact.size })
+                .collect(),
+            hook_failures,
+        })
+    }
+}
+
+// Bytes written as text when they are UTF-8, in base64 otherwise
+fn encode_bytes(bytes: Option<Vec<u8>>) -> (Option<String>, Option<String>) {
+    match bytes.map(String::from_utf8) {
+        Some(Ok(text)) => (Some(text), None),
+        Some(Err(err)) => (None, Some(base64::encode(err.as_bytes()))),
+        None => (None, None),
+    }
+}
+
+fn decode_bytes(text: Option<String>, encoded: Option<String>) -> Result<Option<Vec<u8>>, Error> {
+    match (text, encoded) {
+        (_, Some(encoded)) => base64::decode(encoded).map(Some).map_err(Error::from),
+        (text, None) => Ok(text.map(String::into_bytes)),
+    }
+}
+
+// ISO 8601 UTC timestamp, the civil date is computed from the days since the epoch
+pub fn format_utc(at: SystemTime) -> String {
+    let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day, seconds / 3600 % 24, seconds / 60 % 60, seconds % 60, since_epoch.subsec_millis())
+}
+
+// Timestamp written by format_utc, the milliseconds being optional
+fn parse_utc(source: &str) -> Option<SystemTime> {
+    let source = source.strip_suffix('Z')?;
+    let (date, time) = source.split_once('T')?;
+    let (time, millis) = match time.split_once('.') {
+        Some((time, millis)) if millis.len() == 3 => (time, millis.parse::<u64>().ok()?),
+        Some(_) => return None,
+        None => (time, 0),
+    };
+    let date: Vec<i64> = date.split('-').map(|part| part.parse().ok()).collect::<Option<Vec<i64>>>()?;
+    let time: Vec<u64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    let (year, month, day, hour, minute, second) = match (date.as_slice(), time.as_slice()) {
+        ([year, month, day], [hour, minute, second]) if (1..=12).contains(month) && (1..=31).contains(day) && *hour < 24 && *minute < 60 && *second < 60 =>
+            (*year, *month, *day, *hour, *minute, *second),
+        _ => return None,
+    };
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146097 + day_of_era - 719468).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + hour * 3600 + minute * 60 + second) + Duration::from_millis(millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::executor::model::model::HookStage;
+
+    #[test]
+    fn test_export_formats_should_read_written_tasks() {
+        for format in [ExportFormat::Jsonl, ExportFormat::Csv] {
+            let mut writer = TaskWriter::new(format, vec![]).unwrap();
+            exported_tasks().into_iter().try_for_each(|task| writer.write(task)).unwrap();
+            let written = match writer {
+                TaskWriter::Jsonl(written) => written,
+                TaskWriter::Csv(writer) => writer.into_inner().unwrap(),
+            };
+            let read = read_tasks(format, written.as_slice()).collect::<Result<Vec<ExportedTask>, Error>>().unwrap();
+            assert_eq!(read.len(), 2);
+            for (actual, expected) in read.into_iter().zip(exported_tasks()) {
+                assert_eq!(actual.task, expected.task);
+                assert_eq!(format!("{:?}", actual.status), format!("{:?}", expected.status));
+                assert_eq!(actual.completed_at, expected.completed_at);
+                assert_eq!(actual.artifacts, expected.artifacts);
+                assert_eq!(actual.hook_failures, expected.hook_failures);
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_tasks_should_give_line_of_invalid_task() {
+        let lines = "{\"id\":\"first\",\"command\":\"true\",\"status\":\"scheduled\"}\n{\"id\":\"second\",\"command\":\"true\",\"status\":\"running\"}\n";
+        let read: Vec<Result<ExportedTask, Error>> = read_tasks(ExportFormat::Jsonl, lines.as_bytes()).collect();
+        assert!(read[0].is_ok());
+        assert_eq!(format!("{:#}", read[1].as_ref().unwrap_err()), "Error reading task at line 2: Unknown status running of task second");
+    }
+
+    #[test]
+    fn test_read_tasks_should_refuse_invalid_artifact_digests() {
+        for digest in ["0a1b2c", "../../../etc/shadow", &"A".repeat(64), &"0".repeat(65)] {
+            let line = format!("{{\"id\":\"first\",\"command\":\"true\",\"status\":\"success\",\"artifacts\":[{{\"path\":\"out.txt\",\"digest\":{:?},\"size\":1}}]}}\n", digest);
+            let read: Vec<Result<ExportedTask, Error>> = read_tasks(ExportFormat::Jsonl, line.as_bytes()).collect();
+            assert_eq!(format!("{:#}", read[0].as_ref().unwrap_err()), format!("Error reading task at line 1: {:?} is not a SHA-256 digest of artifact out.txt of task first", digest));
+        }
+    }
+
+    #[test]
+    fn test_parse_utc_should_read_formatted_time() {
+        for millis in [0, 951_782_400_000, 1_600_000_000_123, 4_107_542_399_999] {
+            let at = UNIX_EPOCH + Duration::from_millis(millis);
+            assert_eq!(parse_utc(&format_utc(at)), Some(at));
+        }
+        assert_eq!(parse_utc("2020-09-13T12:26:40Z"), Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)));
+        assert_eq!(parse_utc("2020-13-13T12:26:40Z"), None);
+        assert_eq!(parse_utc("2020-09-13 12:26:40"), None);
+    }
+
+    fn exported_tasks() -> Vec<ExportedTask> {
+        let task = Task {
+            id: "first".to_string(),
+            name: Some("backup".to_string()),
+            command: "tar czf backup.tgz \"/data, and more\"".to_string(),
+            env: Some(HashMap::unit("TARGET".to_string(), "/mnt/backup".to_string())),
+            timeout: Some(Duration::from_secs(60)),
+            labels: HashMap::unit("team".to_string(), "data".to_string()),
+            working_dir: Some(PathBuf::from("/data")),
+            stdin: Some(b"line\n\xfenext".to_vec()),
+            umask: Some(0o027),
+            limits: ResourceLimits { cpu_time: Some(Duration::from_secs(30)), memory: Some(1 << 20), open_files: None, processes: Some(8) },
+            sandboxed: true,
+            credentials: Some(Credentials { uid: 1000, gid: 1000, groups: vec![100] }),
+            container: Some(Container { rootfs: PathBuf::from("/var/lib/rootfs"), mounts: vec![Mount { source: PathBuf::from("/data"), destination: PathBuf::from("/mnt"), read_only: true }] }),
+            host: None,
+            wasm: Some(WasmModule { source: WasmSource::Blob(b"\0asm".to_vec()), dir: None, fuel: Some(1000) }),
+            executor: Some("wasm".to_string()),
+            artifacts: vec!["*.tgz".to_string()],
+            hooks: TaskHooks { pre: Some("mount /data".to_string()), post: None },
+        };
+        vec![
+            ExportedTask {
+                task: task.clone(),
+                status: TaskStatus::Error(TaskOutput { content: b"head\n\xfftail".to_vec(), truncated: 42, log: Some(PathBuf::from("/var/log/tasc/first.log")) }, Some(2)),
+                completed_at: Some(UNIX_EPOCH + Duration::from_millis(1_600_000_000_123)),
+                artifacts: vec![Artifact { path: PathBuf::from("backup.tgz"), digest: "0a1b2c".repeat(10) + "3d4e", size: 12 }],
+                hook_failures: vec![HookFailure { stage: HookStage::Pre, message: "mount failed".to_string() }],
+            },
+            ExportedTask {
+                task: Task { id: "second".to_string(), env: None, stdin: Some(b"line\nnext".to_vec()), wasm: None, ..task },
+                status: TaskStatus::Success("done, \"quoted\"".into()),
+                completed_at: None,
+                artifacts: vec![],
+                hook_failures: vec![],
+            },
+        ]
+    }
+}
\ No newline at end of file
@@ -0,0 +1,321 @@
+use anyhow::{anyhow, Context, Error};
+use domain::executor::model::model::{Container, Credentials, Mount, ResourceLimits, TaskHooks, WasmModule, WasmSource};
+use domain::executor::ports::primary::TaskInput;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::primary::cli::{parse_size, parse_umask};
+
+#[derive(Debug, Deserialize)]
+pub struct TaskManifest {
+    #[serde(default)]
+    pub tasks: Vec<TaskManifestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestEntry {
+    name: String,
+    command: String,
+    env: Option<HashMap<String, String>>,
+    // Timeout in seconds
+    timeout: Option<u64>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    // Relative paths are resolved from the manifest directory
+    working_dir: Option<PathBuf>,
+    stdin: Option<String>,
+    stdin_file: Option<PathBuf>,
+    // Octal umask as a string (e.g. "022")
+    umask: Option<String>,
+    #[serde(default)]
+    limits: TaskManifestLimits,
+    #[serde(default)]
+    sandbox: bool,
+    user: Option<TaskManifestUser>,
+    container: Option<TaskManifestContainer>,
+    // Remote host as HOST[:PORT]
+    host: Option<String>,
+    wasm: Option<TaskManifestWasm>,
+    // Executor declared in the settings, as NAME or ssh:HOST[:PORT]
+    executor: Option<String>,
+    // Glob patterns of the files collected from the working directory
+    #[serde(default)]
+    artifacts: Vec<String>,
+    #[serde(default)]
+    hooks: TaskManifestHooks,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestLimits {
+    // CPU time in seconds
+    cpu_time: Option<u64>,
+    // Size in bytes with an optional K, M or G suffix (e.g. "512M")
+    memory: Option<String>,
+    open_files: Option<u64>,
+    processes: Option<u64>,
+}
+
+// Shell commands run before and after the task
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestHooks {
+    pre: Option<String>,
+    post: Option<String>,
+}
+
+// Relative paths of the host are resolved from the manifest directory
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestContainer {
+    rootfs: PathBuf,
+    #[serde(default)]
+    mounts: Vec<TaskManifestMount>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestMount {
+    source: PathBuf,
+    destination: PathBuf,
+    #[serde(default)]
+    read_only: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestWasm {
+    module: PathBuf,
+    // Store the module content with the task instead of its path
+    #[serde(default)]
+    embed: bool,
+    dir: Option<PathBuf>,
+    fuel: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskManifestUser {
+    uid: u32,
+    gid: u32,
+    #[serde(default)]
+    groups: Vec<u32>,
+}
+
+pub fn load_manifest(path: &Path) -> Result<Vec<TaskInput>, Error> {
+    let content = fs::read_to_string(path)
+        .context(format!("Error reading manifest {}", path.display()))?;
+    let manifest: TaskManifest = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml::from_str(&content).map_err(Error::from),
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&content).map_err(Error::from),
+        _ => Err(anyhow!("Unsupported manifest format, expected a .toml, .yaml or .yml file")),
+    }.context(format!("Error parsing manifest {}", path.display()))?;
+    let base_dir = fs::canonicalize(path)
+        .context(format!("Error resolving manifest {} directory", path.display()))?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    manifest.tasks.into_iter()
+        .map(|entry| entry.into_input(&base_dir))
+        .collect()
+}
+
+impl TaskManifestEntry {
+    fn into_input(self, base_dir: &Path) -> Result<TaskInput, Error> {
+        let umask = self.umask.as_deref()
+            .map(parse_umask)
+            .transpose()
+            .map_err(|err| anyhow!("Invalid umask for task {} : {}", self.name, err))?;
+        let memory = self.limits.memory.as_deref()
+            .map(parse_size)
+            .transpose()
+            .map_err(|err| anyhow!("Invalid memory limit for task {} : {}", self.name, err))?;
+        let wasm = match self.wasm {
+            Some(wasm) => Some(WasmModule {
+                source: match wasm.embed {
+                    true => WasmSource::Blob(fs::read(base_dir.join(&wasm.module))
+                        .context(format!("Error reading WASI module {} of task {}", wasm.module.display(), self.name))?),
+                    false => WasmSource::Path(base_dir.join(wasm.module)),
+                },
+                dir: wasm.dir.map(|dir| base_dir.join(dir)),
+                fuel: wasm.fuel,
+            }),
+            None => None,
+        };
+        let stdin = match (self.stdin, self.stdin_file) {
+            (Some(_), Some(_)) => return Err(anyhow!("Task {} can't define both stdin and stdin_file", self.name)),
+            (Some(stdin), None) => Some(stdin.into_bytes()),
+            (None, Some(stdin_file)) => Some(fs::read(base_dir.join(&stdin_file))
+                .context(format!("Error reading stdin file {} of task {}", stdin_file.display(), self.name))?),
+            (None, None) => None,
+        };
+        Ok(TaskInput {
+            name: Some(self.name),
+            command: self.command,
+            env: self.env.map(|env| env.into_iter().collect()),
+            timeout: self.timeout.map(Duration::from_secs),
+            dependencies: self.depends_on,
+            labels: self.labels.into_iter().collect(),
+            // The working directory of a task with a container is a path of its image
+            working_dir: match self.container {
+                Some(_) => self.working_dir,
+                None => self.working_dir.map(|working_dir| base_dir.join(working_dir)),
+            },
+            stdin,
+            umask,
+            limits: ResourceLimits {
+                cpu_time: self.limits.cpu_time.map(Duration::from_secs),
+                memory,
+                open_files: self.limits.open_files,
+                processes: self.limits.processes,
+            },
+            sandboxed: self.sandbox,
+            credentials: self.user.map(|user| Credentials { uid: user.uid, gid: user.gid, groups: user.groups }),
+            container: self.container.map(|container| Container {
+                rootfs: base_dir.join(container.rootfs),
+                mounts: container.mounts.into_iter()
+                    .map(|mount| Mount { source: base_dir.join(mount.source), destination: mount.destination, read_only: mount.read_only })
+                    .collect(),
+            }),
+            host: self.host,
+            wasm,
+            executor: self.executor,
+            artifacts: self.artifacts,
+            hooks: TaskHooks { pre: self.hooks.pre, post: self.hooks.post },
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use uuid::Uuid;
+
+    // Directory of the manifests of a test, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new() -> Self {
+            let directory = env::temp_dir().join(format!("tasc-manifest-{}", Uuid::new_v4()));
+            fs::create_dir_all(&directory).unwrap();
+            Fixture(fs::canonicalize(directory).unwrap())
+        }
+
+        fn manifest(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_load_manifest_should_parse_toml_and_yaml() {
+        let fixture = Fixture::new();
+        let toml = fixture.manifest("tasks.toml", r#"
+            [[tasks]]
+            name = "build"
+            command = "make"
+            timeout = 60
+            umask = "022"
+            labels = { team = "ci" }
+            limits = { memory = "512M", open_files = 64 }
+            hooks = { pre = "echo start" }
+
+            [[tasks]]
+            name = "test"
+            command = "make test"
+            depends_on = ["build"]
+        "#);
+        let yaml = fixture.manifest("tasks.yml", "
+tasks:
+  - name: build
+    command: make
+    timeout: 60
+    umask: \"022\"
+    labels:
+      team: ci
+    limits:
+      memory: 512M
+      open_files: 64
+    hooks:
+      pre: echo start
+  - name: test
+    command: make test
+    depends_on: [build]
+");
+
+        for path in [toml, yaml].iter() {
+            let tasks = load_manifest(path).unwrap();
+            assert_eq!(tasks.iter().map(|task| task.name.clone().unwrap()).collect::<Vec<String>>(), vec!["build", "test"], "{}", path.display());
+            assert_eq!(tasks[0].command, "make");
+            assert_eq!(tasks[0].timeout, Some(Duration::from_secs(60)));
+            assert_eq!(tasks[0].umask, Some(0o022));
+            assert_eq!(tasks[0].labels.get("team"), Some(&"ci".to_string()));
+            assert_eq!(tasks[0].limits, ResourceLimits { memory: Some(512 << 20), open_files: Some(64), ..ResourceLimits::default() });
+            assert_eq!(tasks[0].hooks, TaskHooks { pre: Some("echo start".to_string()), post: None });
+            assert_eq!(tasks[1].dependencies, vec!["build".to_string()]);
+        }
+    }
+
+    #[test]
+    fn test_load_manifest_should_refuse_unknown_extensions_and_fields() {
+        let fixture = Fixture::new();
+        let json = fixture.manifest("tasks.json", r#"{"tasks": [{"name": "build", "command": "make"}]}"#);
+        assert!(format!("{:#}", load_manifest(&json).unwrap_err()).contains("Unsupported manifest format"));
+
+        let unknown = fixture.manifest("unknown.toml", "[[tasks]]\nname = \"build\"\ncommand = \"make\"\ntimout = 60\n");
+        assert!(format!("{:#}", load_manifest(&unknown).unwrap_err()).contains("unknown field `timout`"));
+        let nested = fixture.manifest("nested.yaml", "tasks:\n  - name: build\n    command: make\n    limits:\n      memroy: 1G\n");
+        assert!(format!("{:#}", load_manifest(&nested).unwrap_err()).contains("unknown field `memroy`"));
+    }
+
+    #[test]
+    fn test_load_manifest_should_resolve_host_paths_from_its_directory() {
+        let fixture = Fixture::new();
+        fs::write(fixture.0.join("input.txt"), "payload").unwrap();
+        let path = fixture.manifest("tasks.toml", r#"
+            [[tasks]]
+            name = "local"
+            command = "cat"
+            working_dir = "work"
+            stdin_file = "input.txt"
+
+            [[tasks]]
+            name = "contained"
+            command = "ls"
+            working_dir = "/srv"
+            container = { rootfs = "rootfs", mounts = [{ source = "data", destination = "/data", read_only = true }] }
+        "#);
+
+        let tasks = load_manifest(&path).unwrap();
+        assert_eq!(tasks[0].working_dir, Some(fixture.0.join("work")));
+        assert_eq!(tasks[0].stdin, Some(b"payload".to_vec()));
+        // The working directory of a container is a path of its image
+        assert_eq!(tasks[1].working_dir, Some(PathBuf::from("/srv")));
+        assert_eq!(tasks[1].container, Some(Container {
+            rootfs: fixture.0.join("rootfs"),
+            mounts: vec![Mount { source: fixture.0.join("data"), destination: PathBuf::from("/data"), read_only: true }],
+        }));
+    }
+
+    #[test]
+    fn test_load_manifest_should_refuse_stdin_with_stdin_file() {
+        let fixture = Fixture::new();
+        fs::write(fixture.0.join("input.txt"), "payload").unwrap();
+        let path = fixture.manifest("tasks.toml", "[[tasks]]\nname = \"cat\"\ncommand = \"cat\"\nstdin = \"inline\"\nstdin_file = \"input.txt\"\n");
+
+        assert_eq!(load_manifest(&path).unwrap_err().to_string(), "Task cat can't define both stdin and stdin_file");
+    }
+}
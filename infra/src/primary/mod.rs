@@ -1,2 +1,4 @@
 pub mod cli;
+pub mod export;
+pub mod manifest;
 pub mod settings;
\ No newline at end of file
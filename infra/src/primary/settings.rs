@@ -1,20 +1,151 @@
 use anyhow::{anyhow, Error, Context};
 
-use config::Config;
+use config::{Config, Value};
+use domain::executor::model::model::{Credentials, RetentionPolicy, TaskOutcome};
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::primary::cli::{parse_duration, parse_size};
+
+const DEFAULT_OUTPUT_LIMIT: u64 = 1 << 20;
+const DEFAULT_COMPACT_AFTER: usize = 1000;
+const DEFAULT_RETENTION_INTERVAL: Duration = Duration::from_secs(3600);
+#[cfg(feature = "sqlite")]
+const DEFAULT_POOL_SIZE: u32 = 4;
+// Seconds
+#[cfg(feature = "sqlite")]
+const DEFAULT_BUSY_TIMEOUT: u64 = 5;
 
 
 #[derive(Debug)]
 pub enum StorageConfiguration {
+    #[cfg(feature = "sqlite")]
     Database {
-        database_url: String
+        database_url: String,
+        // Maximum number of connections opened on the database file
+        pool_size: u32,
+        // Time waited for the lock of another writer before failing
+        busy_timeout: Duration,
+        // Pending migrations applied at start instead of being refused
+        auto_migrate: bool,
+    },
+    // Tasks shared by the tasc instances using the same database
+    #[cfg(feature = "postgres")]
+    Postgres {
+        database_url: String,
+        auto_migrate: bool,
+    },
+    // Embedded store file which needs neither a SQL schema nor a system library
+    Kv {
+        path: PathBuf,
+    },
+    // Tasks lost at exit unless a snapshot file is given, the changes being journaled between snapshots
+    InMemory {
+        snapshot: Option<PathBuf>,
+        // Number of journal entries written before a new snapshot
+        compact_after: usize,
+    },
+}
+
+// Store of the events of the tasks, a durable storage needs a durable one
+#[derive(Debug)]
+pub enum EventStoreConfiguration {
+    #[cfg(feature = "sqlite")]
+    Database {
+        database_url: String,
+        busy_timeout: Duration,
+        auto_migrate: bool,
+    },
+    // Events kept in the database of the postgres storage
+    #[cfg(feature = "postgres")]
+    Postgres {
+        database_url: String,
+        auto_migrate: bool,
+    },
+    Kv {
+        path: PathBuf,
+    },
+    // Events appended to a file, beside the snapshot of the durable in memory storage
+    Journal {
+        path: PathBuf,
     },
     InMemory,
 }
 
+// Executors running processes of the host or of an image may define the credentials of the tasks they run, overriding execution.uid
+#[derive(Debug)]
+pub enum ExecutorConfiguration {
+    Local {
+        credentials: Option<Credentials>,
+    },
+    // Command run by a shell instead of being split on whitespaces
+    Shell {
+        shell: PathBuf,
+        credentials: Option<Credentials>,
+    },
+    Sandbox {
+        credentials: Option<Credentials>,
+    },
+    // OCI runtime running the tasks defining a container
+    Oci {
+        runtime: PathBuf,
+        bundles: PathBuf,
+        credentials: Option<Credentials>,
+    },
+    // ssh client settings, the host is used by the tasks which don't define theirs
+    Ssh {
+        host: Option<String>,
+        user: Option<String>,
+        identity: Option<PathBuf>,
+        known_hosts: Option<PathBuf>,
+    },
+    Wasm,
+}
+
+#[derive(Debug)]
+pub struct ExecutionConfiguration {
+    // cgroup v2 hierarchy delegated to tasc, resource limits fall back on rlimits only without it
+    pub cgroup: Option<PathBuf>,
+    // Credentials of the tasks which don't define theirs
+    pub credentials: Option<Credentials>,
+    // Bytes of stdout and stderr stored with a task, larger outputs keep only their head and tail
+    pub output_limit: u64,
+    // Directory where the full output of the truncated ones is written
+    pub output_logs: Option<PathBuf>,
+    // Executor of the tasks which don't name one
+    pub default: String,
+    // Executors the tasks may name even when they isolate less than the default one
+    pub weaker_executors: Vec<String>,
+    pub executors: Vec<(String, ExecutorConfiguration)>,
+}
+
+// Retention of the completed tasks applied by tasc purge, every interval with --daemon
+#[derive(Debug)]
+pub struct RetentionConfiguration {
+    pub policy: RetentionPolicy,
+    pub interval: Duration,
+}
+
+// Hooks run around every task, the ones of a task run inside of them
+#[derive(Debug)]
+pub struct HookConfiguration {
+    pub shell: PathBuf,
+    pub pre: Option<String>,
+    pub post: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Configuration {
-    pub storage: StorageConfiguration
+    pub storage: StorageConfiguration,
+    pub events: EventStoreConfiguration,
+    pub execution: ExecutionConfiguration,
+    // Directory of the artifact store
+    pub artifacts: PathBuf,
+    pub hooks: HookConfiguration,
+    pub retention: RetentionConfiguration,
 }
 
 pub fn load_settings() -> Result<Configuration, Error> {
@@ -30,8 +161,17 @@ impl TryFrom<Config> for Configuration {
     type Error = Error;
 
     fn try_from(value: Config) -> Result<Self, Self::Error> {
-        StorageConfiguration::try_from(value)
-            .map(|storage| Configuration { storage })
+        let artifacts = value.get_str("artifacts.path").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("artifacts"));
+        let hooks = HookConfiguration {
+            shell: value.get_str("hooks.shell").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("/bin/sh")),
+            pre: value.get_str("hooks.pre").ok(),
+            post: value.get_str("hooks.post").ok(),
+        };
+        StorageConfiguration::try_from(value.clone())
+            .and_then(|storage| EventStoreConfiguration::try_from(value.clone())
+                .and_then(|events| RetentionConfiguration::try_from(value.clone())
+                    .and_then(|retention| ExecutionConfiguration::try_from(value)
+                        .map(|execution| Configuration { storage, events, execution, artifacts, hooks, retention }))))
             .context("Error loading settings")
     }
 }
@@ -41,9 +181,298 @@ impl TryFrom<Config> for StorageConfiguration {
 
     fn try_from(value: Config) -> Result<Self, Self::Error> {
         match value.get_str("storage")?.as_str() {
-            "database" => Ok(StorageConfiguration::Database { database_url: value.get_str("DATABASE_URL").or(value.get_str("database.url"))? }),
-            "inmemory" => Ok(StorageConfiguration::InMemory),
+            #[cfg(feature = "sqlite")]
+            "database" => Ok(StorageConfiguration::Database {
+                database_url: value.get_str("DATABASE_URL").or(value.get_str("database.url"))?,
+                pool_size: pool_size(&value)?,
+                busy_timeout: busy_timeout(&value)?,
+                auto_migrate: auto_migrate(&value),
+            }),
+            #[cfg(feature = "postgres")]
+            "postgres" => Ok(StorageConfiguration::Postgres {
+                database_url: value.get_str("POSTGRES_URL").or(value.get_str("postgres.url"))?,
+                auto_migrate: auto_migrate(&value),
+            }),
+            #[cfg(not(feature = "sqlite"))]
+            "database" => Err(anyhow!("tasc is built without the sqlite feature needed by the database storage")),
+            #[cfg(not(feature = "postgres"))]
+            "postgres" => Err(anyhow!("tasc is built without the postgres feature needed by the postgres storage")),
+            "kv" => Ok(StorageConfiguration::Kv { path: value.get_str("kv.path").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("tasks.redb")) }),
+            "inmemory" => Ok(StorageConfiguration::InMemory {
+                snapshot: value.get_str("inmemory.path").ok().map(PathBuf::from),
+                compact_after: value.get_int("inmemory.compact_after").map(|compact_after| compact_after as usize).unwrap_or(DEFAULT_COMPACT_AFTER),
+            }),
             other => Err(anyhow!("{} is not a valid configuration for storage", other))
         }
     }
+}
+
+impl TryFrom<Config> for EventStoreConfiguration {
+    type Error = Error;
+
+    // The events are kept by the storage of the tasks unless another store is given, in the journal of the durable in
+    // memory storage. They are only lost at exit with the tasks
+    fn try_from(value: Config) -> Result<Self, Self::Error> {
+        let tasks = value.get_str("storage")?;
+        let snapshot = value.get_str("inmemory.path").ok().map(PathBuf::from);
+        let storage = value.get_str("events.storage").unwrap_or_else(|_| match tasks.as_str() {
+            "inmemory" if snapshot.is_some() => "journal".to_string(),
+            _ => tasks.clone(),
+        });
+        match storage.as_str() {
+            #[cfg(feature = "sqlite")]
+            "database" => Ok(EventStoreConfiguration::Database {
+                database_url: value.get_str("events.url").or(value.get_str("DATABASE_URL")).or(value.get_str("database.url"))?,
+                busy_timeout: busy_timeout(&value)?,
+                auto_migrate: auto_migrate(&value),
+            }),
+            #[cfg(feature = "postgres")]
+            "postgres" if tasks == "postgres" => Ok(EventStoreConfiguration::Postgres {
+                database_url: value.get_str("POSTGRES_URL").or(value.get_str("postgres.url"))?,
+                auto_migrate: auto_migrate(&value),
+            }),
+            #[cfg(feature = "postgres")]
+            "postgres" => Err(anyhow!("The postgres event store keeps the events in the database of the postgres storage, not of the {} storage", tasks)),
+            #[cfg(not(feature = "sqlite"))]
+            "database" => Err(anyhow!("tasc is built without the sqlite feature needed by the database event store")),
+            #[cfg(not(feature = "postgres"))]
+            "postgres" => Err(anyhow!("tasc is built without the postgres feature needed by the postgres event store")),
+            "kv" => Ok(EventStoreConfiguration::Kv {
+                path: value.get_str("events.path").or(value.get_str("kv.path")).map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("tasks.redb")),
+            }),
+            "journal" => match value.get_str("events.path").ok().map(PathBuf::from).or_else(|| snapshot.map(|snapshot| {
+                let mut path = snapshot.into_os_string();
+                path.push(".events");
+                PathBuf::from(path)
+            })) {
+                Some(path) => Ok(EventStoreConfiguration::Journal { path }),
+                None => Err(anyhow!("The journal event store needs events.path or inmemory.path")),
+            },
+            "inmemory" if tasks != "inmemory" || snapshot.is_some() => Err(anyhow!("The events of the durable {} storage can't be kept in memory", tasks)),
+            "inmemory" => Ok(EventStoreConfiguration::InMemory),
+            other => Err(anyhow!("{} is not a valid configuration for events storage", other))
+        }
+    }
+}
+
+// A pool needs a connection, whose number must fit a u32
+#[cfg(feature = "sqlite")]
+fn pool_size(value: &Config) -> Result<u32, Error> {
+    match value.get_int("database.pool_size") {
+        Ok(pool_size) => u32::try_from(pool_size).ok().filter(|pool_size| *pool_size >= 1)
+            .ok_or_else(|| anyhow!("{} is not a valid database.pool_size, at least one connection is needed", pool_size)),
+        Err(_) => Ok(DEFAULT_POOL_SIZE),
+    }
+}
+
+// Seconds, a negative timeout is refused rather than wrapped around
+#[cfg(feature = "sqlite")]
+fn busy_timeout(value: &Config) -> Result<Duration, Error> {
+    match value.get_int("database.busy_timeout") {
+        Ok(timeout) => u64::try_from(timeout).map(Duration::from_secs)
+            .map_err(|_| anyhow!("{} is not a valid database.busy_timeout", timeout)),
+        Err(_) => Ok(Duration::from_secs(DEFAULT_BUSY_TIMEOUT)),
+    }
+}
+
+// Every database is migrated by tasc db unless told otherwise, a production schema doesn't change at start
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn auto_migrate(value: &Config) -> bool {
+    value.get_bool("database.auto_migrate").unwrap_or(false)
+}
+
+impl TryFrom<Config> for RetentionConfiguration {
+    type Error = Error;
+
+    fn try_from(value: Config) -> Result<Self, Self::Error> {
+        let duration = |key: &str| value.get_str(key).ok()
+            .map(|duration| parse_duration(&duration).map_err(|err| anyhow!(err)))
+            .transpose();
+        Ok(RetentionConfiguration {
+            policy: RetentionPolicy {
+                max_age: duration("retention.max_age")?,
+                max_per_name: value.get_int("retention.max_per_name").ok().map(|max| max as usize),
+                keep_failures: value.get_int("retention.keep_failures").ok().map(|keep| keep as usize),
+                outcomes: value.get_array("retention.statuses").unwrap_or_default().into_iter()
+                    .map(|status| status.into_str().map_err(Error::from)
+                        .and_then(|status| status.parse::<TaskOutcome>().map_err(Error::from)))
+                    .collect::<Result<Vec<TaskOutcome>, Error>>()?,
+            },
+            interval: duration("retention.interval")?.unwrap_or(DEFAULT_RETENTION_INTERVAL),
+        })
+    }
+}
+
+impl TryFrom<Config> for ExecutionConfiguration {
+    type Error = Error;
+
+    fn try_from(value: Config) -> Result<Self, Self::Error> {
+        // Every kind of executor is available under its own name when none is declared
+        let executors = match value.get_table("executors") {
+            Ok(executors) => executors.into_iter()
+                .map(|(name, executor)| executor.into_table().map_err(Error::from)
+                    .and_then(|executor| ExecutorConfiguration::try_from((name.as_str(), executor)))
+                    .map(|executor| (name, executor)))
+                .collect::<Result<Vec<(String, ExecutorConfiguration)>, Error>>()?,
+            Err(_) => ["local", "shell", "sandbox", "oci", "ssh", "wasm"].iter()
+                .map(|kind| ExecutorConfiguration::try_from((*kind, HashMap::new()))
+                    .map(|executor| (kind.to_string(), executor)))
+                .collect::<Result<Vec<(String, ExecutorConfiguration)>, Error>>()?,
+        };
+        let default = value.get_str("execution.default").unwrap_or_else(|_| "local".to_string());
+        if !executors.iter().any(|(name, _)| *name == default) {
+            return Err(anyhow!("Default executor {} is not declared", default));
+        }
+        let weaker_executors = value.get_array("execution.weaker_executors")
+            .map(|names| names.into_iter().map(Value::into_str).collect::<Result<Vec<String>, _>>())
+            .unwrap_or_else(|_| Ok(vec![]))?;
+        if let Some(name) = weaker_executors.iter().find(|weaker| !executors.iter().any(|(name, _)| name == *weaker)) {
+            return Err(anyhow!("Executor {} allowed below the default one is not declared", name));
+        }
+        Ok(ExecutionConfiguration {
+            cgroup: value.get_str("execution.cgroup").ok().map(PathBuf::from),
+            output_limit: match value.get_str("execution.output.limit") {
+                Ok(limit) => parse_size(&limit).map_err(|err| anyhow!(err))?,
+                Err(_) => DEFAULT_OUTPUT_LIMIT,
+            },
+            output_logs: value.get_str("execution.output.logs").ok().map(PathBuf::from),
+            credentials: parse_credentials(&mut value.get_table("execution").unwrap_or_default(), "execution")?,
+            default,
+            weaker_executors,
+            executors,
+        })
+    }
+}
+
+impl TryFrom<(&str, HashMap<String, Value>)> for ExecutorConfiguration {
+    type Error = Error;
+
+    // The kind of the executor is its name unless a type is given
+    fn try_from((name, mut table): (&str, HashMap<String, Value>)) -> Result<Self, Self::Error> {
+        let credentials = parse_credentials(&mut table, &format!("executor {}", name))?;
+        let mut setting = |key: &str| table.remove(key).map(|value| value.into_str()).transpose()
+            .context(format!("Invalid setting {} of executor {}", key, name));
+        let kind = setting("type")?.unwrap_or_else(|| name.to_string());
+        if credentials.is_some() && !["local", "shell", "sandbox", "oci"].contains(&kind.as_str()) {
+            return Err(anyhow!("Executor {} of type {} can't define the user of its tasks", name, kind));
+        }
+        match kind.as_str() {
+            "local" => Ok(ExecutorConfiguration::Local { credentials }),
+            "shell" => Ok(ExecutorConfiguration::Shell { shell: setting("shell")?.map(PathBuf::from).unwrap_or_else(|| PathBuf::from("/bin/sh")), credentials }),
+            "sandbox" => Ok(ExecutorConfiguration::Sandbox { credentials }),
+            "oci" => Ok(ExecutorConfiguration::Oci {
+                runtime: setting("runtime")?.map(PathBuf::from).unwrap_or_else(|| PathBuf::from("runc")),
+                bundles: setting("bundles")?.map(PathBuf::from).unwrap_or_else(env::temp_dir),
+                credentials,
+            }),
+            "ssh" => Ok(ExecutorConfiguration::Ssh {
+                host: setting("host")?,
+                user: setting("user")?,
+                identity: setting("identity")?.map(PathBuf::from),
+                known_hosts: setting("known_hosts")?.map(PathBuf::from),
+            }),
+            "wasm" => Ok(ExecutorConfiguration::Wasm),
+            other => Err(anyhow!("{} is not a valid type for executor {}", other, name)),
+        }
+    }
+}
+
+// uid, gid and groups of a settings table, the ids must fit a u32
+fn parse_credentials(table: &mut HashMap<String, Value>, section: &str) -> Result<Option<Credentials>, Error> {
+    let id = |value: Value, key: &str| -> Result<u32, Error> {
+        let id = value.into_int().context(format!("Invalid setting {} of {}", key, section))?;
+        u32::try_from(id).map_err(|_| anyhow!("{} is not a valid {} of {}", id, key, section))
+    };
+    match (table.remove("uid"), table.remove("gid")) {
+        (Some(uid), Some(gid)) => Ok(Some(Credentials {
+            uid: id(uid, "uid")?,
+            gid: id(gid, "gid")?,
+            groups: match table.remove("groups") {
+                Some(groups) => groups.into_array().context(format!("Invalid setting groups of {}", section))?
+                    .into_iter()
+                    .map(|group| id(group, "group"))
+                    .collect::<Result<Vec<u32>, Error>>()?,
+                None => vec![],
+            },
+        })),
+        (None, None) => Ok(None),
+        _ => Err(anyhow!("uid and gid of {} must be defined together", section)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn table(values: Vec<(&str, Value)>) -> HashMap<String, Value> {
+        values.into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+    }
+
+    fn settings(values: Vec<(&str, &str)>) -> Config {
+        let mut settings = Config::default();
+        for (key, value) in values {
+            settings.set(key, value).unwrap();
+        }
+        settings
+    }
+
+    #[test]
+    fn test_events_should_be_kept_by_a_durable_store_with_a_durable_storage() {
+        let events = |values: Vec<(&str, &str)>| EventStoreConfiguration::try_from(settings(values));
+        assert!(matches!(events(vec![("storage", "database"), ("database.url", "tasks.db")]).unwrap(),
+                         EventStoreConfiguration::Database { ref database_url, .. } if database_url == "tasks.db"));
+        assert!(matches!(events(vec![("storage", "postgres"), ("postgres.url", "host=db")]).unwrap(),
+                         EventStoreConfiguration::Postgres { ref database_url, .. } if database_url == "host=db"));
+        assert!(matches!(events(vec![("storage", "kv"), ("kv.path", "tasks.redb")]).unwrap(),
+                         EventStoreConfiguration::Kv { ref path } if path.as_path() == Path::new("tasks.redb")));
+        assert!(matches!(events(vec![("storage", "inmemory"), ("inmemory.path", "tasks.json")]).unwrap(),
+                         EventStoreConfiguration::Journal { ref path } if path.as_path() == Path::new("tasks.json.events")));
+        assert!(matches!(events(vec![("storage", "inmemory")]).unwrap(), EventStoreConfiguration::InMemory));
+        assert!(matches!(events(vec![("storage", "kv"), ("events.storage", "database"), ("events.url", "events.db")]).unwrap(),
+                         EventStoreConfiguration::Database { ref database_url, .. } if database_url == "events.db"));
+
+        assert_eq!(events(vec![("storage", "kv"), ("events.storage", "inmemory")]).unwrap_err().to_string(),
+                   "The events of the durable kv storage can't be kept in memory");
+        assert_eq!(events(vec![("storage", "inmemory"), ("inmemory.path", "tasks.json"), ("events.storage", "inmemory")]).unwrap_err().to_string(),
+                   "The events of the durable inmemory storage can't be kept in memory");
+        assert!(events(vec![("storage", "database"), ("events.storage", "postgres"), ("postgres.url", "host=db")]).is_err());
+        assert!(events(vec![("storage", "inmemory"), ("events.storage", "journal")]).is_err());
+    }
+
+    #[test]
+    fn test_parse_credentials_should_refuse_ids_out_of_range() {
+        let mut credentials = table(vec![("uid", Value::from(1000)), ("gid", Value::from(100)), ("groups", Value::from(vec![Value::from(10)]))]);
+        assert_eq!(parse_credentials(&mut credentials, "execution").unwrap(), Some(Credentials { uid: 1000, gid: 100, groups: vec![10] }));
+        let mut negative = table(vec![("uid", Value::from(-1)), ("gid", Value::from(100))]);
+        assert_eq!(parse_credentials(&mut negative, "execution").unwrap_err().to_string(), "-1 is not a valid uid of execution");
+        let mut oversized = table(vec![("uid", Value::from(0)), ("gid", Value::from(1i64 << 32))]);
+        assert_eq!(parse_credentials(&mut oversized, "executor build").unwrap_err().to_string(), "4294967296 is not a valid gid of executor build");
+        let mut missing = table(vec![("uid", Value::from(0))]);
+        assert!(parse_credentials(&mut missing, "execution").is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_database_settings_should_refuse_values_out_of_range() {
+        let storage = |values: Vec<(&str, &str)>| StorageConfiguration::try_from(settings([vec![("storage", "database"), ("database.url", "tasks.db")], values].concat()));
+        assert!(matches!(storage(vec![("database.pool_size", "2"), ("database.busy_timeout", "10")]).unwrap(),
+                         StorageConfiguration::Database { pool_size: 2, busy_timeout, .. } if busy_timeout == Duration::from_secs(10)));
+        assert!(matches!(storage(vec![]).unwrap(), StorageConfiguration::Database { pool_size: DEFAULT_POOL_SIZE, .. }));
+        assert_eq!(storage(vec![("database.pool_size", "0")]).unwrap_err().to_string(),
+                   "0 is not a valid database.pool_size, at least one connection is needed");
+        assert_eq!(storage(vec![("database.pool_size", "4294967296")]).unwrap_err().to_string(),
+                   "4294967296 is not a valid database.pool_size, at least one connection is needed");
+        assert_eq!(storage(vec![("database.busy_timeout", "-1")]).unwrap_err().to_string(), "-1 is not a valid database.busy_timeout");
+        assert!(EventStoreConfiguration::try_from(settings(vec![("storage", "database"), ("database.url", "tasks.db"), ("database.busy_timeout", "-5")])).is_err());
+    }
+
+    #[test]
+    fn test_executor_credentials_should_only_be_accepted_on_the_host() {
+        let credentials = || vec![("uid", Value::from(1000)), ("gid", Value::from(1000))];
+        assert!(matches!(ExecutorConfiguration::try_from(("build", table([vec![("type", Value::from("shell"))], credentials()].concat()))).unwrap(),
+                         ExecutorConfiguration::Shell { credentials: Some(_), .. }));
+        assert_eq!(ExecutorConfiguration::try_from(("ssh", table(credentials()))).unwrap_err().to_string(),
+                   "Executor ssh of type ssh can't define the user of its tasks");
+    }
 }
\ No newline at end of file
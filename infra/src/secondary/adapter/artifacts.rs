@@ -0,0 +1,236 @@
+use domain::executor::model::model::{Artifact, Task};
+use domain::executor::ports::secondary::ArtifactStorePort;
+use anyhow::{anyhow, Error, Context};
+use glob::Pattern;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+// Artifacts stored on disk under the SHA-256 of their content, identical files are stored once
+pub struct FileArtifactStoreAdapter {
+    root: PathBuf,
+    // Directory of the output logs of the executions, the only one where logs are removed
+    logs: Option<PathBuf>,
+}
+
+impl ArtifactStorePort for FileArtifactStoreAdapter {
+    fn collect(&self, task: &Task) -> Result<Vec<Artifact>, Error> {
+        let working_dir = match task.working_dir {
+            Some(ref working_dir) => working_dir.clone(),
+            None => env::current_dir()?,
+        };
+        let root = working_dir.canonicalize()
+            .context(format!("Error resolving working directory {}", working_dir.display()))?;
+        let mut paths = BTreeSet::new();
+        for pattern in task.artifacts.iter() {
+            let pattern = format!("{}/{}", Pattern::escape(&working_dir.to_string_lossy()), pattern);
+            for path in glob::glob(&pattern).context(format!("Invalid artifact pattern {}", pattern))? {
+                let path = path?;
+                // A task could otherwise link any file tasc can read into the store
+                if fs::symlink_metadata(&path)?.file_type().is_symlink() {
+                    return Err(anyhow!("Artifact {} is a symbolic link", path.display()));
+                }
+                if !path.is_file() {
+                    continue;
+                }
+                // The directories matched by the pattern may be links too
+                if !path.canonicalize()?.starts_with(&root) {
+                    return Err(anyhow!("Artifact {} is outside of the working directory", path.display()));
+                }
+                paths.insert(path);
+            }
+        }
+        paths.into_iter()
+            .map(|path| self.store(&path)
+                .map(|(digest, size)| Artifact { path: path.strip_prefix(&working_dir).unwrap_or(&path).to_path_buf(), digest, size })
+                .context(format!("Error storing artifact {}", path.display())))
+            .collect()
+    }
+
+    fn extract(&self, artifact: &Artifact, destination: &Path) -> Result<(), Error> {
+        let source = self.blob(&artifact.digest)?;
+        if !source.is_file() {
+            return Err(anyhow!("Content {} of artifact {} is missing from the store", artifact.digest, artifact.path.display()));
+        }
+        fs::copy(&source, destination)
+            .map(|_| ())
+            .context(format!("Error copying artifact to {}", destination.display()))
+    }
+
+    // The logs are written in the log directory itself, a log recorded elsewhere, as by an import, is left in place
+    fn remove(&self, digests: &[String], logs: &[PathBuf]) -> Result<(), Error> {
+        for digest in digests {
+            let blob = self.blob(digest)?;
+            remove_file(&blob).context(format!("Error removing content {} from the store", digest))?;
+        }
+        let directory = match self.logs.as_ref().map(|logs| logs.canonicalize()) {
+            Some(Ok(directory)) => directory,
+            _ => return Ok(()),
+        };
+        for log in logs {
+            if log.parent().and_then(|parent| parent.canonicalize().ok()).as_ref() == Some(&directory) {
+                remove_file(log).context(format!("Error removing log {}", log.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileArtifactStoreAdapter {
+    pub fn new(root: PathBuf, logs: Option<PathBuf>) -> FileArtifactStoreAdapter {
+        FileArtifactStoreAdapter { root, logs }
+    }
+
+    // Blobs are sharded by the first byte of their digest
+    fn blob(&self, digest: &str) -> Result<PathBuf, Error> {
+        match Artifact::is_digest(digest) {
+            true => Ok(self.root.join(&digest[..2]).join(&digest[2..])),
+            false => Err(anyhow!("{:?} is not a SHA-256 digest", digest)),
+        }
+    }
+
+    // Copy the file in a temporary blob while hashing it, then move it under its digest
+    fn store(&self, path: &Path) -> io::Result<(String, u64)> {
+        fs::create_dir_all(&self.root)?;
+        let temporary = self.root.join(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        let result = copy_hashed(path, &temporary).and_then(|(digest, size)| {
+            let blob = self.blob(&digest).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+            fs::create_dir_all(blob.parent().unwrap_or(&self.root))?;
+            match blob.exists() {
+                true => fs::remove_file(&temporary),
+                false => fs::rename(&temporary, &blob),
+            }.map(|_| (digest, size))
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+}
+
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+// The source is opened without following a link it may have been replaced with since it was checked
+fn copy_hashed(source: &Path, destination: &Path) -> io::Result<(String, u64)> {
+    let mut input = OpenOptions::new().read(true).custom_flags(libc::O_NOFOLLOW).open(source)?;
+    if !input.metadata()?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a regular file", source.display())));
+    }
+    let mut output = File::create(destination)?;
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        output.write_all(&buffer[..read])?;
+        size += read as u64;
+    }
+    output.sync_all()?;
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::task;
+    use std::os::unix::fs::symlink;
+
+    // Working directory and store of a test, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Fixture {
+            let root = env::temp_dir().join(format!("tasc-artifacts-{}-{}", name, std::process::id()));
+            fs::create_dir_all(root.join("work/dist")).unwrap();
+            fs::write(root.join("secret"), "secret").unwrap();
+            fs::write(root.join("work/dist/report.pdf"), "report").unwrap();
+            Fixture(root)
+        }
+
+        fn store(&self) -> FileArtifactStoreAdapter {
+            FileArtifactStoreAdapter::new(self.0.join("store"), Some(self.0.join("logs")))
+        }
+
+        fn task(&self, pattern: &str) -> Task {
+            Task { working_dir: Some(self.0.join("work")), artifacts: vec![pattern.to_string()], ..task("make report") }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_collect_should_store_and_extract_files() {
+        let fixture = Fixture::new("collect");
+        let store = fixture.store();
+        let artifacts = store.collect(&fixture.task("dist/*.pdf")).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!((artifacts[0].path.as_path(), artifacts[0].size), (Path::new("dist/report.pdf"), 6));
+        store.extract(&artifacts[0], &fixture.0.join("extracted.pdf")).unwrap();
+        assert_eq!(fs::read_to_string(fixture.0.join("extracted.pdf")).unwrap(), "report");
+    }
+
+    #[test]
+    fn test_collect_should_refuse_symbolic_links() {
+        let fixture = Fixture::new("links");
+        symlink(fixture.0.join("secret"), fixture.0.join("work/dist/secret.pdf")).unwrap();
+        let error = fixture.store().collect(&fixture.task("dist/*.pdf")).unwrap_err();
+        assert!(error.to_string().contains("is a symbolic link"), "{}", error);
+        fs::remove_file(fixture.0.join("work/dist/secret.pdf")).unwrap();
+        // A linked directory leads out of the working directory
+        symlink(&fixture.0, fixture.0.join("work/outside")).unwrap();
+        let error = fixture.store().collect(&fixture.task("outside/secret")).unwrap_err();
+        assert!(error.to_string().contains("is outside of the working directory"), "{}", error);
+        assert!(!fixture.0.join("store").exists());
+    }
+
+    #[test]
+    fn test_remove_should_only_remove_given_contents_and_logs_of_the_log_directory() {
+        let fixture = Fixture::new("remove");
+        let store = fixture.store();
+        fs::write(fixture.0.join("work/dist/other.pdf"), "other").unwrap();
+        let removed = store.collect(&fixture.task("dist/report.pdf")).unwrap().remove(0);
+        let kept = store.collect(&fixture.task("dist/other.pdf")).unwrap().remove(0);
+        fs::create_dir_all(fixture.0.join("logs")).unwrap();
+        fs::write(fixture.0.join("logs/task.stdout"), "output").unwrap();
+        fs::write(fixture.0.join("task.stdout"), "output").unwrap();
+        let logs = vec![fixture.0.join("logs/task.stdout"), fixture.0.join("logs/../task.stdout"), fixture.0.join("logs/missing.stdout")];
+
+        store.remove(&[removed.digest.clone(), "0".repeat(64)], &logs).unwrap();
+
+        assert!(store.extract(&removed, &fixture.0.join("extracted")).is_err());
+        store.extract(&kept, &fixture.0.join("extracted")).unwrap();
+        assert!(!fixture.0.join("logs/task.stdout").exists());
+        assert!(fixture.0.join("task.stdout").exists(), "A log out of the log directory must be left");
+        assert!(store.remove(&["../secret".to_string()], &[]).is_err());
+        assert!(fixture.0.join("secret").exists());
+    }
+
+    #[test]
+    fn test_extract_should_refuse_invalid_digests() {
+        let fixture = Fixture::new("digests");
+        let store = fixture.store();
+        for digest in ["", "a", "../../../etc/shadow", "é", &"A".repeat(64), &format!("{}/", "0".repeat(63))] {
+            let artifact = Artifact { path: PathBuf::from("secret"), digest: digest.to_string(), size: 6 };
+            let error = store.extract(&artifact, &fixture.0.join("extracted")).unwrap_err();
+            assert_eq!(error.to_string(), format!("{:?} is not a SHA-256 digest", digest));
+        }
+        assert!(!fixture.0.join("extracted").exists());
+    }
+}
@@ -0,0 +1,332 @@
+use anyhow::{Error, Context};
+#[cfg(feature = "sqlite")]
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+#[cfg(feature = "sqlite")]
+use diesel::SqliteConnection;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use domain::executor::model::model::{RecordedEvent, TaskEvent};
+#[cfg(feature = "sqlite")]
+use domain::executor::ports::secondary::EventStorePort;
+
+use super::record::{self, EventRecord};
+#[cfg(feature = "sqlite")]
+use crate::secondary::adapter::storage::database::migrations::{self, EVENTS_MIGRATIONS};
+
+// Rows can only be inserted, the migrations add triggers aborting their update or deletion. The outputs are not
+// kept, the sqlite column of their content is emptied by the migration adding their size
+table! {
+    task_events (sequence) {
+        sequence -> BigInt,
+        task_id -> Text,
+        at -> BigInt,
+        actor -> Text,
+        kind -> Text,
+        command -> Nullable<Text>,
+        size -> Nullable<BigInt>,
+        success -> Nullable<Bool>,
+        exit_code -> Nullable<Integer>,
+        truncated -> Nullable<BigInt>,
+        log -> Nullable<Text>,
+        message -> Nullable<Text>,
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "task_events"]
+pub(super) struct NewDbTaskEvent {
+    task_id: String,
+    // Milliseconds since the epoch
+    at: i64,
+    actor: String,
+    kind: String,
+    command: Option<String>,
+    size: Option<i64>,
+    success: Option<bool>,
+    exit_code: Option<i32>,
+    truncated: Option<i64>,
+    log: Option<String>,
+    message: Option<String>,
+}
+
+impl NewDbTaskEvent {
+    pub(super) fn new(task_id: &str, actor: &str, event: &TaskEvent) -> Result<NewDbTaskEvent, Error> {
+        let record = EventRecord::from(event);
+        Ok(NewDbTaskEvent {
+            task_id: task_id.to_string(),
+            at: record::now_millis()? as i64,
+            actor: actor.to_string(),
+            kind: record.kind,
+            command: record.command,
+            size: record.size.map(|size| size as i64),
+            success: record.success,
+            exit_code: record.exit_code,
+            truncated: record.truncated.map(|truncated| truncated as i64),
+            log: record.log.map(|log| log.to_string_lossy().into_owned()),
+            message: record.message,
+        })
+    }
+}
+
+#[derive(Queryable)]
+pub(super) struct DbTaskEvent {
+    sequence: i64,
+    task_id: String,
+    at: i64,
+    actor: String,
+    kind: String,
+    command: Option<String>,
+    size: Option<i64>,
+    success: Option<bool>,
+    exit_code: Option<i32>,
+    truncated: Option<i64>,
+    log: Option<String>,
+    message: Option<String>,
+}
+
+// Event log kept in the tables of the sqlite storage, or in a database file of its own
+#[cfg(feature = "sqlite")]
+pub struct SqliteEventStoreAdapter {
+    connection: SqliteConnection,
+    actor: String,
+}
+
+#[cfg(feature = "sqlite")]
+impl EventStorePort for SqliteEventStoreAdapter {
+    fn append(&mut self, task_id: &str, event: TaskEvent) -> Result<(), Error> {
+        diesel::insert_into(task_events::table)
+            .values(&NewDbTaskEvent::new(task_id, &self.actor, &event)?)
+            .execute(&self.connection)
+            .map(|_| ())
+            .context(format!("Error inserting in db event {} of task {}", event, task_id))
+    }
+
+    fn events(&mut self, task_id: &str) -> Result<Vec<RecordedEvent>, Error> {
+        task_events::table.filter(task_events::task_id.eq(task_id))
+            .order(task_events::sequence)
+            .load::<DbTaskEvent>(&self.connection)
+            .context(format!("Error loading from database events of task {}", task_id))?
+            .into_iter()
+            .map(RecordedEvent::try_from)
+            .collect()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteEventStoreAdapter {
+    pub fn new(database_url: &str, busy_timeout: Duration, auto_migrate: bool, actor: String) -> Result<SqliteEventStoreAdapter, Error> {
+        let connection = SqliteConnection::establish(database_url)
+            .context("Error connecting to event database")?;
+        connection.batch_execute(&format!("PRAGMA busy_timeout = {}; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;", busy_timeout.as_millis()))?;
+        connection.immediate_transaction(|| migrations::prepare(&connection, "events", EVENTS_MIGRATIONS, auto_migrate))?;
+        Ok(SqliteEventStoreAdapter { connection, actor })
+    }
+}
+
+impl TryFrom<DbTaskEvent> for RecordedEvent {
+    type Error = Error;
+
+    fn try_from(row: DbTaskEvent) -> Result<Self, Self::Error> {
+        let record = EventRecord {
+            kind: row.kind,
+            command: row.command,
+            size: row.size.map(|size| size as u64),
+            success: row.success,
+            exit_code: row.exit_code,
+            truncated: row.truncated.map(|truncated| truncated as u64),
+            log: row.log.map(PathBuf::from),
+            message: row.message,
+        };
+        Ok(RecordedEvent {
+            event: record.into_event(&row.task_id)?,
+            task_id: row.task_id,
+            sequence: row.sequence as u64,
+            at: UNIX_EPOCH + Duration::from_millis(row.at as u64),
+            actor: row.actor,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use diesel::dsl::sql;
+    use diesel::sql_types::BigInt;
+    use domain::executor::model::model::{ResourceLimits, RetentionPolicy, TaskHooks, TaskId, TaskOutput, TaskStatus};
+    use domain::executor::ports::primary::{TaskInput, TaskSchedulerPort};
+    use domain::executor::ports::secondary::ArtifactStorePort;
+    use domain::executor::service::task_execution::TaskScheduler;
+    use im::HashMap;
+    use std::{env, fs, io};
+    use uuid::Uuid;
+    use crate::secondary::adapter::artifacts::FileArtifactStoreAdapter;
+    use crate::secondary::adapter::execution::{LocalExecutionAdapter, OutputCapture};
+    use crate::secondary::adapter::hooks::ShellHookAdapter;
+    use crate::secondary::adapter::id_generator::UUIDGeneratorAdapter;
+    use crate::secondary::adapter::storage::database::SqliteStorageAdapter;
+
+    // Directory of the database of a test, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new() -> Self {
+            let directory = env::temp_dir().join(format!("tasc-events-{}", Uuid::new_v4()));
+            fs::create_dir_all(&directory).unwrap();
+            Fixture(directory)
+        }
+
+        fn database(&self) -> String {
+            self.0.join("tasc.db").to_string_lossy().into_owned()
+        }
+
+        fn adapter(&self) -> SqliteEventStoreAdapter {
+            SqliteEventStoreAdapter::new(&self.database(), Duration::from_secs(5), true, String::from("tasc@host")).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn count(connection: &SqliteConnection, query: &str) -> i64 {
+        diesel::select(sql::<BigInt>(&format!("({})", query))).get_result(connection).unwrap()
+    }
+
+    #[test]
+    fn test_events_should_be_read_in_the_order_of_their_append() {
+        let fixture = Fixture::new();
+        let mut store = fixture.adapter();
+        store.append("first", TaskEvent::Submitted { command: String::from("echo first") }).unwrap();
+        store.append("second", TaskEvent::Submitted { command: String::from("echo second") }).unwrap();
+        store.append("first", TaskEvent::Started).unwrap();
+        store.append("first", TaskEvent::ArtifactsFailed { message: String::from("No space left") }).unwrap();
+        store.append("second", TaskEvent::Cancelled).unwrap();
+        store.append("first", TaskEvent::Completed { success: false, exit_code: None, truncated: 0, log: None }).unwrap();
+
+        let events = fixture.adapter().events("first").unwrap();
+        assert_eq!(events.iter().map(|recorded| recorded.event.clone()).collect::<Vec<TaskEvent>>(), vec![
+            TaskEvent::Submitted { command: String::from("echo first") },
+            TaskEvent::Started,
+            TaskEvent::ArtifactsFailed { message: String::from("No space left") },
+            TaskEvent::Completed { success: false, exit_code: None, truncated: 0, log: None },
+        ]);
+        assert!(events.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+        assert!(events.iter().all(|recorded| recorded.task_id == "first" && recorded.actor == "tasc@host"));
+        assert_eq!(store.events("second").unwrap().len(), 2);
+        assert!(store.events("unknown").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_events_should_project_to_the_status_of_the_task() {
+        let fixture = Fixture::new();
+        let mut store = fixture.adapter();
+        let status = TaskStatus::Error(TaskOutput { content: b"failed".to_vec(), truncated: 3, log: Some(PathBuf::from("/var/log/tasc/1.log")) }, Some(2));
+        store.append("1", TaskEvent::Submitted { command: String::from("false") }).unwrap();
+        store.append("1", TaskEvent::Started).unwrap();
+        for event in status.events() {
+            store.append("1", event).unwrap();
+        }
+
+        let events = store.events("1").unwrap();
+        assert_eq!(events[2].event, TaskEvent::Output { size: 6 });
+        // Only the storage keeps the content of the output
+        let expected = TaskStatus::Error(TaskOutput { content: vec![], truncated: 3, log: Some(PathBuf::from("/var/log/tasc/1.log")) }, Some(2));
+        assert_eq!(format!("{:?}", TaskStatus::project(events.iter().map(|recorded| &recorded.event))), format!("{:?}", expected));
+        assert_eq!(count(&store.connection, "SELECT COUNT(*) FROM task_events WHERE output IS NOT NULL"), 0);
+    }
+
+    #[test]
+    fn test_migration_should_replace_the_outputs_of_the_events_with_their_size() {
+        let fixture = Fixture::new();
+        let connection = SqliteConnection::establish(&fixture.database()).unwrap();
+        migrations::migrate(&connection, &EVENTS_MIGRATIONS[..2], &mut io::sink()).unwrap();
+        diesel::sql_query("INSERT INTO task_events (task_id, at, actor, kind, output) VALUES ('1', 0, 'tasc@host', 'OUTPUT', x'436f75636f75')").execute(&connection).unwrap();
+
+        migrations::migrate(&connection, EVENTS_MIGRATIONS, &mut io::sink()).unwrap();
+
+        assert_eq!(count(&connection, "SELECT COUNT(*) FROM task_events WHERE output IS NOT NULL"), 0);
+        assert_eq!(fixture.adapter().events("1").unwrap()[0].event, TaskEvent::Output { size: 6 });
+        assert!(diesel::sql_query("UPDATE task_events SET size = 0").execute(&connection).is_err());
+    }
+
+    // The events are kept by the purge, the tasks, their output logs and their artifacts are not
+    #[test]
+    fn test_purge_should_reclaim_the_outputs_of_the_tasks_sharing_the_database() {
+        let fixture = Fixture::new();
+        let logs = fixture.0.join("logs");
+        fs::create_dir_all(fixture.0.join("work")).unwrap();
+        fs::write(fixture.0.join("work/report.txt"), "report").unwrap();
+        let mut storage = SqliteStorageAdapter::new(&fixture.database(), 1, Duration::from_secs(5), true).unwrap();
+        let mut events = fixture.adapter();
+        let execution = LocalExecutionAdapter::new(None, None, OutputCapture::new(16, Some(logs.clone())));
+        let artifacts = FileArtifactStoreAdapter::new(fixture.0.join("store"), Some(logs.clone()));
+        let hooks = ShellHookAdapter::new(PathBuf::from("/bin/sh"), None, None, None);
+        let id_generator = UUIDGeneratorAdapter::new();
+        let mut service = TaskScheduler::new(&mut storage, &mut events, &execution, &artifacts, &hooks, &id_generator);
+        service.schedule_task(TaskInput {
+            name: Some("report".to_string()),
+            command: "seq 1 1000".to_string(),
+            env: None,
+            timeout: None,
+            dependencies: vec![],
+            labels: HashMap::new(),
+            working_dir: Some(fixture.0.join("work")),
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec!["report.txt".to_string()],
+            hooks: TaskHooks::default(),
+        }).unwrap();
+        let output = match service.task_status(TaskId::Name("report".to_string())).unwrap() {
+            TaskStatus::Success(output) => output,
+            status => panic!("Task is not successful: {:?}", status),
+        };
+        assert!(output.content.starts_with(b"1\n2\n") && output.log.is_some(), "{:?}", output);
+        let artifact = service.task_artifacts(TaskId::Name("report".to_string())).unwrap().remove(0);
+        let policy = RetentionPolicy { max_per_name: Some(0), ..RetentionPolicy::default() };
+        let id = service.purge_tasks(&policy, false).unwrap().remove(0).id;
+
+        assert_eq!(count(&events.connection, "SELECT COUNT(*) FROM tasks"), 0);
+        assert_eq!(count(&events.connection, "SELECT COUNT(*) FROM artifacts"), 0);
+        assert_eq!(count(&events.connection, "SELECT COUNT(*) FROM task_events WHERE output IS NOT NULL"), 0);
+        assert!(events.events(&id).unwrap().iter().any(|recorded| recorded.event == TaskEvent::Output { size: output.content.len() as u64 }));
+        assert_eq!(fs::read_dir(&logs).unwrap().count(), 0, "The output logs of the purged task must be removed");
+        assert!(artifacts.extract(&artifact, &fixture.0.join("extracted")).is_err(), "The content of the purged artifact must be removed");
+    }
+
+    #[test]
+    fn test_events_should_refuse_updates_and_deletions() {
+        let fixture = Fixture::new();
+        let store = {
+            let mut store = fixture.adapter();
+            store.append("1", TaskEvent::Submitted { command: String::from("true") }).unwrap();
+            store
+        };
+
+        let update = diesel::sql_query("UPDATE task_events SET command = 'false'").execute(&store.connection).unwrap_err();
+        assert!(update.to_string().contains("task events are immutable"), "{}", update);
+        let delete = diesel::sql_query("DELETE FROM task_events").execute(&store.connection).unwrap_err();
+        assert!(delete.to_string().contains("task events are immutable"), "{}", delete);
+        assert_eq!(fixture.adapter().events("1").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_events_database_should_only_hold_the_events() {
+        let fixture = Fixture::new();
+        let store = fixture.adapter();
+
+        assert!(diesel::sql_query("SELECT id FROM tasks").execute(&store.connection).is_err());
+        assert_eq!(migrations::status(&store.connection, EVENTS_MIGRATIONS).unwrap().iter().filter(|(_, applied)| !applied).count(), 0);
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Error, Context};
+use im::Vector;
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use domain::executor::model::model::{RecordedEvent, TaskEvent};
+use domain::executor::ports::secondary::EventStorePort;
+
+use super::record::StoredEvent;
+
+// Events of the durable in memory storage, appended as JSON lines to a file beside its snapshot. Unlike the journal of
+// the tasks it is never compacted, the events are only appended
+pub struct JournalEventStoreAdapter {
+    path: PathBuf,
+    // Locked by the process as long as it is open
+    file: File,
+    events: Vector<RecordedEvent>,
+    actor: String,
+}
+
+impl EventStorePort for JournalEventStoreAdapter {
+    fn append(&mut self, task_id: &str, event: TaskEvent) -> Result<(), Error> {
+        let stored = StoredEvent::new(self.events.len() as u64 + 1, task_id, &self.actor, &event)?;
+        let mut line = serde_json::to_vec(&stored)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+            .and_then(|_| self.file.sync_data())
+            .context(format!("Error writing in event journal {} event {} of task {}", self.path.display(), event, task_id))?;
+        self.events.push_back(RecordedEvent::try_from(stored)?);
+        Ok(())
+    }
+
+    fn events(&mut self, task_id: &str) -> Result<Vec<RecordedEvent>, Error> {
+        Ok(self.events.iter()
+            .filter(|recorded| recorded.task_id == task_id)
+            .cloned()
+            .collect())
+    }
+}
+
+impl JournalEventStoreAdapter {
+    // The events are read when the journal is opened, a last line left incomplete by a stopped tasc is dropped
+    pub fn new(path: &Path, actor: String) -> Result<JournalEventStoreAdapter, Error> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)
+            .context(format!("Error opening event journal {}", path.display()))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(anyhow!("Event journal {} is locked by another tasc process", path.display()));
+        }
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)
+            .context(format!("Error reading event journal {}", path.display()))?;
+        let complete = content.iter().rposition(|byte| *byte == b'\n').map_or(0, |last| last + 1);
+        if complete < content.len() {
+            file.set_len(complete as u64)
+                .context(format!("Error dropping the incomplete event of journal {}", path.display()))?;
+        }
+        let events = content[..complete].split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(number, line)| serde_json::from_slice::<StoredEvent>(line)
+                .map_err(Error::from)
+                .and_then(RecordedEvent::try_from)
+                .context(format!("Error reading line {} of event journal {}", number + 1, path.display())))
+            .collect::<Result<Vector<RecordedEvent>, Error>>()?;
+        Ok(JournalEventStoreAdapter { path: path.to_path_buf(), file, events, actor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_journal_events_should_be_read_again_once_reopened() {
+        let path = env::temp_dir().join(format!("tasc-events-{}.json.events", Uuid::new_v4()));
+        {
+            let mut store = JournalEventStoreAdapter::new(&path, String::from("tasc@host")).unwrap();
+            store.append("first", TaskEvent::Submitted { command: String::from("echo first") }).unwrap();
+            store.append("second", TaskEvent::Submitted { command: String::from("echo second") }).unwrap();
+            store.append("first", TaskEvent::Started).unwrap();
+            assert!(JournalEventStoreAdapter::new(&path, String::from("tasc@host")).is_err(), "The journal must be locked by the process");
+        }
+        // tasc stopped while writing an event
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"sequence\":4,\"task_id\":\"fir").unwrap();
+
+        let mut store = JournalEventStoreAdapter::new(&path, String::from("tasc@host")).unwrap();
+        store.append("first", TaskEvent::Cancelled).unwrap();
+        let events = JournalEventStoreAdapter::new(&path, String::from("tasc@host")).map(|_| ());
+        drop(store);
+        let reopened = JournalEventStoreAdapter::new(&path, String::from("tasc@host")).unwrap().events("first").unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(events.is_err());
+        assert_eq!(reopened.iter().map(|recorded| recorded.event.clone()).collect::<Vec<TaskEvent>>(), vec![
+            TaskEvent::Submitted { command: String::from("echo first") },
+            TaskEvent::Started,
+            TaskEvent::Cancelled,
+        ]);
+        assert_eq!(reopened.iter().map(|recorded| recorded.sequence).collect::<Vec<u64>>(), vec![1, 3, 4]);
+        assert!(reopened.iter().all(|recorded| recorded.task_id == "first" && recorded.actor == "tasc@host"));
+    }
+}
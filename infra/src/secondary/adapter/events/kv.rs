@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Error, Context};
+use redb::{Database, MultimapTableDefinition, ReadableTable, TableDefinition};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::Arc;
+
+use domain::executor::model::model::{RecordedEvent, TaskEvent};
+use domain::executor::ports::secondary::EventStorePort;
+
+use super::record::StoredEvent;
+use crate::secondary::adapter::storage::kv::open_database;
+
+// Events are JSON values keyed by their sequence, beside the tables of the kv storage when it uses the same file. No
+// operation of the adapter updates or removes them
+const TASK_EVENTS: TableDefinition<u64, &str> = TableDefinition::new("task_events");
+// Sequences of the events of each task
+const TASK_EVENTS_BY_TASK: MultimapTableDefinition<&str, u64> = MultimapTableDefinition::new("task_events_by_task");
+
+pub struct KvEventStoreAdapter {
+    database: Arc<Database>,
+    actor: String,
+}
+
+impl EventStorePort for KvEventStoreAdapter {
+    fn append(&mut self, task_id: &str, event: TaskEvent) -> Result<(), Error> {
+        let txn = self.database.begin_write()?;
+        {
+            let mut events = txn.open_table(TASK_EVENTS)?;
+            let sequence = events.last()?.map_or(1, |(last, _)| last.value() + 1);
+            let stored = StoredEvent::new(sequence, task_id, &self.actor, &event)?;
+            events.insert(sequence, serde_json::to_string(&stored)?.as_str())?;
+            txn.open_multimap_table(TASK_EVENTS_BY_TASK)?.insert(task_id, sequence)?;
+        }
+        txn.commit()
+            .context(format!("Error inserting in kv store event {} of task {}", event, task_id))
+    }
+
+    fn events(&mut self, task_id: &str) -> Result<Vec<RecordedEvent>, Error> {
+        let txn = self.database.begin_read()?;
+        let events = txn.open_table(TASK_EVENTS)?;
+        // Values of a multimap are sorted, so are the sequences
+        txn.open_multimap_table(TASK_EVENTS_BY_TASK)?.get(task_id)?
+            .map(|sequence| {
+                let sequence = sequence?.value();
+                let stored = events.get(sequence)?
+                    .ok_or_else(|| anyhow!("No event {} of task {}", sequence, task_id))?;
+                RecordedEvent::try_from(serde_json::from_str::<StoredEvent>(stored.value())?)
+            })
+            .collect::<Result<Vec<RecordedEvent>, Error>>()
+            .context(format!("Error loading from kv store events of task {}", task_id))
+    }
+}
+
+impl KvEventStoreAdapter {
+    pub fn new(path: &Path, actor: String) -> Result<KvEventStoreAdapter, Error> {
+        let database = open_database(path)?;
+        // Tables are created on their first write, reading them before fails
+        let txn = database.begin_write()?;
+        txn.open_table(TASK_EVENTS)?;
+        txn.open_multimap_table(TASK_EVENTS_BY_TASK)?;
+        txn.commit().context("Error creating kv store event tables")?;
+        Ok(KvEventStoreAdapter { database, actor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::executor::model::model::{TaskId, TaskStatus};
+    use domain::executor::ports::secondary::TaskStoragePort;
+    use std::{env, fs};
+    use uuid::Uuid;
+    use crate::secondary::adapter::execution::tests::task;
+    use crate::secondary::adapter::storage::kv::KvStorageAdapter;
+
+    #[test]
+    fn test_kv_events_should_be_kept_beside_the_tasks_of_the_storage() {
+        let path = env::temp_dir().join(format!("tasc-kv-events-{}.redb", Uuid::new_v4()));
+        let mut storage = KvStorageAdapter::new(&path).unwrap();
+        let mut store = KvEventStoreAdapter::new(&path, String::from("tasc@host")).unwrap();
+        storage.save(task("true")).unwrap();
+        store.append("first", TaskEvent::Submitted { command: String::from("echo first") }).unwrap();
+        store.append("second", TaskEvent::Submitted { command: String::from("echo second") }).unwrap();
+        store.append("first", TaskEvent::Started).unwrap();
+        store.append("first", TaskEvent::Output { size: 6 }).unwrap();
+        store.append("first", TaskEvent::Completed { success: true, exit_code: Some(0), truncated: 0, log: None }).unwrap();
+        drop(store);
+        drop(storage);
+
+        let mut store = KvEventStoreAdapter::new(&path, String::from("tasc@host")).unwrap();
+        let events = store.events("first").unwrap();
+        let (second, unknown) = (store.events("second").unwrap(), store.events("unknown").unwrap());
+        let stored = KvStorageAdapter::new(&path).unwrap().status(TaskId::Id("test".to_string()));
+        drop(store);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(events.iter().map(|recorded| recorded.event.clone()).collect::<Vec<TaskEvent>>(), vec![
+            TaskEvent::Submitted { command: String::from("echo first") },
+            TaskEvent::Started,
+            TaskEvent::Output { size: 6 },
+            TaskEvent::Completed { success: true, exit_code: Some(0), truncated: 0, log: None },
+        ]);
+        assert_eq!(events.iter().map(|recorded| recorded.sequence).collect::<Vec<u64>>(), vec![1, 3, 4, 5]);
+        assert!(events.iter().all(|recorded| recorded.task_id == "first" && recorded.actor == "tasc@host"));
+        assert!(matches!(TaskStatus::project(events.iter().map(|recorded| &recorded.event)), TaskStatus::Success(_)));
+        assert_eq!(second.len(), 1);
+        assert!(unknown.is_empty());
+        assert!(matches!(stored.unwrap(), TaskStatus::Scheduled));
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::Error;
+use im::Vector;
+use std::time::SystemTime;
+
+use domain::executor::model::model::{RecordedEvent, TaskEvent};
+use domain::executor::ports::secondary::EventStorePort;
+
+// Events lost at exit, for the tasks run by the process
+pub struct InMemoryEventStoreAdapter {
+    events: Vector<RecordedEvent>,
+    actor: String,
+}
+
+impl EventStorePort for InMemoryEventStoreAdapter {
+    fn append(&mut self, task_id: &str, event: TaskEvent) -> Result<(), Error> {
+        self.events.push_back(RecordedEvent {
+            task_id: task_id.to_string(),
+            sequence: self.events.len() as u64 + 1,
+            at: SystemTime::now(),
+            actor: self.actor.clone(),
+            event,
+        });
+        Ok(())
+    }
+
+    fn events(&mut self, task_id: &str) -> Result<Vec<RecordedEvent>, Error> {
+        Ok(self.events.iter()
+            .filter(|recorded| recorded.task_id == task_id)
+            .cloned()
+            .collect())
+    }
+}
+
+impl InMemoryEventStoreAdapter {
+    pub fn new(actor: String) -> InMemoryEventStoreAdapter {
+        InMemoryEventStoreAdapter { events: Vector::new(), actor }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+use anyhow::Error;
+use std::ffi::CStr;
+
+use domain::executor::ports::secondary::EventStorePort;
+
+#[cfg(feature = "sqlite")]
+use crate::secondary::adapter::events::database::SqliteEventStoreAdapter;
+use crate::secondary::adapter::events::journal::JournalEventStoreAdapter;
+use crate::secondary::adapter::events::kv::KvEventStoreAdapter;
+use crate::secondary::adapter::events::memory::InMemoryEventStoreAdapter;
+#[cfg(feature = "postgres")]
+use crate::secondary::adapter::events::postgres::PostgresEventStoreAdapter;
+use crate::primary::settings::EventStoreConfiguration;
+
+// Rows shared by the diesel event stores
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+pub mod database;
+pub mod journal;
+pub mod kv;
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+mod record;
+
+pub fn new_event_store_adapter(configuration: EventStoreConfiguration) -> Result<Box<dyn EventStorePort>, Error> {
+    let actor = current_actor();
+    match configuration {
+        #[cfg(feature = "sqlite")]
+        EventStoreConfiguration::Database { database_url, busy_timeout, auto_migrate } => Ok(Box::new(SqliteEventStoreAdapter::new(&database_url, busy_timeout, auto_migrate, actor)?)),
+        #[cfg(feature = "postgres")]
+        EventStoreConfiguration::Postgres { database_url, auto_migrate } => Ok(Box::new(PostgresEventStoreAdapter::new(&database_url, auto_migrate, actor)?)),
+        EventStoreConfiguration::Kv { path } => Ok(Box::new(KvEventStoreAdapter::new(&path, actor)?)),
+        EventStoreConfiguration::Journal { path } => Ok(Box::new(JournalEventStoreAdapter::new(&path, actor)?)),
+        EventStoreConfiguration::InMemory => Ok(Box::new(InMemoryEventStoreAdapter::new(actor))),
+    }
+}
+
+// Events are recorded as user(uid)@host, the user being resolved from the real uid rather than the environment
+fn current_actor() -> String {
+    let uid = unsafe { libc::getuid() };
+    let user = unsafe {
+        let passwd = libc::getpwuid(uid);
+        if passwd.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr((*passwd).pw_name).to_string_lossy().into_owned())
+        }
+    };
+    let mut host = [0u8; 256];
+    let host = match unsafe { libc::gethostname(host.as_mut_ptr() as *mut libc::c_char, host.len()) } {
+        0 => CStr::from_bytes_until_nul(&host).map(|host| host.to_string_lossy().into_owned()).unwrap_or_default(),
+        _ => String::new(),
+    };
+    format!("{}({})@{}", user.unwrap_or_default(), uid, host)
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+use anyhow::{Error, Context};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::convert::TryFrom;
+
+use domain::executor::model::model::{RecordedEvent, TaskEvent};
+use domain::executor::ports::secondary::EventStorePort;
+
+use super::database::{task_events, DbTaskEvent, NewDbTaskEvent};
+use crate::secondary::adapter::storage::database::migrations::{self, POSTGRES_MIGRATIONS};
+
+// Event log kept in the database of the postgres storage, its migrations create the table of the events
+pub struct PostgresEventStoreAdapter {
+    connection: PgConnection,
+    actor: String,
+}
+
+impl EventStorePort for PostgresEventStoreAdapter {
+    fn append(&mut self, task_id: &str, event: TaskEvent) -> Result<(), Error> {
+        diesel::insert_into(task_events::table)
+            .values(&NewDbTaskEvent::new(task_id, &self.actor, &event)?)
+            .execute(&self.connection)
+            .map(|_| ())
+            .context(format!("Error inserting in db event {} of task {}", event, task_id))
+    }
+
+    fn events(&mut self, task_id: &str) -> Result<Vec<RecordedEvent>, Error> {
+        task_events::table.filter(task_events::task_id.eq(task_id))
+            .order(task_events::sequence)
+            .load::<DbTaskEvent>(&self.connection)
+            .context(format!("Error loading from database events of task {}", task_id))?
+            .into_iter()
+            .map(RecordedEvent::try_from)
+            .collect()
+    }
+}
+
+impl PostgresEventStoreAdapter {
+    pub fn new(database_url: &str, auto_migrate: bool, actor: String) -> Result<PostgresEventStoreAdapter, Error> {
+        let connection = PgConnection::establish(database_url)
+            .context("Error connecting to event database")?;
+        migrations::prepare(&connection, "events", POSTGRES_MIGRATIONS, auto_migrate)?;
+        Ok(PostgresEventStoreAdapter { connection, actor })
+    }
+}
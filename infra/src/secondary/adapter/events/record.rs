@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use domain::executor::model::model::{RecordedEvent, TaskEvent};
+
+pub(super) const SUBMITTED: &str = "SUBMITTED";
+pub(super) const STARTED: &str = "STARTED";
+pub(super) const OUTPUT: &str = "OUTPUT";
+pub(super) const COMPLETED: &str = "COMPLETED";
+pub(super) const CANCELLED: &str = "CANCELLED";
+pub(super) const ARTIFACTS_FAILED: &str = "ARTIFACTS_FAILED";
+
+// Event as the stores keep it, only the fields of its kind are set
+#[derive(Serialize, Deserialize, Default)]
+pub(super) struct EventRecord {
+    pub(super) kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) command: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) success: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) truncated: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) log: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) message: Option<String>,
+}
+
+impl From<&TaskEvent> for EventRecord {
+    fn from(event: &TaskEvent) -> Self {
+        match event {
+            TaskEvent::Submitted { command } => EventRecord { kind: SUBMITTED.to_string(), command: Some(command.clone()), ..EventRecord::default() },
+            TaskEvent::Started => EventRecord { kind: STARTED.to_string(), ..EventRecord::default() },
+            TaskEvent::Output { size } => EventRecord { kind: OUTPUT.to_string(), size: Some(*size), ..EventRecord::default() },
+            TaskEvent::Completed { success, exit_code, truncated, log } => EventRecord {
+                kind: COMPLETED.to_string(),
+                success: Some(*success),
+                exit_code: *exit_code,
+                truncated: Some(*truncated),
+                log: log.clone(),
+                ..EventRecord::default()
+            },
+            TaskEvent::Cancelled => EventRecord { kind: CANCELLED.to_string(), ..EventRecord::default() },
+            TaskEvent::ArtifactsFailed { message } => EventRecord { kind: ARTIFACTS_FAILED.to_string(), message: Some(message.clone()), ..EventRecord::default() },
+        }
+    }
+}
+
+impl EventRecord {
+    pub(super) fn into_event(self, task_id: &str) -> Result<TaskEvent, Error> {
+        Ok(match self.kind.as_str() {
+            SUBMITTED => TaskEvent::Submitted { command: self.command.unwrap_or_default() },
+            STARTED => TaskEvent::Started,
+            OUTPUT => TaskEvent::Output { size: self.size.unwrap_or_default() },
+            COMPLETED => TaskEvent::Completed {
+                success: self.success.unwrap_or_default(),
+                exit_code: self.exit_code,
+                truncated: self.truncated.unwrap_or_default(),
+                log: self.log,
+            },
+            CANCELLED => TaskEvent::Cancelled,
+            ARTIFACTS_FAILED => TaskEvent::ArtifactsFailed { message: self.message.unwrap_or_default() },
+            other => return Err(anyhow!("Unknown event {} of task {}", other, task_id)),
+        })
+    }
+}
+
+// Line of the event journal and value of the kv event table
+#[derive(Serialize, Deserialize)]
+pub(super) struct StoredEvent {
+    pub(super) sequence: u64,
+    pub(super) task_id: String,
+    // Milliseconds since the epoch
+    pub(super) at: u64,
+    pub(super) actor: String,
+    #[serde(flatten)]
+    pub(super) event: EventRecord,
+}
+
+impl StoredEvent {
+    pub(super) fn new(sequence: u64, task_id: &str, actor: &str, event: &TaskEvent) -> Result<StoredEvent, Error> {
+        Ok(StoredEvent { sequence, task_id: task_id.to_string(), at: now_millis()?, actor: actor.to_string(), event: EventRecord::from(event) })
+    }
+}
+
+impl TryFrom<StoredEvent> for RecordedEvent {
+    type Error = Error;
+
+    fn try_from(stored: StoredEvent) -> Result<Self, Self::Error> {
+        Ok(RecordedEvent {
+            event: stored.event.into_event(&stored.task_id)?,
+            task_id: stored.task_id,
+            sequence: stored.sequence,
+            at: UNIX_EPOCH + Duration::from_millis(stored.at),
+            actor: stored.actor,
+        })
+    }
+}
+
+pub(super) fn now_millis() -> Result<u64, Error> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
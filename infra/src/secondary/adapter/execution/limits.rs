@@ -0,0 +1,158 @@
+use domain::executor::model::model::ResourceLimits;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type RlimitResource = libc::c_int;
+
+// Limits left to setrlimit, memory.max bounds the resident memory in a cgroup while RLIMIT_AS
+// would bound the virtual size, which JVMs or Go binaries exceed long before using the memory
+pub fn rlimits(limits: &ResourceLimits, in_cgroup: bool) -> ResourceLimits {
+    ResourceLimits {
+        memory: limits.memory.filter(|_| !in_cgroup),
+        ..limits.clone()
+    }
+}
+
+// Called between fork and exec, must not allocate
+// RLIMIT_NPROC counts every process of the user, not only the ones of the task
+pub fn apply_rlimits(limits: &ResourceLimits) -> io::Result<()> {
+    set_rlimit(libc::RLIMIT_CPU, limits.cpu_time.map(|cpu_time| cpu_time.as_secs()))?;
+    set_rlimit(libc::RLIMIT_AS, limits.memory)?;
+    set_rlimit(libc::RLIMIT_NOFILE, limits.open_files)?;
+    set_rlimit(libc::RLIMIT_NPROC, limits.processes)
+}
+
+fn set_rlimit(resource: RlimitResource, limit: Option<u64>) -> io::Result<()> {
+    match limit {
+        None => Ok(()),
+        Some(limit) => {
+            let rlimit = libc::rlimit { rlim_cur: limit as libc::rlim_t, rlim_max: limit as libc::rlim_t };
+            match unsafe { libc::setrlimit(resource, &rlimit) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+    }
+}
+
+// cgroup v2 hierarchy delegated to tasc, checked once by the first task with limits
+pub struct CgroupHierarchy {
+    root: PathBuf,
+    available: OnceLock<bool>,
+}
+
+impl CgroupHierarchy {
+    pub fn new(root: PathBuf) -> CgroupHierarchy {
+        CgroupHierarchy { root, available: OnceLock::new() }
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        let available = *self.available.get_or_init(|| {
+            let available = controllers_enabled(&self.root);
+            if !available {
+                eprintln!("memory and pids controllers are not enabled for cgroup v2 hierarchy {}, only rlimits are applied", self.root.display());
+            }
+            available
+        });
+        Some(self.root.as_path()).filter(|_| available)
+    }
+}
+
+// The controllers are enabled once when the hierarchy is set up, tasc never changes them
+fn controllers_enabled(root: &Path) -> bool {
+    let controllers = fs::read_to_string(root.join("cgroup.subtree_control")).unwrap_or_default();
+    ["memory", "pids"].iter()
+        .all(|required| controllers.split_whitespace().any(|controller| controller == *required))
+}
+
+// Called between fork and exec, writing 0 moves the calling process in the cgroup
+pub fn join_cgroup(procs: RawFd) -> io::Result<()> {
+    let pid = b"0";
+    match unsafe { libc::write(procs, pid.as_ptr() as *const libc::c_void, pid.len()) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+// cgroup of a task, killed and removed when dropped
+pub struct Cgroup {
+    path: PathBuf,
+    procs: File,
+}
+
+impl Cgroup {
+    pub fn create(root: &Path, name: &str, limits: &ResourceLimits) -> io::Result<Cgroup> {
+        let path = root.join(name);
+        fs::create_dir(&path)?;
+        let cgroup = Cgroup {
+            procs: OpenOptions::new().write(true).open(path.join("cgroup.procs"))?,
+            path,
+        };
+        if let Some(memory) = limits.memory {
+            fs::write(cgroup.path.join("memory.max"), memory.to_string())?;
+            fs::write(cgroup.path.join("memory.swap.max"), "0").or_else(ignore_missing)?;
+        }
+        if let Some(processes) = limits.processes {
+            fs::write(cgroup.path.join("pids.max"), processes.to_string())?;
+        }
+        Ok(cgroup)
+    }
+
+    pub fn procs_fd(&self) -> RawFd {
+        self.procs.as_raw_fd()
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        let _ = fs::write(self.path.join("cgroup.kill"), "1");
+        let _ = fs::remove_dir(&self.path);
+    }
+}
+
+// Swap accounting can be disabled on the host
+fn ignore_missing(err: io::Error) -> io::Result<()> {
+    match err.kind() {
+        io::ErrorKind::NotFound => Ok(()),
+        _ => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::time::Duration;
+
+    fn limits() -> ResourceLimits {
+        ResourceLimits {
+            cpu_time: Some(Duration::from_secs(10)),
+            memory: Some(1 << 30),
+            open_files: Some(64),
+            processes: Some(32),
+        }
+    }
+
+    #[test]
+    fn test_rlimits_should_leave_memory_to_the_cgroup() {
+        assert_eq!(rlimits(&limits(), false), limits());
+        assert_eq!(rlimits(&limits(), true), ResourceLimits { memory: None, ..limits() });
+    }
+
+    #[test]
+    fn test_cgroup_hierarchy_should_require_enabled_controllers() {
+        let root = env::temp_dir().join(format!("tasc-cgroup-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("cgroup.subtree_control"), "cpu memory\n").unwrap();
+        assert_eq!(CgroupHierarchy::new(root.clone()).root(), None);
+        fs::write(root.join("cgroup.subtree_control"), "cpu memory pids\n").unwrap();
+        assert_eq!(CgroupHierarchy::new(root.clone()).root(), Some(root.as_path()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,324 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Credentials, ResourceLimits, Task, TaskOutput, TaskStatus};
+use domain::executor::model::error::TaskError;
+use std::process::{Command, ExitStatus, Stdio};
+use anyhow::{anyhow, Error, Context};
+use im::Vector;
+use std::io::{self, Write};
+use std::iter::FromIterator;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::primary::settings::{ExecutionConfiguration, ExecutorConfiguration};
+
+use self::limits::{Cgroup, CgroupHierarchy};
+
+pub use self::output::OutputCapture;
+
+pub use self::oci::OciExecutionAdapter;
+pub use self::registry::{ExecutorKind, ExecutorRegistry};
+pub use self::sandbox::SandboxExecutionAdapter;
+pub use self::shell::ShellExecutionAdapter;
+pub use self::ssh::SshExecutionAdapter;
+pub use self::wasm::WasmExecutionAdapter;
+
+mod limits;
+mod oci;
+mod output;
+mod registry;
+mod sandbox;
+mod shell;
+mod ssh;
+mod wasm;
+
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn new_execution_adapter(configuration: ExecutionConfiguration) -> Result<ExecutorRegistry, Error> {
+    let capture = OutputCapture::new(configuration.output_limit, configuration.output_logs);
+    let default_credentials = configuration.credentials;
+    let local = LocalExecutionAdapter::new(configuration.cgroup, default_credentials.clone(), capture.clone());
+    let mut registry = ExecutorRegistry::new(configuration.default, configuration.weaker_executors);
+    for (name, executor) in configuration.executors {
+        let (kind, executor): (ExecutorKind, Box<dyn TaskExecutionPort>) = match executor {
+            ExecutorConfiguration::Local { credentials } => (ExecutorKind::Local, Box::new(local.with_credentials(credentials))),
+            ExecutorConfiguration::Shell { shell, credentials } => (ExecutorKind::Shell, Box::new(ShellExecutionAdapter::new(local.with_credentials(credentials), shell))),
+            ExecutorConfiguration::Sandbox { credentials } => (ExecutorKind::Sandbox, Box::new(SandboxExecutionAdapter::new(local.with_credentials(credentials)))),
+            ExecutorConfiguration::Oci { runtime, bundles, credentials } =>
+                (ExecutorKind::Oci, Box::new(OciExecutionAdapter::new(runtime, bundles, credentials.or_else(|| default_credentials.clone()), capture.clone()))),
+            ExecutorConfiguration::Ssh { host, user, identity, known_hosts } => (ExecutorKind::Ssh, Box::new(SshExecutionAdapter::new(host, user, identity, known_hosts, capture.clone()))),
+            ExecutorConfiguration::Wasm => (ExecutorKind::Wasm, Box::new(WasmExecutionAdapter::new(capture.clone())?)),
+        };
+        registry.register(name, kind, executor);
+    }
+    Ok(registry)
+}
+
+// Executor of the hooks of the tasks, which run with the settings of a task run by the default executor
+// A default executor isolating the tasks has none, the tasks can't then escape it through their hooks
+pub fn new_task_hook_executor(configuration: &ExecutionConfiguration, shell: PathBuf) -> Option<Box<dyn TaskExecutionPort>> {
+    let local = LocalExecutionAdapter::new(configuration.cgroup.clone(), configuration.credentials.clone(), OutputCapture::new(configuration.output_limit, None));
+    configuration.executors.iter()
+        .find(|(name, _)| *name == configuration.default)
+        .and_then(|(_, executor)| match executor {
+            ExecutorConfiguration::Local { credentials } | ExecutorConfiguration::Shell { credentials, .. } =>
+                Some(Box::new(ShellExecutionAdapter::new(local.with_credentials(credentials.clone()), shell)) as Box<dyn TaskExecutionPort>),
+            _ => None,
+        })
+}
+
+#[derive(Clone)]
+pub struct LocalExecutionAdapter {
+    // cgroup v2 hierarchy where a sub-tree is created for each task with limits
+    cgroup: Option<Arc<CgroupHierarchy>>,
+    // Credentials of the tasks which don't define theirs
+    default_credentials: Option<Credentials>,
+    capture: OutputCapture,
+}
+
+impl TaskExecutionPort for LocalExecutionAdapter {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        let (command, _cgroup) = self.prepare(task, split_command(task)?)?;
+        complete(command, task, &self.capture)
+    }
+}
+
+impl LocalExecutionAdapter {
+    pub fn new(cgroup_root: Option<PathBuf>, default_credentials: Option<Credentials>, capture: OutputCapture) -> LocalExecutionAdapter {
+        let cgroup = cgroup_root.map(|cgroup_root| Arc::new(CgroupHierarchy::new(cgroup_root)));
+        LocalExecutionAdapter { cgroup, default_credentials, capture }
+    }
+
+    // Copy of the adapter running the tasks which don't define their credentials with the given ones
+    pub fn with_credentials(&self, default_credentials: Option<Credentials>) -> LocalExecutionAdapter {
+        LocalExecutionAdapter {
+            default_credentials: default_credentials.or_else(|| self.default_credentials.clone()),
+            ..self.clone()
+        }
+    }
+
+    // The default credentials only apply when tasc can switch user, the ones of the task are always required
+    fn credentials<'a>(&'a self, task: &'a Task) -> Option<&'a Credentials> {
+        task.credentials.as_ref()
+            .or_else(|| self.default_credentials.as_ref().filter(|_| unsafe { libc::geteuid() } == 0))
+    }
+
+    // Apply the process settings of the task to its command, the cgroup must be kept until the command is completed
+    fn prepare(&self, task: &Task, mut command: Command) -> Result<(Command, Option<Cgroup>), Error> {
+        let credentials = self.credentials(task).cloned();
+        if credentials.is_some() && unsafe { libc::geteuid() } != 0 {
+            return Err(TaskError::PermissionError(format!("Task {} can't change its user, the executor is not running as root", task.id)).into());
+        }
+        command.envs(task.env.iter().flat_map(|env| env.iter().map(|(key, value)| (key.clone(), value.clone()))));
+        if let Some(ref working_dir) = task.working_dir {
+            command.current_dir(working_dir);
+        }
+        let cgroup_root = self.cgroup.as_ref()
+            .filter(|_| task.limits.memory.is_some() || task.limits.processes.is_some())
+            .and_then(|cgroup| cgroup.root());
+        let cgroup = match cgroup_root {
+            Some(cgroup_root) =>
+                Some(Cgroup::create(cgroup_root, &format!("tasc-{}", task.id), &task.limits)
+                    .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })
+                    .context(format!("Error creating cgroup for task {}", task.id))?),
+            None => None,
+        };
+        if task.umask.is_some() || task.limits != ResourceLimits::default() || credentials.is_some() {
+            let umask = task.umask;
+            let limits = limits::rlimits(&task.limits, cgroup.is_some());
+            let cgroup_procs = cgroup.as_ref().map(Cgroup::procs_fd);
+            // Only async-signal-safe calls are allowed between fork and exec
+            unsafe {
+                command.pre_exec(move || {
+                    if let Some(umask) = umask {
+                        libc::umask(umask as libc::mode_t);
+                    }
+                    if let Some(cgroup_procs) = cgroup_procs {
+                        limits::join_cgroup(cgroup_procs)?;
+                    }
+                    limits::apply_rlimits(&limits)?;
+                    // Last step since the other ones may require the privileges of the executor
+                    match credentials {
+                        Some(ref credentials) => switch_user(credentials),
+                        None => Ok(()),
+                    }
+                });
+            }
+        }
+        Ok((command, cgroup))
+    }
+}
+
+// Program and arguments of the task, without shell interpretation
+fn split_command(task: &Task) -> Result<Command, Error> {
+    let command_splitted = Vector::from_iter(task.command.split_whitespace());
+    let main_command: &str = command_splitted.head()
+        .ok_or_else(|| TaskError::CommandError("Command can't be empty".to_string()))
+        .context("Error during command validation")?;
+    let mut command = Command::new(main_command);
+    command.args(command_splitted.split_at(1).1);
+    Ok(command)
+}
+
+// Called between fork and exec
+fn switch_user(credentials: &Credentials) -> io::Result<()> {
+    let check = |result: libc::c_int| match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    };
+    check(unsafe { libc::setgroups(credentials.groups.len() as libc::size_t, credentials.groups.as_ptr() as *const libc::gid_t) })?;
+    check(unsafe { libc::setgid(credentials.gid as libc::gid_t) })?;
+    check(unsafe { libc::setuid(credentials.uid as libc::uid_t) })
+}
+
+fn complete(command: Command, task: &Task, capture: &OutputCapture) -> Result<TaskStatus, Error> {
+    run_command(command, task, capture)
+        .map(|output| match output {
+            Some(output) => validate_output(output),
+            None => TaskStatus::Error(format!("Task timed out after {} seconds", task.timeout.unwrap_or_default().as_secs()).into(), None),
+        })
+        .context("Error during command execution")
+}
+
+struct CommandOutput {
+    status: ExitStatus,
+    stdout: TaskOutput,
+    stderr: TaskOutput,
+}
+
+// Run the command and kill it if it is still running after timeout, None is returned in this case
+fn run_command(mut command: Command, task: &Task, capture: &OutputCapture) -> Result<Option<CommandOutput>, TaskError> {
+    let (stdin, timeout) = (task.stdin.clone(), task.timeout);
+    let mut child = command.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })?;
+    // Pipes are written and read in background to avoid the child blocking on a full pipe
+    if let (Some(payload), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        thread::spawn(move || pipe.write_all(&payload));
+    }
+    let stdout = read_pipe(child.stdout.take(), capture, format!("{}.stdout", task.id));
+    let stderr = read_pipe(child.stderr.take(), capture, format!("{}.stderr", task.id));
+    let start = Instant::now();
+    let status = loop {
+        let status = match timeout {
+            Some(_) => child.try_wait(),
+            None => child.wait().map(Some),
+        }.map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })?;
+        match status {
+            Some(status) => break status,
+            None if timeout.is_some_and(|timeout| start.elapsed() >= timeout) => {
+                child.kill()
+                    .and_then(|_| child.wait())
+                    .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })?;
+                return Ok(None);
+            }
+            None => thread::sleep(TIMEOUT_POLL_INTERVAL),
+        }
+    };
+    Ok(Some(CommandOutput {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+fn read_pipe<R: io::Read + Send + 'static>(pipe: Option<R>, capture: &OutputCapture, log_name: String) -> JoinHandle<TaskOutput> {
+    let capture = capture.clone();
+    thread::spawn(move || match pipe {
+        Some(pipe) => capture.read(pipe, &log_name),
+        None => TaskOutput::default(),
+    })
+}
+
+// Output is kept as bytes, it is only decoded lossily when displayed
+fn validate_output(output: CommandOutput) -> TaskStatus {
+    match output.status.success() {
+        true => TaskStatus::Success(output.stdout),
+        false => TaskStatus::Error(output.stderr, output.status.code()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use domain::executor::model::model::TaskHooks;
+    use im::HashMap;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    pub(crate) fn task(command: &str) -> Task {
+        Task {
+            id: "test".to_string(),
+            name: None,
+            command: command.to_string(),
+            env: None,
+            timeout: None,
+            labels: HashMap::new(),
+            working_dir: None,
+            stdin: None,
+            umask: None,
+            limits: ResourceLimits::default(),
+            sandboxed: false,
+            credentials: None,
+            container: None,
+            host: None,
+            wasm: None,
+            executor: None,
+            artifacts: vec![],
+            hooks: TaskHooks::default(),
+        }
+    }
+
+    // Programs some tests need and skip without
+    pub(crate) fn program(name: &str) -> Option<PathBuf> {
+        std::env::var_os("PATH")
+            .and_then(|paths| std::env::split_paths(&paths).map(|path| path.join(name)).find(|path| path.is_file()))
+    }
+
+    pub(crate) fn success_output(status: TaskStatus) -> String {
+        match status {
+            TaskStatus::Success(output) => output.to_string(),
+            status => panic!("Task is not successful: {:?}", status),
+        }
+    }
+
+    #[test]
+    fn test_prepare_should_apply_rlimits() {
+        let capture = OutputCapture::new(1024, None);
+        let adapter = LocalExecutionAdapter::new(None, None, capture.clone());
+        let mut task = task("sh");
+        task.limits = ResourceLimits {
+            cpu_time: Some(Duration::from_secs(30)),
+            memory: Some(1 << 30),
+            open_files: Some(64),
+            processes: None,
+        };
+        let mut command = Command::new("sh");
+        command.args(["-c", "ulimit -t; ulimit -v; ulimit -n"]);
+        let (command, cgroup) = adapter.prepare(&task, command).unwrap();
+        assert!(cgroup.is_none());
+        assert_eq!(success_output(complete(command, &task, &capture).unwrap()), "30\n1048576\n64\n");
+    }
+
+    #[test]
+    fn test_execute_should_apply_working_dir_stdin_and_umask() {
+        let working_dir = std::env::temp_dir().join(format!("tasc-process-{}", std::process::id()));
+        fs::create_dir_all(&working_dir).unwrap();
+        // Run from the working directory, the script is found by its relative path
+        fs::write(working_dir.join("check.sh"), "pwd; cat > stdin.out; touch created").unwrap();
+        let mut task = task("sh check.sh");
+        task.working_dir = Some(working_dir.clone());
+        task.stdin = Some(b"payload\n\xff".to_vec());
+        task.umask = Some(0o077);
+        let status = LocalExecutionAdapter::new(None, None, OutputCapture::new(1024, None)).execute(&task);
+        let result = (fs::read(working_dir.join("stdin.out")), fs::metadata(working_dir.join("created")));
+        fs::remove_dir_all(&working_dir).unwrap();
+        assert_eq!(success_output(status.unwrap()), format!("{}\n", working_dir.canonicalize().unwrap_or(working_dir).display()));
+        assert_eq!(result.0.unwrap(), b"payload\n\xff");
+        assert_eq!(result.1.unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,221 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Container, Credentials, Task, TaskStatus};
+use domain::executor::model::error::TaskError;
+use anyhow::{anyhow, Error, Context};
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{complete, limits, OutputCapture};
+
+const DEFAULT_PATH: &str = "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+// Run the tasks defining a container in an OCI bundle through a local runtime (runc, crun)
+pub struct OciExecutionAdapter {
+    runtime: PathBuf,
+    // Directory where the bundles of the running tasks are created
+    bundles: PathBuf,
+    // Credentials of the tasks which don't define theirs
+    default_credentials: Option<Credentials>,
+    capture: OutputCapture,
+}
+
+impl TaskExecutionPort for OciExecutionAdapter {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        let container = match task.container {
+            Some(ref container) => container,
+            None => return Err(TaskError::ExecutionError { source: anyhow!("No container image is defined") })
+                .context(format!("Error running task {} in a container", task.id)),
+        };
+        // The working directory of a task with a container is a path of its image
+        if task.working_dir.as_ref().is_some_and(|working_dir| !working_dir.is_absolute()) {
+            return Err(TaskError::ValidationError(format!("Task {} working directory must be an absolute path of its image", task.id)).into());
+        }
+        let rootless = unsafe { libc::geteuid() } != 0;
+        if rootless && task.credentials.is_some() {
+            return Err(TaskError::PermissionError(format!("Task {} can't change its user, the executor is not running as root", task.id)).into());
+        }
+        // As for the local executor, the default credentials only apply when tasc can switch user
+        let credentials = task.credentials.as_ref().or_else(|| self.default_credentials.as_ref().filter(|_| !rootless));
+        let container_id = format!("tasc-{}", task.id);
+        let bundle = Bundle::create(&self.bundles.join(&container_id), &spec(task, container, credentials, rootless))
+            .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })
+            .context(format!("Error creating OCI bundle for task {}", task.id))?;
+        let mut command = Command::new(&self.runtime);
+        command.arg("run").arg("--bundle").arg(&bundle.0).arg(&container_id);
+        let status = complete(command, task, &self.capture);
+        // The runtime only removes the container when it exits by itself, not when it is killed on timeout
+        let _ = Command::new(&self.runtime)
+            .args(["delete", "--force", &container_id])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        status
+    }
+}
+
+impl OciExecutionAdapter {
+    pub fn new(runtime: PathBuf, bundles: PathBuf, default_credentials: Option<Credentials>, capture: OutputCapture) -> OciExecutionAdapter {
+        OciExecutionAdapter { runtime, bundles, default_credentials, capture }
+    }
+}
+
+// Bundle directory, removed when dropped
+struct Bundle(PathBuf);
+
+impl Bundle {
+    fn create(path: &Path, spec: &Value) -> io::Result<Bundle> {
+        fs::create_dir_all(path)?;
+        let bundle = Bundle(path.to_path_buf());
+        fs::write(path.join("config.json"), spec.to_string())?;
+        Ok(bundle)
+    }
+}
+
+impl Drop for Bundle {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// OCI runtime specification of the task, the image is used read-only with a writable /tmp
+fn spec(task: &Task, container: &Container, credentials: Option<&Credentials>, rootless: bool) -> Value {
+    let mut env: Vec<String> = vec![DEFAULT_PATH.to_string()];
+    env.extend(task.env.iter().flat_map(|env| env.iter().map(|(key, value)| format!("{}={}", key, value))));
+    let (uid, gid, groups) = credentials
+        .map(|credentials| (credentials.uid, credentials.gid, credentials.groups.clone()))
+        .unwrap_or((0, 0, vec![]));
+    let limits = &task.limits;
+    // The memory is only limited by the cgroup of the container
+    let rlimits = limits::rlimits(limits, true);
+    let rlimits: Vec<Value> = vec![
+        ("RLIMIT_CPU", rlimits.cpu_time.map(|cpu_time| cpu_time.as_secs())),
+        ("RLIMIT_AS", rlimits.memory),
+        ("RLIMIT_NOFILE", rlimits.open_files),
+        ("RLIMIT_NPROC", rlimits.processes),
+    ].into_iter()
+        .filter_map(|(kind, limit)| limit.map(|limit| json!({ "type": kind, "hard": limit, "soft": limit })))
+        .collect();
+    let mut mounts = vec![
+        json!({ "destination": "/proc", "type": "proc", "source": "proc" }),
+        json!({ "destination": "/dev", "type": "tmpfs", "source": "tmpfs", "options": ["nosuid", "strictatime", "mode=755", "size=65536k"] }),
+        json!({ "destination": "/dev/pts", "type": "devpts", "source": "devpts", "options": ["nosuid", "noexec", "newinstance", "ptmxmode=0666", "mode=0620"] }),
+        json!({ "destination": "/dev/shm", "type": "tmpfs", "source": "shm", "options": ["nosuid", "noexec", "nodev", "mode=1777", "size=65536k"] }),
+        json!({ "destination": "/sys", "type": "none", "source": "/sys", "options": ["rbind", "nosuid", "noexec", "nodev", "ro"] }),
+        json!({ "destination": "/tmp", "type": "tmpfs", "source": "tmpfs", "options": ["nosuid", "nodev", "mode=1777"] }),
+    ];
+    mounts.extend(container.mounts.iter().map(|mount| json!({
+        "destination": mount.destination,
+        "type": "none",
+        "source": mount.source,
+        "options": ["rbind", if mount.read_only { "ro" } else { "rw" }],
+    })));
+    let mut namespaces = vec![json!({ "type": "pid" }), json!({ "type": "ipc" }), json!({ "type": "uts" }), json!({ "type": "mount" })];
+    // Without root the runtime needs a user namespace where the executor is root
+    if rootless {
+        namespaces.push(json!({ "type": "user" }));
+    }
+    let mut linux = json!({
+        "namespaces": namespaces,
+        "maskedPaths": ["/proc/kcore", "/proc/keys", "/proc/timer_list", "/proc/sched_debug", "/sys/firmware"],
+        "readonlyPaths": ["/proc/bus", "/proc/fs", "/proc/irq", "/proc/sys", "/proc/sysrq-trigger"],
+    });
+    if limits.memory.is_some() || limits.processes.is_some() {
+        linux["resources"] = json!({
+            "memory": limits.memory.map(|memory| json!({ "limit": memory })),
+            "pids": limits.processes.map(|processes| json!({ "limit": processes })),
+        });
+    }
+    if rootless {
+        linux["uidMappings"] = json!([{ "containerID": 0, "hostID": unsafe { libc::geteuid() }, "size": 1 }]);
+        linux["gidMappings"] = json!([{ "containerID": 0, "hostID": unsafe { libc::getegid() }, "size": 1 }]);
+    }
+    json!({
+        "ociVersion": "1.0.2",
+        "process": {
+            "terminal": false,
+            "user": { "uid": uid, "gid": gid, "additionalGids": groups, "umask": task.umask },
+            "args": task.command.split_whitespace().collect::<Vec<&str>>(),
+            "env": env,
+            "cwd": task.working_dir.as_ref().map(|working_dir| working_dir.to_string_lossy().into_owned()).unwrap_or_else(|| "/".to_string()),
+            "rlimits": rlimits,
+            "noNewPrivileges": true,
+        },
+        "root": { "path": container.rootfs, "readonly": true },
+        "hostname": format!("tasc-{}", task.id.chars().take(8).collect::<String>()),
+        "mounts": mounts,
+        "linux": linux,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::{program, success_output, task};
+    use domain::executor::model::model::ResourceLimits;
+    use std::env;
+    use std::os::unix::fs::symlink;
+
+    fn container(rootfs: &Path) -> Container {
+        Container { rootfs: rootfs.to_path_buf(), mounts: vec![] }
+    }
+
+    // Rootfs made of a static busybox, the binary of the busybox-static packages
+    fn busybox_rootfs(busybox: &Path) -> PathBuf {
+        let rootfs = env::temp_dir().join(format!("tasc-busybox-{}", std::process::id()));
+        fs::create_dir_all(rootfs.join("bin")).unwrap();
+        for directory in ["proc", "dev", "sys", "tmp", "work"].iter() {
+            fs::create_dir_all(rootfs.join(directory)).unwrap();
+        }
+        fs::copy(busybox, rootfs.join("bin/busybox")).unwrap();
+        for applet in ["sh", "pwd", "id", "echo"].iter() {
+            symlink("busybox", rootfs.join("bin").join(applet)).unwrap();
+        }
+        rootfs
+    }
+
+    #[test]
+    fn test_spec_should_use_the_image_working_dir_and_credentials() {
+        let mut task = task("pwd");
+        let rootfs = PathBuf::from("/images/busybox");
+        let default = spec(&task, &container(&rootfs), None, false);
+        assert_eq!(default["process"]["cwd"], "/");
+        assert_eq!(default["process"]["user"]["uid"], 0);
+        task.working_dir = Some(PathBuf::from("/work"));
+        let credentials = Credentials { uid: 1000, gid: 100, groups: vec![10] };
+        let spec = spec(&task, &container(&rootfs), Some(&credentials), false);
+        assert_eq!(spec["process"]["cwd"], "/work");
+        assert_eq!(spec["process"]["user"], json!({ "uid": 1000, "gid": 100, "additionalGids": [10], "umask": null }));
+        assert_eq!(spec["root"]["path"], "/images/busybox");
+    }
+
+    #[test]
+    fn test_spec_should_leave_memory_to_the_cgroup() {
+        let mut task = task("pwd");
+        task.limits = ResourceLimits { memory: Some(1 << 30), open_files: Some(64), ..ResourceLimits::default() };
+        let spec = spec(&task, &container(Path::new("/images/busybox")), None, false);
+        assert_eq!(spec["linux"]["resources"]["memory"], json!({ "limit": 1u64 << 30 }));
+        assert_eq!(spec["process"]["rlimits"], json!([{ "type": "RLIMIT_NOFILE", "hard": 64, "soft": 64 }]));
+    }
+
+    #[test]
+    fn test_oci_executor_should_run_the_command_in_the_image() {
+        let (runtime, busybox) = match (program("runc"), program("busybox")) {
+            (Some(runtime), Some(busybox)) => (runtime, busybox),
+            _ => {
+                eprintln!("OCI test skipped, runc and busybox are not installed");
+                return;
+            }
+        };
+        let rootfs = busybox_rootfs(&busybox);
+        let adapter = OciExecutionAdapter::new(runtime, env::temp_dir(), None, OutputCapture::new(1024, None));
+        let mut task = task("pwd");
+        task.working_dir = Some(PathBuf::from("/work"));
+        task.container = Some(container(&rootfs));
+        let status = adapter.execute(&task);
+        fs::remove_dir_all(&rootfs).unwrap();
+        assert_eq!(success_output(status.unwrap()), "/work\n");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,191 @@
+use domain::executor::model::model::TaskOutput;
+use anyhow::{Error, Context};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+const READ_BUFFER_SIZE: usize = 8192;
+
+// Bytes of output kept with the task, the full output is spilled to a log file beyond it
+#[derive(Clone, Debug)]
+pub struct OutputCapture {
+    limit: usize,
+    // Directory of the log files, the output is only truncated without it
+    logs: Option<PathBuf>,
+}
+
+impl OutputCapture {
+    pub fn new(limit: u64, logs: Option<PathBuf>) -> OutputCapture {
+        OutputCapture { limit: limit as usize, logs }
+    }
+
+    // Read the pipe to its end, keeping the head and the tail of the output when it exceeds the limit
+    pub fn read<R: Read>(&self, mut pipe: R, log_name: &str) -> TaskOutput {
+        let head_size = self.limit / 2;
+        let tail_size = self.limit - head_size;
+        let mut head: Vec<u8> = vec![];
+        let mut tail: VecDeque<u8> = VecDeque::new();
+        let mut total: u64 = 0;
+        let mut spilled = false;
+        let mut log: Option<(PathBuf, File)> = None;
+        // Failure to write the full output, reported with the truncated content
+        let mut log_error: Option<Error> = None;
+        let mut buffer = [0; READ_BUFFER_SIZE];
+        loop {
+            let chunk = match pipe.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => &buffer[..read],
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            if !spilled && total + chunk.len() as u64 > self.limit as u64 {
+                spilled = true;
+                match self.create_log(log_name, &head) {
+                    Ok(created) => log = created,
+                    Err(err) => log_error = Some(err),
+                }
+                tail.extend(head.drain(head_size.min(head.len())..));
+            }
+            total += chunk.len() as u64;
+            if !spilled {
+                head.extend_from_slice(chunk);
+                continue;
+            }
+            // The log is given up on the first write error, the output is still truncated
+            if let Some((ref path, ref mut file)) = log {
+                if let Err(err) = file.write_all(chunk) {
+                    let _ = fs::remove_file(path);
+                    log_error = Some(Error::from(err).context(format!("Error writing log file {}", path.display())));
+                    log = None;
+                }
+            }
+            let room = head_size.saturating_sub(head.len()).min(chunk.len());
+            head.extend_from_slice(&chunk[..room]);
+            tail.extend(&chunk[room..]);
+            let excess = tail.len().saturating_sub(tail_size);
+            tail.drain(..excess);
+        }
+        let truncated = total - (head.len() + tail.len()) as u64;
+        let mut content = head;
+        match log_error {
+            Some(err) => content.extend(format!("\n[... {} bytes truncated, full output lost : {:#} ...]\n", truncated, err).into_bytes()),
+            None if truncated > 0 => content.extend(format!("\n[... {} bytes truncated ...]\n", truncated).into_bytes()),
+            None => (),
+        }
+        content.extend(tail);
+        TaskOutput { content, truncated, log: log.map(|(path, _)| path) }
+    }
+
+    // None when no log directory is set
+    fn create_log(&self, log_name: &str, head: &[u8]) -> Result<Option<(PathBuf, File)>, Error> {
+        let logs = match self.logs {
+            Some(ref logs) => logs,
+            None => return Ok(None),
+        };
+        let path = logs.join(log_name);
+        fs::create_dir_all(logs)
+            .and_then(|_| File::create(&path))
+            .and_then(|mut file| file.write_all(head).map(|_| file))
+            .context(format!("Error creating log file {}", path.display()))
+            .map(|file| Some((path, file)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io;
+
+    const OUTPUT: &[u8] = b"0123456789abcdef";
+
+    // Pipe giving the output a few bytes at a time, the limit being crossed in the middle of a read
+    struct Chunked<'a>(&'a [u8]);
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let read = self.0.len().min(buffer.len()).min(3);
+            buffer[..read].copy_from_slice(&self.0[..read]);
+            self.0 = &self.0[read..];
+            Ok(read)
+        }
+    }
+
+    // The output read at once and by chunks must be the same
+    fn read(capture: &OutputCapture, output: &[u8], log_name: &str) -> TaskOutput {
+        let whole = capture.read(output, log_name);
+        let chunked = capture.read(Chunked(output), log_name);
+        assert_eq!(whole, chunked);
+        whole
+    }
+
+    fn logs(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("tasc-logs-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_read_should_keep_output_under_the_limit() {
+        let capture = OutputCapture::new(32, Some(logs("under")));
+        assert_eq!(read(&capture, OUTPUT, "task.stdout"), TaskOutput { content: OUTPUT.to_vec(), truncated: 0, log: None });
+        assert!(!logs("under").exists());
+    }
+
+    #[test]
+    fn test_read_should_keep_output_at_the_limit() {
+        let capture = OutputCapture::new(OUTPUT.len() as u64, Some(logs("at")));
+        assert_eq!(read(&capture, OUTPUT, "task.stdout"), TaskOutput { content: OUTPUT.to_vec(), truncated: 0, log: None });
+        assert!(!logs("at").exists());
+    }
+
+    #[test]
+    fn test_read_should_keep_head_and_tail_over_the_limit() {
+        let capture = OutputCapture::new(10, None);
+        assert_eq!(read(&capture, OUTPUT, "task.stdout"), TaskOutput {
+            content: b"01234\n[... 6 bytes truncated ...]\nbcdef".to_vec(),
+            truncated: 6,
+            log: None,
+        });
+        // An odd limit gives the extra byte to the tail
+        assert_eq!(read(&OutputCapture::new(5, None), OUTPUT, "task.stdout").content, b"01\n[... 11 bytes truncated ...]\ndef".to_vec());
+    }
+
+    #[test]
+    fn test_read_should_write_full_output_to_log_over_the_limit() {
+        let logs = logs("over");
+        let capture = OutputCapture::new(10, Some(logs.clone()));
+        let output = read(&capture, OUTPUT, "task.stdout");
+        let full = fs::read(logs.join("task.stdout")).unwrap();
+        fs::remove_dir_all(&logs).unwrap();
+        assert_eq!(output, TaskOutput {
+            content: b"01234\n[... 6 bytes truncated ...]\nbcdef".to_vec(),
+            truncated: 6,
+            log: Some(logs.join("task.stdout")),
+        });
+        assert_eq!(full, OUTPUT);
+    }
+
+    #[test]
+    fn test_read_should_drop_every_byte_with_a_limit_of_0() {
+        let capture = OutputCapture::new(0, None);
+        assert_eq!(read(&capture, OUTPUT, "task.stdout"), TaskOutput {
+            content: b"\n[... 16 bytes truncated ...]\n".to_vec(),
+            truncated: 16,
+            log: None,
+        });
+        assert_eq!(read(&capture, b"", "task.stdout"), TaskOutput::default());
+    }
+
+    #[test]
+    fn test_read_should_record_log_failure_with_the_output() {
+        // The log directory can't be created under a file
+        let logs = logs("failure");
+        fs::write(&logs, "").unwrap();
+        let output = read(&OutputCapture::new(10, Some(logs.join("nested"))), OUTPUT, "task.stdout");
+        fs::remove_file(&logs).unwrap();
+        assert_eq!(String::from_utf8(output.content).unwrap(), format!(
+            "01234\n[... 6 bytes truncated, full output lost : Error creating log file {}: Not a directory (os error 20) ...]\nbcdef",
+            logs.join("nested").join("task.stdout").display()));
+        assert_eq!((output.truncated, output.log), (6, None));
+    }
+}
@@ -0,0 +1,195 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Task, TaskStatus};
+use domain::executor::model::error::TaskError;
+use anyhow::{anyhow, Error, Context};
+use std::collections::HashMap;
+
+// Kind of an executor, telling the isolation it gives to the tasks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExecutorKind {
+    Local,
+    Shell,
+    Sandbox,
+    Oci,
+    Ssh,
+    Wasm,
+}
+
+impl ExecutorKind {
+    // Commands of the local executors reach everything their user can, a container the mounts its task asks for
+    fn isolation(self) -> u8 {
+        match self {
+            ExecutorKind::Local | ExecutorKind::Shell | ExecutorKind::Ssh => 0,
+            ExecutorKind::Oci => 1,
+            ExecutorKind::Sandbox | ExecutorKind::Wasm => 2,
+        }
+    }
+}
+
+// Executors declared in the settings, each task is run by the one it names
+// The dispatch stays in infra since the executors are a matter of settings, the domain only sees one TaskExecutionPort
+pub struct ExecutorRegistry {
+    executors: HashMap<String, (ExecutorKind, Box<dyn TaskExecutionPort>)>,
+    // Executor of the tasks which neither name one nor need a specific isolation
+    default: String,
+    // Executors the tasks may use even when they isolate less than the default one
+    weaker: Vec<String>,
+}
+
+impl TaskExecutionPort for ExecutorRegistry {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        // ssh:build1 names the ssh executor and the host it runs the task on
+        let (name, host) = match task.executor {
+            Some(ref executor) => match executor.split_once(':') {
+                Some((name, host)) => (name, Some(host)),
+                None => (executor.as_str(), None),
+            },
+            None => (self.implicit(task), None),
+        };
+        let (kind, executor) = self.executors.get(name)
+            .ok_or_else(|| TaskError::ExecutionError { source: anyhow!("Executor {} is not declared", name) })
+            .context(format!("Error running task {}", task.id))?;
+        check_isolation(name, *kind, host.is_some(), task).context(format!("Error running task {}", task.id))?;
+        self.check_floor(name, *kind).context(format!("Error running task {}", task.id))?;
+        match host {
+            Some(host) => executor.execute(&Task { host: Some(host.to_string()), ..task.clone() }),
+            None => executor.execute(task),
+        }
+    }
+}
+
+impl ExecutorRegistry {
+    pub fn new(default: String, weaker: Vec<String>) -> ExecutorRegistry {
+        ExecutorRegistry { executors: HashMap::new(), default, weaker }
+    }
+
+    pub fn register(&mut self, name: String, kind: ExecutorKind, executor: Box<dyn TaskExecutionPort>) {
+        self.executors.insert(name, (kind, executor));
+    }
+
+    // Tasks defining an isolation are run by the executor named after it
+    fn implicit(&self, task: &Task) -> &str {
+        if task.wasm.is_some() {
+            "wasm"
+        } else if task.container.is_some() {
+            "oci"
+        } else if task.host.is_some() {
+            "ssh"
+        } else if task.sandboxed {
+            "sandbox"
+        } else {
+            &self.default
+        }
+    }
+
+    // The isolation of the default executor is a floor, a task can't escape it by naming another executor
+    fn check_floor(&self, name: &str, kind: ExecutorKind) -> Result<(), TaskError> {
+        match self.executors.get(&self.default) {
+            Some((default, _)) if kind.isolation() < default.isolation() && !self.weaker.iter().any(|weaker| weaker == name) =>
+                Err(TaskError::PermissionError(format!("Executor {} of kind {:?} isolates less than the default executor {}", name, kind, self.default))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// A task naming its executor must not lose the isolation it asks for, nor give a host to an executor ignoring it
+fn check_isolation(name: &str, kind: ExecutorKind, named_host: bool, task: &Task) -> Result<(), TaskError> {
+    let missing = if task.wasm.is_some() && kind != ExecutorKind::Wasm {
+        Some("a WASI module")
+    } else if task.container.is_some() && kind != ExecutorKind::Oci {
+        Some("a container")
+    } else if (task.host.is_some() || named_host) && kind != ExecutorKind::Ssh {
+        Some("a remote host")
+    } else if task.sandboxed && kind != ExecutorKind::Sandbox {
+        Some("a sandbox")
+    } else {
+        None
+    };
+    match missing {
+        Some(isolation) => Err(TaskError::ValidationError(format!("Executor {} of kind {:?} can't run a task with {}", name, kind, isolation))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::{success_output, task};
+    use domain::executor::model::model::{Container, TaskOutput, WasmModule, WasmSource};
+    use std::path::PathBuf;
+
+    // Executor answering with its name and the host it is given
+    struct NamedExecutor(&'static str);
+
+    impl TaskExecutionPort for NamedExecutor {
+        fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+            Ok(TaskStatus::Success(TaskOutput::from(format!("{} {}", self.0, task.host.as_deref().unwrap_or("-")))))
+        }
+    }
+
+    fn registry() -> ExecutorRegistry {
+        registry_with_default("local", vec![])
+    }
+
+    fn registry_with_default(default: &str, weaker: Vec<String>) -> ExecutorRegistry {
+        let mut registry = ExecutorRegistry::new(default.to_string(), weaker);
+        for (name, kind) in [("local", ExecutorKind::Local), ("shell", ExecutorKind::Shell), ("sandbox", ExecutorKind::Sandbox),
+                             ("oci", ExecutorKind::Oci), ("build", ExecutorKind::Ssh), ("wasm", ExecutorKind::Wasm)] {
+            registry.register(name.to_string(), kind, Box::new(NamedExecutor(name)));
+        }
+        registry
+    }
+
+    fn named(executor: &str, task: Task) -> Task {
+        Task { executor: Some(executor.to_string()), ..task }
+    }
+
+    fn container() -> Option<Container> {
+        Some(Container { rootfs: PathBuf::from("/images/busybox"), mounts: vec![] })
+    }
+
+    #[test]
+    fn test_registry_should_dispatch_on_named_or_implicit_executor() {
+        let registry = registry();
+        assert_eq!(success_output(registry.execute(&task("ls")).unwrap()), "local -");
+        assert_eq!(success_output(registry.execute(&named("shell", task("ls"))).unwrap()), "shell -");
+        assert_eq!(success_output(registry.execute(&Task { sandboxed: true, ..task("ls") }).unwrap()), "sandbox -");
+        assert_eq!(success_output(registry.execute(&named("build:ci1:2222", task("ls"))).unwrap()), "build ci1:2222");
+        assert_eq!(success_output(registry.execute(&named("oci", Task { container: container(), ..task("ls") })).unwrap()), "oci -");
+        assert!(registry.execute(&named("unknown", task("ls"))).is_err());
+    }
+
+    #[test]
+    fn test_registry_should_refuse_executors_without_the_requested_isolation() {
+        let registry = registry();
+        let refused = vec![
+            named("local", Task { sandboxed: true, ..task("ls") }),
+            named("shell", Task { container: container(), ..task("ls") }),
+            named("sandbox", Task { host: Some("ci1".to_string()), ..task("ls") }),
+            named("oci", Task { host: Some("ci1".to_string()), container: container(), ..task("ls") }),
+            named("local", Task { wasm: Some(WasmModule { source: WasmSource::Path(PathBuf::from("/opt/task.wasm")), dir: None, fuel: None }), ..task("ls") }),
+            named("shell", Task { sandboxed: true, ..task("ls") }),
+            named("oci", Task { sandboxed: true, container: container(), ..task("ls") }),
+            named("local:ci1", task("ls")),
+            named("sandbox:ci1", task("ls")),
+        ];
+        for task in refused {
+            let error = registry.execute(&task).unwrap_err();
+            assert!(format!("{:?}", error).contains("can't run a task with"), "{:?} should be refused, got {:?}", task.executor, error);
+        }
+    }
+
+    #[test]
+    fn test_registry_should_refuse_executors_weaker_than_the_default() {
+        let registry = registry_with_default("sandbox", vec![]);
+        assert_eq!(success_output(registry.execute(&task("ls")).unwrap()), "sandbox -");
+        assert_eq!(success_output(registry.execute(&named("wasm", Task { wasm: Some(WasmModule { source: WasmSource::Blob(vec![]), dir: None, fuel: None }), ..task("ls") })).unwrap()), "wasm -");
+        for refused in [named("local", task("ls")), named("shell", task("ls")), named("build:ci1", task("ls")), Task { container: container(), ..task("ls") }] {
+            let error = registry.execute(&refused).unwrap_err();
+            assert!(format!("{:#}", error).contains("isolates less than the default executor sandbox"), "{:?} should be refused, got {:#}", refused.executor, error);
+        }
+        let registry = registry_with_default("sandbox", vec!["build".to_string()]);
+        assert_eq!(success_output(registry.execute(&named("build:ci1", task("ls"))).unwrap()), "build ci1");
+        assert!(registry.execute(&named("local", task("ls"))).is_err());
+    }
+}
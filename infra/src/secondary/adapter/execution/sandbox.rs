@@ -0,0 +1,230 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Credentials, Task, TaskStatus};
+use domain::executor::model::error::TaskError;
+use anyhow::{anyhow, Error, Context};
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use super::{complete, split_command, LocalExecutionAdapter};
+
+// Flag of the mount_setattr uapi, not exposed by libc
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+// User the command is run as inside the sandbox, it has no capability once the command is executed
+// It is also the user of the host the command is run as when tasc runs as root without credentials
+const SANDBOX_ID: u32 = 65534;
+
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+// Run tasks in fresh user, mount, PID and network namespaces with a read-only root
+pub struct SandboxExecutionAdapter {
+    local: LocalExecutionAdapter,
+}
+
+impl TaskExecutionPort for SandboxExecutionAdapter {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        // The user of the sandbox is mapped onto the one of the command, as root it would own every file of the host
+        let credentials = self.local.credentials(task).cloned()
+            .or_else(|| Some(Credentials { uid: SANDBOX_ID, gid: SANDBOX_ID, groups: vec![] }).filter(|_| unsafe { libc::geteuid() } == 0));
+        if credentials.as_ref().is_some_and(|credentials| credentials.uid == 0 || credentials.gid == 0 || credentials.groups.contains(&0)) {
+            return Err(TaskError::PermissionError(format!("Sandboxed task {} can't be run as root", task.id)).into());
+        }
+        let task = &Task { credentials: credentials.clone(), ..task.clone() };
+        let (mut command, _cgroup) = self.local.prepare(task, split_command(task)?)?;
+        let scratch = ScratchDir::create(&task.id)
+            .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })
+            .context(format!("Error creating scratch directory for task {}", task.id))?;
+        let credentials = credentials.as_ref();
+        if let Some(credentials) = credentials {
+            std::os::unix::fs::chown(&scratch.0, Some(credentials.uid), Some(credentials.gid))
+                .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })
+                .context(format!("Error giving scratch directory to user {}", credentials.uid))?;
+        }
+        let sandbox = Sandbox::new(&scratch.0, task.working_dir.as_deref().unwrap_or(&scratch.0), credentials)?;
+        command.env("TMPDIR", &scratch.0);
+        // Only async-signal-safe calls are allowed between fork and exec
+        unsafe {
+            command.pre_exec(move || sandbox.enter());
+        }
+        complete(command, task, &self.local.capture)
+    }
+}
+
+impl SandboxExecutionAdapter {
+    pub fn new(local: LocalExecutionAdapter) -> SandboxExecutionAdapter {
+        SandboxExecutionAdapter { local }
+    }
+}
+
+// Only writable directory of the sandbox, removed with its content when dropped
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn create(id: &str) -> io::Result<ScratchDir> {
+        let path = env::temp_dir().join(format!("tasc-{}", id));
+        fs::create_dir(&path).map(|_| ScratchDir(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Everything is allocated before the fork so entering the sandbox is async-signal-safe
+struct Sandbox {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    scratch: CString,
+    working_dir: CString,
+}
+
+impl Sandbox {
+    // The namespace is created once the user is switched, the ids of the command are mapped
+    fn new(scratch: &Path, working_dir: &Path, credentials: Option<&Credentials>) -> Result<Sandbox, Error> {
+        let (uid, gid) = credentials.map(|credentials| (credentials.uid, credentials.gid))
+            .unwrap_or_else(|| unsafe { (libc::geteuid(), libc::getegid()) });
+        let c_path = |path: &Path| CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| TaskError::ExecutionError { source: anyhow!("{:?}", err) })
+            .context(format!("Invalid path {}", path.display()));
+        Ok(Sandbox {
+            uid_map: format!("{} {} 1", SANDBOX_ID, uid).into_bytes(),
+            gid_map: format!("{} {} 1", SANDBOX_ID, gid).into_bytes(),
+            scratch: c_path(scratch)?,
+            working_dir: c_path(working_dir)?,
+        })
+    }
+
+    // Called between fork and exec, the process forks once more to enter the PID namespace :
+    // the intermediate process only waits for the command and exits with its status
+    fn enter(&self) -> io::Result<()> {
+        // Switching user clears the dumpable flag, which makes /proc/self owned by root
+        check(unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 1) })?;
+        check(unsafe { libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWNET) })?;
+        write_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_file(b"/proc/self/uid_map\0", &self.uid_map)?;
+        write_file(b"/proc/self/gid_map\0", &self.gid_map)?;
+        check(unsafe { libc::mount(ptr::null(), b"/\0".as_ptr() as *const libc::c_char, ptr::null(), libc::MS_REC | libc::MS_PRIVATE, ptr::null()) })?;
+        set_read_only(b"/\0".as_ptr() as *const libc::c_char, libc::AT_RECURSIVE as libc::c_uint, true)?;
+        check(unsafe { libc::mount(self.scratch.as_ptr(), self.scratch.as_ptr(), ptr::null(), libc::MS_BIND, ptr::null()) })?;
+        set_read_only(self.scratch.as_ptr(), 0, false)?;
+        match unsafe { libc::fork() } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => {
+                check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) })?;
+                // A fresh /proc only shows the processes of the sandbox, it is refused when parts of /proc are masked
+                unsafe {
+                    libc::mount(b"proc\0".as_ptr() as *const libc::c_char, b"/proc\0".as_ptr() as *const libc::c_char,
+                                b"proc\0".as_ptr() as *const libc::c_char, libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC, ptr::null());
+                }
+                // The current directory still points to the mount it had before the sandbox
+                check(unsafe { libc::chdir(self.working_dir.as_ptr()) })
+            }
+            child => unsafe {
+                // Release the pipes of the command, spawn only returns once they are all closed
+                libc::syscall(libc::SYS_close_range, 3 as libc::c_uint, libc::c_uint::MAX, 0 as libc::c_uint);
+                let mut status = 0;
+                while libc::waitpid(child, &mut status, 0) == -1 && io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {}
+                if libc::WIFEXITED(status) {
+                    libc::_exit(libc::WEXITSTATUS(status))
+                }
+                libc::_exit(128 + libc::WTERMSIG(status))
+            },
+        }
+    }
+}
+
+fn set_read_only(path: *const libc::c_char, flags: libc::c_uint, read_only: bool) -> io::Result<()> {
+    let attr = MountAttr {
+        attr_set: if read_only { MOUNT_ATTR_RDONLY } else { 0 },
+        attr_clr: if read_only { 0 } else { MOUNT_ATTR_RDONLY },
+        propagation: 0,
+        userns_fd: 0,
+    };
+    let result = unsafe { libc::syscall(libc::SYS_mount_setattr, libc::AT_FDCWD, path, flags, &attr as *const MountAttr, std::mem::size_of::<MountAttr>()) };
+    check(result as libc::c_int)
+}
+
+fn write_file(path: &[u8], content: &[u8]) -> io::Result<()> {
+    let fd = unsafe { libc::open(path.as_ptr() as *const libc::c_char, libc::O_WRONLY | libc::O_CLOEXEC) };
+    check(fd)?;
+    let written = match unsafe { libc::write(fd, content.as_ptr() as *const libc::c_void, content.len()) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    };
+    unsafe { libc::close(fd) };
+    written
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::OutputCapture;
+    use crate::secondary::adapter::execution::tests::{success_output, task};
+    use std::os::unix::fs::PermissionsExt;
+
+    fn sandbox() -> SandboxExecutionAdapter {
+        SandboxExecutionAdapter::new(LocalExecutionAdapter::new(None, None, OutputCapture::new(1024, None)))
+    }
+
+    // User namespaces can be disabled by the host or by the container running the tests
+    fn namespaces_available() -> bool {
+        match sandbox().execute(&task("true")) {
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!("Sandbox tests skipped, namespaces are not available: {:?}", err);
+                false
+            }
+        }
+    }
+
+    #[test]
+    fn test_sandbox_should_isolate_the_command() {
+        if !namespaces_available() {
+            return;
+        }
+        let sandbox = sandbox();
+        assert_eq!(success_output(sandbox.execute(&task("id -u")).unwrap()), format!("{}\n", SANDBOX_ID));
+        assert!(matches!(sandbox.execute(&task("touch /tasc-sandbox-test")).unwrap(), TaskStatus::Error(_, Some(1))));
+        assert!(!Path::new("/tasc-sandbox-test").exists());
+        // Only the loopback interface exists in the network namespace
+        let interfaces = success_output(sandbox.execute(&task("cat /proc/net/dev")).unwrap());
+        assert!(interfaces.lines().skip(2).all(|line| line.trim_start().starts_with("lo:")), "{}", interfaces);
+    }
+
+    #[test]
+    fn test_sandbox_should_not_map_root_of_the_host() {
+        if unsafe { libc::geteuid() } != 0 || !namespaces_available() {
+            return;
+        }
+        let secret = env::temp_dir().join(format!("tasc-sandbox-secret-{}", std::process::id()));
+        fs::write(&secret, "secret").unwrap();
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o600)).unwrap();
+        let status = sandbox().execute(&task(&format!("cat {}", secret.display())));
+        fs::remove_file(&secret).unwrap();
+        assert!(matches!(status.unwrap(), TaskStatus::Error(_, Some(1))));
+        let mut as_root = task("true");
+        as_root.credentials = Some(Credentials { uid: 0, gid: 0, groups: vec![] });
+        assert_eq!(sandbox().execute(&as_root).unwrap_err().to_string(), "Permission denied : Sandboxed task test can't be run as root");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Task, TaskStatus};
+use anyhow::Error;
+use std::path::PathBuf;
+use std::process::Command;
+
+use super::{complete, LocalExecutionAdapter};
+
+// Run the command of the tasks through a shell, so pipes, redirections and quotes are interpreted
+pub struct ShellExecutionAdapter {
+    local: LocalExecutionAdapter,
+    shell: PathBuf,
+}
+
+impl TaskExecutionPort for ShellExecutionAdapter {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        let mut command = Command::new(&self.shell);
+        command.arg("-c").arg(&task.command);
+        let (command, _cgroup) = self.local.prepare(task, command)?;
+        complete(command, task, &self.local.capture)
+    }
+}
+
+impl ShellExecutionAdapter {
+    pub fn new(local: LocalExecutionAdapter, shell: PathBuf) -> ShellExecutionAdapter {
+        ShellExecutionAdapter { local, shell }
+    }
+}
\ No newline at end of file
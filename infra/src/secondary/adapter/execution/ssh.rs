@@ -0,0 +1,208 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Task, TaskStatus};
+use domain::executor::model::error::TaskError;
+use anyhow::{anyhow, Error, Context};
+use std::path::PathBuf;
+use std::process::Command;
+
+use super::{complete, OutputCapture};
+
+const SSH_FAILURE: i32 = 255;
+
+// Run the tasks on a remote host through the ssh client with key-based authentication only
+pub struct SshExecutionAdapter {
+    // Host of the tasks which don't define theirs
+    host: Option<String>,
+    user: Option<String>,
+    identity: Option<PathBuf>,
+    // Hosts missing from this file are refused
+    known_hosts: Option<PathBuf>,
+    capture: OutputCapture,
+}
+
+impl TaskExecutionPort for SshExecutionAdapter {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        let host = match task.host.as_ref().or(self.host.as_ref()) {
+            Some(host) => host,
+            None => return Err(TaskError::ExecutionError { source: anyhow!("No remote host is defined") })
+                .context(format!("Error running task {} on a remote host", task.id)),
+        };
+        // The option limiting processes differs between the shells of the remote hosts
+        if task.sandboxed || task.credentials.is_some() || task.container.is_some() || task.limits.processes.is_some() {
+            return Err(TaskError::ExecutionError { source: anyhow!("Sandbox, user, container and processes limit are not supported on remote hosts") })
+                .context(format!("Error running task {} on {}", task.id, host));
+        }
+        let (host, port) = parse_host(host).context(format!("Error running task {} on a remote host", task.id))?;
+        let mut command = Command::new("ssh");
+        command.args(["-T", "-o", "BatchMode=yes", "-o", "StrictHostKeyChecking=yes", "-o", "PasswordAuthentication=no", "-o", "KbdInteractiveAuthentication=no"]);
+        if let Some(ref known_hosts) = self.known_hosts {
+            command.arg("-o").arg(format!("UserKnownHostsFile={}", known_hosts.display()));
+        }
+        if let Some(ref identity) = self.identity {
+            command.args(["-o", "IdentitiesOnly=yes", "-i"]).arg(identity);
+        }
+        if let Some(port) = port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if let Some(ref user) = self.user {
+            command.arg("-l").arg(user);
+        }
+        // Options end before the host, which can't be read as one of them
+        command.arg("--").arg(host).arg(remote_command(task));
+        match complete(command, task, &self.capture)? {
+            // ssh exits with 255 when the connection fails, it can't be told apart from a command exiting with 255
+            TaskStatus::Error(output, Some(SSH_FAILURE)) => Err(TaskError::ExecutionError { source: anyhow!("{}", output) })
+                .context(format!("Error connecting to {} for task {}", host, task.id)),
+            status => Ok(status),
+        }
+    }
+}
+
+impl SshExecutionAdapter {
+    pub fn new(host: Option<String>, user: Option<String>, identity: Option<PathBuf>, known_hosts: Option<PathBuf>, capture: OutputCapture) -> SshExecutionAdapter {
+        SshExecutionAdapter { host, user, identity, known_hosts, capture }
+    }
+}
+
+// HOST, HOST:PORT, an IPv6 address or [ADDRESS]:PORT, the host may be prefixed by USER@
+fn parse_host(source: &str) -> Result<(&str, Option<u16>), TaskError> {
+    let invalid = || TaskError::ValidationError(format!("{} is not a valid host", source));
+    let (host, port) = match source.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once(']') {
+            Some((address, "")) => (address, None),
+            Some((address, port)) => (address, Some(port.strip_prefix(':').ok_or_else(invalid)?)),
+            None => return Err(invalid()),
+        },
+        // A bare IPv6 address has no port
+        None if source.matches(':').count() > 1 => (source, None),
+        None => match source.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (source, None),
+        },
+    };
+    if host.is_empty() || host.starts_with('-') || host.contains(char::is_whitespace) {
+        return Err(invalid());
+    }
+    let port = port.map(|port| port.parse::<u16>().map_err(|_| invalid())).transpose()?;
+    Ok((host, port))
+}
+
+// Shell command applying the process settings of the task before running it on the remote host
+// Killing the client on timeout closes the session but a command ignoring SIGHUP keeps running
+fn remote_command(task: &Task) -> String {
+    let mut steps: Vec<String> = vec![];
+    if let Some(ref working_dir) = task.working_dir {
+        steps.push(format!("cd {}", quote(&working_dir.to_string_lossy())));
+    }
+    if let Some(umask) = task.umask {
+        steps.push(format!("umask {:03o}", umask));
+    }
+    let limits = &task.limits;
+    let ulimits: Vec<String> = vec![
+        ("-t", limits.cpu_time.map(|cpu_time| cpu_time.as_secs())),
+        ("-v", limits.memory.map(|memory| memory / 1024)),
+        ("-n", limits.open_files),
+    ].into_iter()
+        .filter_map(|(option, limit)| limit.map(|limit| format!("ulimit {} {}", option, limit)))
+        .collect();
+    steps.extend(ulimits);
+    let env = task.env.iter()
+        .flat_map(|env| env.iter().map(|(key, value)| quote(&format!("{}={}", key, value))));
+    let args = task.command.split_whitespace().map(quote);
+    steps.push(vec!["exec".to_string(), "env".to_string()].into_iter().chain(env).chain(args).collect::<Vec<String>>().join(" "));
+    steps.join(" && ")
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::{program, success_output, task};
+    use std::env;
+    use std::fs;
+    use std::net::{TcpListener, TcpStream};
+    use std::path::Path;
+    use std::process::{Child, Stdio};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_parse_host_should_split_the_port() {
+        assert_eq!(parse_host("build1").unwrap(), ("build1", None));
+        assert_eq!(parse_host("ci@build1:2222").unwrap(), ("ci@build1", Some(2222)));
+        assert_eq!(parse_host("fe80::1").unwrap(), ("fe80::1", None));
+        assert_eq!(parse_host("[fe80::1]").unwrap(), ("fe80::1", None));
+        assert_eq!(parse_host("[::1]:2222").unwrap(), ("::1", Some(2222)));
+        for invalid in ["-oProxyCommand=touch /tmp/pwned", "build1 -v", "build1:ssh", "[::1]2222", "[::1", ":22", ""].iter() {
+            assert!(parse_host(invalid).is_err(), "{} should be refused", invalid);
+        }
+    }
+
+    // sshd of the test, killed when dropped
+    struct Sshd(Child);
+
+    impl Drop for Sshd {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn keygen(path: &Path) {
+        let status = Command::new("ssh-keygen").args(["-q", "-t", "ed25519", "-N", "", "-f"]).arg(path).status().unwrap();
+        assert!(status.success());
+    }
+
+    // sshd accepting the key of the test on a free port of localhost, running as the user of the test
+    fn start_sshd(sshd: &Path, directory: &Path) -> (Sshd, u16) {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        keygen(&directory.join("host_key"));
+        keygen(&directory.join("client_key"));
+        fs::copy(directory.join("client_key.pub"), directory.join("authorized_keys")).unwrap();
+        let host_key = fs::read_to_string(directory.join("host_key.pub")).unwrap();
+        fs::write(directory.join("known_hosts"), format!("[127.0.0.1]:{} {}", port, host_key)).unwrap();
+        let child = Command::new(sshd)
+            .args(["-D", "-e", "-f", "/dev/null", "-p", &port.to_string(), "-o", "ListenAddress=127.0.0.1", "-o", "StrictModes=no", "-o", "PidFile=none"])
+            .arg("-h").arg(directory.join("host_key"))
+            .arg("-o").arg(format!("AuthorizedKeysFile={}", directory.join("authorized_keys").display()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let start = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(start.elapsed() < Duration::from_secs(10), "sshd is not listening on port {}", port);
+            thread::sleep(Duration::from_millis(50));
+        }
+        (Sshd(child), port)
+    }
+
+    #[test]
+    fn test_ssh_executor_should_run_the_command_on_localhost() {
+        let sshd = match program("sshd").or_else(|| Some(PathBuf::from("/usr/sbin/sshd")).filter(|sshd| sshd.is_file())) {
+            Some(sshd) if program("ssh").is_some() => sshd,
+            _ => {
+                eprintln!("ssh test skipped, sshd is not installed");
+                return;
+            }
+        };
+        let directory = env::temp_dir().join(format!("tasc-sshd-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let (_sshd, port) = start_sshd(&sshd, &directory);
+        let user = String::from_utf8(Command::new("id").arg("-un").output().unwrap().stdout).unwrap().trim().to_string();
+        let adapter = SshExecutionAdapter::new(Some(format!("127.0.0.1:{}", port)), Some(user), Some(directory.join("client_key")),
+                                               Some(directory.join("known_hosts")), OutputCapture::new(1024, None));
+        let mut echo = task("echo remote");
+        echo.working_dir = Some(PathBuf::from("/"));
+        let echoed = adapter.execute(&echo);
+        let failed = adapter.execute(&task("false"));
+        let unreachable = adapter.execute(&Task { host: Some("127.0.0.1:1".to_string()), ..task("true") });
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(success_output(echoed.unwrap()), "remote\n");
+        assert!(matches!(failed.unwrap(), TaskStatus::Error(_, Some(1))));
+        assert!(unreachable.is_err());
+    }
+}
\ No newline at end of file
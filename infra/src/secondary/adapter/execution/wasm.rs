@@ -0,0 +1,180 @@
+use domain::executor::ports::secondary::TaskExecutionPort;
+use domain::executor::model::model::{Task, TaskStatus, WasmModule, WasmSource};
+use domain::executor::model::error::TaskError;
+use anyhow::{anyhow, Error, Context};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+use wasmtime_wasi::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, WasiCtxBuilder};
+
+use super::OutputCapture;
+
+const OUTPUT_CAPACITY: usize = 16 << 20;
+
+struct WasmState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+// Run the tasks defining a WASI module with an embedded runtime
+pub struct WasmExecutionAdapter {
+    engine: Engine,
+    capture: OutputCapture,
+}
+
+impl TaskExecutionPort for WasmExecutionAdapter {
+    fn execute(&self, task: &Task) -> Result<TaskStatus, Error> {
+        // Fuel and timeout bound the execution of a module, it has no process to apply the other limits to
+        let limits = &task.limits;
+        if limits.cpu_time.is_some() || limits.open_files.is_some() || limits.processes.is_some() {
+            return Err(TaskError::ExecutionError { source: anyhow!("CPU time, open files and processes limits are not supported by WASI modules") })
+                .context(format!("Error running task {} WASI module", task.id));
+        }
+        match task.wasm {
+            Some(ref wasm) => self.run(task, wasm),
+            None => Err(anyhow!("No WASI module is defined")),
+        }
+            .map_err(|err| TaskError::ExecutionError { source: err })
+            .context("Error during WASI module execution")
+    }
+}
+
+impl WasmExecutionAdapter {
+    pub fn new(capture: OutputCapture) -> Result<WasmExecutionAdapter, Error> {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+        Engine::new(&config)
+            .map(|engine| WasmExecutionAdapter { engine, capture })
+            .context("Error creating WASI runtime")
+    }
+
+    fn run(&self, task: &Task, wasm: &WasmModule) -> Result<TaskStatus, Error> {
+        let module = match wasm.source {
+            WasmSource::Path(ref path) => Module::from_file(&self.engine, path),
+            WasmSource::Blob(ref blob) => Module::new(&self.engine, blob),
+        }.context("Error loading WASI module")?;
+        let stdout = MemoryOutputPipe::new(OUTPUT_CAPACITY);
+        let stderr = MemoryOutputPipe::new(OUTPUT_CAPACITY);
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.args(&task.command.split_whitespace().collect::<Vec<&str>>())
+            .envs(&task.env.iter().flat_map(|env| env.iter().map(|(key, value)| (key, value))).collect::<Vec<(&String, &String)>>())
+            .stdin(MemoryInputPipe::new(task.stdin.clone().unwrap_or_default()))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone());
+        if let Some(ref dir) = wasm.dir {
+            wasi.preopened_dir(dir, "/", DirPerms::all(), FilePerms::all())
+                .context(format!("Error opening directory {}", dir.display()))?;
+        }
+        let mut limits = StoreLimitsBuilder::new();
+        if let Some(memory) = task.limits.memory {
+            limits = limits.memory_size(memory as usize);
+        }
+        let mut store = Store::new(&self.engine, WasmState { wasi: wasi.build_p1(), limits: limits.build() });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(wasm.fuel.unwrap_or(u64::MAX))?;
+        store.set_epoch_deadline(1);
+        let mut linker: Linker<WasmState> = Linker::new(&self.engine);
+        preview1::add_to_linker_sync(&mut linker, |state| &mut state.wasi)?;
+        let start = linker.module(&mut store, "", &module)
+            .and_then(|linker| linker.get_default(&mut store, ""))
+            .and_then(|start| start.typed::<(), ()>(&store))
+            .context("Error linking WASI module")?;
+        // The execution is interrupted when the timeout elapses before the module returns
+        let (done, timer) = mpsc::channel::<()>();
+        if let Some(timeout) = task.timeout {
+            let engine = self.engine.clone();
+            thread::spawn(move || if let Err(RecvTimeoutError::Timeout) = timer.recv_timeout(timeout) {
+                engine.increment_epoch();
+            });
+        }
+        let result = start.call(&mut store, ());
+        drop(done);
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(err) => match (err.downcast_ref::<I32Exit>(), err.downcast_ref::<Trap>()) {
+                (Some(exit), _) => exit.0,
+                (_, Some(Trap::Interrupt)) => return Ok(TaskStatus::Error(format!("Task timed out after {} seconds", task.timeout.unwrap_or_default().as_secs()).into(), None)),
+                (_, Some(Trap::OutOfFuel)) => return Ok(TaskStatus::Error("WASI module ran out of fuel".into(), None)),
+                _ => return Ok(TaskStatus::Error(format!("{:?}", err).into(), None)),
+            },
+        };
+        Ok(match exit_code {
+            0 => TaskStatus::Success(self.capture.read(&stdout.contents()[..], &format!("{}.stdout", task.id))),
+            _ => TaskStatus::Error(self.capture.read(&stderr.contents()[..], &format!("{}.stderr", task.id)), Some(exit_code)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::{success_output, task};
+    use domain::executor::model::model::ResourceLimits;
+    use std::time::Duration;
+    use wast::parser::{self, ParseBuffer};
+    use wast::Wat;
+
+    // Module printing hello on stdout then exiting with the code given by the body
+    const HELLO: &str = r#"(module
+        (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 8) "hello\n")
+        (func (export "_start")
+            (i32.store (i32.const 0) (i32.const 8))
+            (i32.store (i32.const 4) (i32.const 6))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16)))
+            BODY))"#;
+
+    fn wasm_task(body: &str, fuel: Option<u64>) -> Task {
+        let text = HELLO.replace("BODY", body);
+        let buffer = ParseBuffer::new(&text).unwrap();
+        let module = parser::parse::<Wat>(&buffer).unwrap().module.encode().unwrap();
+        Task { wasm: Some(WasmModule { source: WasmSource::Blob(module), dir: None, fuel }), ..task("hello") }
+    }
+
+    fn adapter() -> WasmExecutionAdapter {
+        WasmExecutionAdapter::new(OutputCapture::new(1024, None)).unwrap()
+    }
+
+    fn error_output(status: TaskStatus) -> (String, Option<i32>) {
+        match status {
+            TaskStatus::Error(output, code) => (output.to_string(), code),
+            status => panic!("Task has not failed: {:?}", status),
+        }
+    }
+
+    #[test]
+    fn test_wasm_should_capture_stdout_and_exit_code() {
+        assert_eq!(success_output(adapter().execute(&wasm_task("", None)).unwrap()), "hello\n");
+        assert_eq!(error_output(adapter().execute(&wasm_task("(call $proc_exit (i32.const 3))", None)).unwrap()), (String::new(), Some(3)));
+    }
+
+    #[test]
+    fn test_wasm_should_stop_on_fuel_exhaustion_and_timeout() {
+        assert_eq!(error_output(adapter().execute(&wasm_task("(loop (br 0))", Some(10_000))).unwrap()), ("WASI module ran out of fuel".to_string(), None));
+        let mut task = wasm_task("(loop (br 0))", None);
+        task.timeout = Some(Duration::from_secs(1));
+        assert_eq!(error_output(adapter().execute(&task).unwrap()), ("Task timed out after 1 seconds".to_string(), None));
+    }
+
+    #[test]
+    fn test_wasm_should_refuse_memory_beyond_the_limit() {
+        // Growing the memory returns -1 when it is refused
+        let grow = "(if (i32.eq (memory.grow (i32.const 32)) (i32.const -1)) (then (call $proc_exit (i32.const 12))))";
+        assert!(matches!(adapter().execute(&wasm_task(grow, None)).unwrap(), TaskStatus::Success(_)));
+        let mut task = wasm_task(grow, None);
+        task.limits.memory = Some(1 << 20);
+        assert_eq!(error_output(adapter().execute(&task).unwrap()).1, Some(12));
+    }
+
+    #[test]
+    fn test_wasm_should_reject_process_limits() {
+        let mut task = wasm_task("", None);
+        task.limits = ResourceLimits { open_files: Some(64), ..ResourceLimits::default() };
+        assert_eq!(format!("{:#}", adapter().execute(&task).unwrap_err()),
+                   "Error running task test WASI module: Error executing the command: CPU time, open files and processes limits are not supported by WASI modules");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,193 @@
+use domain::executor::model::model::{HookStage, Task, TaskStatus};
+use domain::executor::ports::secondary::{HookPort, TaskExecutionPort};
+use anyhow::{anyhow, Error, Context};
+use itertools::Itertools;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::secondary::adapter::execution::OutputCapture;
+
+// Bytes of stderr of a settings hook kept for its failure, its stdout is discarded
+const HOOK_STDERR_LIMIT: u64 = 4096;
+
+// Run the hooks of the settings through a shell where tasc runs, and the ones of the tasks as their command would be run
+pub struct ShellHookAdapter {
+    shell: PathBuf,
+    // Hooks run around every task, outside of its own ones
+    pre: Option<String>,
+    post: Option<String>,
+    // Executor of the hooks of the tasks, they are refused without it
+    task_hooks: Option<Box<dyn TaskExecutionPort>>,
+}
+
+impl HookPort for ShellHookAdapter {
+    // Pre hooks stop at the first failure, post hooks are all run to clean up as much as possible
+    fn run(&self, task: &Task, stage: HookStage, status: &TaskStatus) -> Result<(), Error> {
+        match stage {
+            HookStage::Pre => self.run_settings_hook(self.pre.as_ref(), task, stage, status)
+                .and_then(|_| self.run_task_hook(task.hooks.pre.as_ref(), task, stage, status)),
+            HookStage::Post => {
+                let failures: Vec<String> = vec![self.run_task_hook(task.hooks.post.as_ref(), task, stage, status),
+                                                 self.run_settings_hook(self.post.as_ref(), task, stage, status)].into_iter()
+                    .filter_map(Result::err)
+                    .map(|err| format!("{:#}", err))
+                    .collect();
+                match failures.is_empty() {
+                    true => Ok(()),
+                    false => Err(anyhow!(failures.join(", "))),
+                }
+            }
+        }
+    }
+}
+
+impl ShellHookAdapter {
+    pub fn new(shell: PathBuf, pre: Option<String>, post: Option<String>, task_hooks: Option<Box<dyn TaskExecutionPort>>) -> ShellHookAdapter {
+        ShellHookAdapter { shell, pre, post, task_hooks }
+    }
+
+    fn run_settings_hook(&self, hook: Option<&String>, task: &Task, stage: HookStage, status: &TaskStatus) -> Result<(), Error> {
+        let hook = match hook {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+        let mut child = Command::new(&self.shell)
+            .arg("-c")
+            .arg(hook)
+            .envs(hook_env(task, stage, status))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(format!("Error running {} hook {}", stage, hook))?;
+        let stderr = child.stderr.take()
+            .map(|stderr| OutputCapture::new(HOOK_STDERR_LIMIT, None).read(stderr, ""))
+            .unwrap_or_default();
+        let exit_status = child.wait()
+            .context(format!("Error waiting {} hook {}", stage, hook))?;
+        hook_result(hook, exit_status.code(), &stderr.to_string())
+    }
+
+    // The user, env, working directory and limits of the task apply to its hooks
+    fn run_task_hook(&self, hook: Option<&String>, task: &Task, stage: HookStage, status: &TaskStatus) -> Result<(), Error> {
+        let hook = match hook {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+        let executor = self.task_hooks.as_ref()
+            .ok_or_else(|| anyhow!("Hooks of task {} are refused, the default executor isolates the tasks", task.id))?;
+        let hook_task = Task {
+            command: hook.clone(),
+            env: Some(task.env.clone().unwrap_or_default().union(hook_env(task, stage, status).into_iter().collect())),
+            stdin: None,
+            ..task.clone()
+        };
+        match executor.execute(&hook_task).context(format!("Error running {} hook {}", stage, hook))? {
+            TaskStatus::Success(_) => Ok(()),
+            TaskStatus::Error(output, code) => hook_result(hook, code, &output.to_string()),
+            status => Err(anyhow!("'{}' ended as {:?}", hook, status)),
+        }
+    }
+}
+
+fn hook_result(hook: &str, code: Option<i32>, stderr: &str) -> Result<(), Error> {
+    match (code, stderr.trim()) {
+        (Some(0), _) => Ok(()),
+        (code, stderr) => Err(anyhow!("'{}' exited with {}{}", hook,
+            code.map_or_else(|| "a signal".to_string(), |code| code.to_string()),
+            if stderr.is_empty() { String::new() } else { format!(" : {}", stderr) })),
+    }
+}
+
+// Metadata of the task given to the hooks
+fn hook_env(task: &Task, stage: HookStage, status: &TaskStatus) -> Vec<(String, String)> {
+    let (status, exit_code) = match status {
+        TaskStatus::Scheduled => ("SCHEDULED", None),
+        TaskStatus::Success(_) => ("SUCCESS", Some(0)),
+        TaskStatus::Error(_, exit_code) => ("ERROR", *exit_code),
+        TaskStatus::Cancelled => ("CANCELLED", None),
+    };
+    let mut env = vec![
+        ("TASC_TASK_ID".to_string(), task.id.clone()),
+        ("TASC_HOOK".to_string(), stage.to_string()),
+        ("TASC_STATUS".to_string(), status.to_string()),
+        ("TASC_COMMAND".to_string(), task.command.clone()),
+        ("TASC_LABELS".to_string(), task.labels.iter().map(|(key, value)| format!("{}={}", key, value)).sorted().join(",")),
+    ];
+    env.extend(task.name.clone().map(|name| ("TASC_TASK_NAME".to_string(), name)));
+    env.extend(task.working_dir.as_ref().map(|working_dir| ("TASC_WORKDIR".to_string(), working_dir.to_string_lossy().into_owned())));
+    env.extend(exit_code.map(|exit_code| ("TASC_EXIT_CODE".to_string(), exit_code.to_string())));
+    env
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::{LocalExecutionAdapter, OutputCapture, ShellExecutionAdapter};
+    use crate::secondary::adapter::execution::tests::task;
+    use domain::executor::model::model::{TaskHooks, TaskOutput};
+    use std::env;
+    use std::fs;
+
+    fn adapter(pre: Option<&str>, post: Option<&str>) -> ShellHookAdapter {
+        let local = LocalExecutionAdapter::new(None, None, OutputCapture::new(1024, None));
+        ShellHookAdapter::new(PathBuf::from("/bin/sh"), pre.map(str::to_string), post.map(str::to_string),
+                              Some(Box::new(ShellExecutionAdapter::new(local, PathBuf::from("/bin/sh")))))
+    }
+
+    fn hooked(pre: Option<&str>, post: Option<&str>) -> Task {
+        Task { hooks: TaskHooks { pre: pre.map(str::to_string), post: post.map(str::to_string) }, ..task("true") }
+    }
+
+    fn file(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("tasc-hooks-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_hooks_should_be_given_the_status_of_the_task() {
+        let (settings, own) = (file("env-settings"), file("env-task"));
+        let adapter = adapter(None, Some(&format!("echo $TASC_HOOK $TASC_STATUS $TASC_EXIT_CODE > {}", settings.display())));
+        let mut task = hooked(None, Some(&format!("echo $TASC_TASK_ID $TASC_STATUS $TASC_EXIT_CODE $GREETING > {}", own.display())));
+        task.env = Some(vec![("GREETING".to_string(), "hello".to_string())].into_iter().collect());
+        adapter.run(&task, HookStage::Post, &TaskStatus::Error(TaskOutput::default(), Some(3))).unwrap();
+        let written = (fs::read_to_string(&settings).unwrap(), fs::read_to_string(&own).unwrap());
+        fs::remove_file(&settings).unwrap();
+        fs::remove_file(&own).unwrap();
+        assert_eq!(written, ("post ERROR 3\n".to_string(), "test ERROR 3 hello\n".to_string()));
+    }
+
+    #[test]
+    fn test_pre_hooks_should_stop_at_the_first_failure() {
+        let marker = file("pre");
+        let adapter = adapter(Some("echo unmounted >&2; exit 3"), None);
+        let error = adapter.run(&hooked(Some(&format!("touch {}", marker.display())), None), HookStage::Pre, &TaskStatus::Scheduled).unwrap_err();
+        assert_eq!(format!("{:#}", error), "'echo unmounted >&2; exit 3' exited with 3 : unmounted");
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn test_settings_hooks_should_only_keep_a_bounded_part_of_stderr() {
+        let adapter = adapter(Some("head -c 10000000 /dev/zero; head -c 100000 /dev/zero | tr '\\0' x >&2; exit 4"), None);
+        let error = format!("{:#}", adapter.run(&task("true"), HookStage::Pre, &TaskStatus::Scheduled).unwrap_err());
+        assert!(error.contains("exited with 4 : xxx") && error.contains("bytes truncated"), "{}", &error[..200.min(error.len())]);
+        assert!(error.len() < HOOK_STDERR_LIMIT as usize + 256, "{} bytes of stderr kept", error.len());
+    }
+
+    #[test]
+    fn test_post_hooks_should_all_run_and_report_every_failure() {
+        let marker = file("post");
+        let adapter = adapter(None, Some(&format!("touch {}; exit 2", marker.display())));
+        let error = adapter.run(&hooked(None, Some("exit 1")), HookStage::Post, &TaskStatus::Success(TaskOutput::default())).unwrap_err();
+        let ran = marker.exists();
+        let _ = fs::remove_file(&marker);
+        assert!(ran);
+        assert_eq!(format!("{:#}", error), format!("'exit 1' exited with 1, 'touch {}; exit 2' exited with 2", marker.display()));
+    }
+
+    #[test]
+    fn test_task_hooks_should_be_refused_without_executor() {
+        let adapter = ShellHookAdapter::new(PathBuf::from("/bin/sh"), None, None, None);
+        assert!(adapter.run(&task("true"), HookStage::Pre, &TaskStatus::Scheduled).is_ok());
+        assert_eq!(adapter.run(&hooked(Some("true"), None), HookStage::Pre, &TaskStatus::Scheduled).unwrap_err().to_string(),
+                   "Hooks of task test are refused, the default executor isolates the tasks");
+    }
+}
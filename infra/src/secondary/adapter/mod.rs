@@ -1,3 +1,6 @@
 pub mod storage;
 pub mod execution;
-pub mod id_generator;
\ No newline at end of file
+pub mod id_generator;
+pub mod artifacts;
+pub mod hooks;
+pub mod events;
\ No newline at end of file
@@ -1,31 +1,16 @@
-use super::schema::tasks;
+use super::model::{DbArtifact, DbCompletedTask, DbHookFailure, DbTask, DbTemplate, TaskStatusUpdate, SCHEDULED};
+use super::schema::{artifacts, hook_failures, tasks, templates};
 use diesel::{SqliteConnection, Connection, RunQueryDsl};
 use anyhow::{anyhow, Error, Context};
-use domain::executor::model::model::{Task, TaskId, TaskStatus};
-use std::convert::TryInto;
+use domain::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, LabelSelector, SearchQuery, Task, TaskId, TaskStatus, TaskTemplate};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
 use crate::diesel::*;
-use im::HashMap;
+use diesel::dsl::sql;
+use diesel::expression::SqlLiteral;
+use diesel::sql_types::{BigInt, Bool, Text};
 
-
-pub const SCHEDULED: &str = "SCHEDULED";
-pub const SUCCESS: &str = "SUCCESS";
-pub const ERROR: &str = "ERROR";
-
-#[derive(Queryable, Insertable)]
-#[table_name = "tasks"]
-struct DbTask {
-    id: String,
-    name: Option<String>,
-    command: String,
-    env: Option<String>,
-    status: String,
-    status_log: Option<String>,
-}
-
-pub fn establish_connection(database_url: &str) -> Result<SqliteConnection, Error> {
-    SqliteConnection::establish(database_url)
-        .context("Error connecting to database")
-}
+const DELETE_CHUNK_SIZE: usize = 500;
 
 pub fn create_task(conn: &SqliteConnection, new_task: &Task) -> Result<usize, Error> {
     let insertable_task: DbTask = (new_task, &TaskStatus::Scheduled).into();
@@ -35,6 +20,13 @@ pub fn create_task(conn: &SqliteConnection, new_task: &Task) -> Result<usize, Er
         .context(format!("Error inserting in db task {:?}", new_task))
 }
 
+// The write lock is taken at the start so the transaction waits for other writers instead of failing on upgrade
+pub fn create_tasks(conn: &SqliteConnection, new_tasks: &[Task]) -> Result<usize, Error> {
+    conn.immediate_transaction::<_, Error, _>(|| new_tasks.iter()
+        .try_fold(0, |inserted, new_task| create_task(conn, new_task).map(|count| inserted + count)))
+        .context("Error inserting tasks in db")
+}
+
 pub fn get_task(conn: &SqliteConnection, task_id: &TaskId) -> Result<TaskStatus, Error> {
     use super::schema::tasks::dsl::*;
     let (_, status_value) = match task_id {
@@ -43,6 +35,7 @@ pub fn get_task(conn: &SqliteConnection, task_id: &TaskId) -> Result<TaskStatus,
             .first::<DbTask>(conn)
             .context(format!("Error loading from database id {}", id_value))?.try_into(),
         TaskId::Name(name_value) => tasks.filter(name.nullable().eq(name_value))
+            .order(last_saved())
             .limit(1)
             .first::<DbTask>(conn)
             .context(format!("Error loading from database name {}", name_value))?.try_into()
@@ -50,75 +43,192 @@ pub fn get_task(conn: &SqliteConnection, task_id: &TaskId) -> Result<TaskStatus,
     return Ok(status_value);
 }
 
-#[derive(AsChangeset)]
-#[table_name = "tasks"]
-struct TaskStatusUpdate<'a> {
-    status: &'a str,
-    status_log: Option<&'a str>,
+// Labels are stored serialized so the selection is done after loading the tasks
+pub fn find_tasks(conn: &SqliteConnection, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+    use super::schema::tasks::dsl::*;
+    tasks.load::<DbTask>(conn)
+        .context("Error loading tasks from database")?
+        .into_iter()
+        .map(TryInto::try_into)
+        .filter(|result: &Result<(Task, TaskStatus), Error>| result.as_ref().map_or(true, |(task, _)| selector.matches(&task.labels)))
+        .collect()
+}
+
+// The words are quoted as a phrase of the FTS5 index, they hold only letters and digits
+pub fn search_tasks(conn: &SqliteConnection, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+    tasks::table.filter(sql::<Bool>("tasks.rowid IN (SELECT rowid FROM tasks_search WHERE tasks_search MATCH ")
+            .bind::<Text, _>(format!("\"{}\"", query.words.join(" ")))
+            .sql(")"))
+        .order(saved_order())
+        .load::<DbTask>(conn)
+        .context(format!("Error searching tasks in database for {}", query.words.join(" ")))?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
 }
 
-pub fn update_task(conn: &SqliteConnection, id_value: &str, status: &str, status_log: Option<&str>) -> Result<(), Error> {
+pub fn update_task(conn: &SqliteConnection, id_value: &str, status: &TaskStatus) -> Result<(), Error> {
     use super::schema::tasks::dsl as tasks_dsl;
     diesel::update(tasks_dsl::tasks.find(id_value))
-        .set(&TaskStatusUpdate { status, status_log })
+        .set(&TaskStatusUpdate::from(status))
         .execute(conn)
-        .map(|_| ())
         .context(format!("Error update in database for task id {}", id_value))
+        .and_then(|updated| match updated {
+            0 => Err(anyhow!("No task with id {}", id_value)),
+            _ => Ok(()),
+        })
 }
 
-impl From<(&Task, &TaskStatus)> for DbTask {
-    fn from(insertable_value: (&Task, &TaskStatus)) -> Self {
-        let (task, status) = insertable_value;
-        let (status, status_log) = match status {
-            TaskStatus::Scheduled => (SCHEDULED.to_string(), None),
-            TaskStatus::Success(ref stdout) => (SUCCESS.to_string(), Some(stdout.clone())),
-            TaskStatus::Error(ref stderr) => (ERROR.to_string(), Some(stderr.clone())),
-        };
-        DbTask {
-            id: task.id.clone(),
-            name: task.name.clone(),
-            command: task.command.clone(),
-            // KEY=VAL;KEY2=VAL2;KEY3=VAL
-            env: task.env.as_ref().map(|env_vars| env_vars.iter()
-                .fold(vec![], |mut acc, (key, value)| {
-                    acc.push(format!("{}:{}", key, value));
-                    acc
-                })
-                .join(";")),
-            status,
-            status_log,
+pub fn get_completed_tasks(conn: &SqliteConnection) -> Result<Vec<CompletedTask>, Error> {
+    tasks::table.select((tasks::id, tasks::name, tasks::status, tasks::completed_at))
+        .filter(tasks::status.ne(SCHEDULED))
+        .load::<DbCompletedTask>(conn)
+        .context("Error loading completed tasks from database")?
+        .into_iter()
+        .map(CompletedTask::try_from)
+        .collect()
+}
+
+// The ids are bound by chunks to stay under the limit of parameters of a statement, the digests of the deleted
+// artifacts are returned unless another artifact has them
+pub fn delete_tasks(conn: &SqliteConnection, ids: &[String]) -> Result<Vec<String>, Error> {
+    conn.immediate_transaction::<_, Error, _>(|| {
+        let mut digests = BTreeSet::new();
+        for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+            digests.extend(artifacts::table.select(artifacts::digest).filter(artifacts::task_id.eq_any(chunk)).load::<String>(conn)?);
+            diesel::delete(artifacts::table.filter(artifacts::task_id.eq_any(chunk))).execute(conn)?;
+            diesel::delete(hook_failures::table.filter(hook_failures::task_id.eq_any(chunk))).execute(conn)?;
+            diesel::delete(tasks::table.filter(tasks::id.eq_any(chunk))).execute(conn)?;
         }
-    }
+        let digests: Vec<String> = digests.into_iter().collect();
+        let mut referenced = HashSet::new();
+        for chunk in digests.chunks(DELETE_CHUNK_SIZE) {
+            referenced.extend(artifacts::table.select(artifacts::digest).filter(artifacts::digest.eq_any(chunk)).load::<String>(conn)?);
+        }
+        Ok(digests.into_iter().filter(|digest| !referenced.contains(digest)).collect())
+    }).context("Error deleting tasks from database")
+}
+
+// Page of the tasks in the order they were saved, read with their artifacts and hook failures in one transaction
+pub fn export_tasks(conn: &SqliteConnection, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error> {
+    conn.transaction::<_, Error, _>(|| {
+        let mut exported = tasks::table.order(saved_order())
+            .offset(offset as i64)
+            .limit(limit as i64)
+            .load::<DbTask>(conn)?
+            .into_iter()
+            .map(ExportedTask::try_from)
+            .collect::<Result<Vec<ExportedTask>, Error>>()?;
+        let ids: Vec<String> = exported.iter().map(|exported| exported.task.id.clone()).collect();
+        let mut task_artifacts: HashMap<String, Vec<Artifact>> = HashMap::new();
+        for artifact in artifacts::table.filter(artifacts::task_id.eq_any(&ids)).order(artifacts::path).load::<DbArtifact>(conn)? {
+            task_artifacts.entry(artifact.task_id.clone()).or_default().push(Artifact::from(artifact));
+        }
+        let mut task_hook_failures: HashMap<String, Vec<HookFailure>> = HashMap::new();
+        for failure in hook_failures::table.filter(hook_failures::task_id.eq_any(&ids)).order(hook_failures::stage.desc()).load::<DbHookFailure>(conn)? {
+            task_hook_failures.entry(failure.task_id.clone()).or_default().push(HookFailure::try_from(failure)?);
+        }
+        for exported in exported.iter_mut() {
+            exported.artifacts = task_artifacts.remove(&exported.task.id).unwrap_or_default();
+            exported.hook_failures = task_hook_failures.remove(&exported.task.id).unwrap_or_default();
+        }
+        Ok(exported)
+    }).context("Error exporting tasks from database")
 }
 
-impl TryInto<(Task, TaskStatus)> for DbTask {
-    type Error = Error;
-
-    fn try_into(self) -> Result<(Task, TaskStatus), Self::Error> {
-        let status = match self.status.as_str() {
-            SCHEDULED => Ok(TaskStatus::Scheduled),
-            SUCCESS => self.status_log.ok_or_else(|| anyhow!("Task {} is defined in database as SUCCES but doesn't have any status_log"))
-                .map(|stdout| TaskStatus::Success(stdout)),
-            ERROR => self.status_log.ok_or_else(|| anyhow!("Task {} is defined in database as ERROR but doesn't have any status_log"))
-                .map(|stdout| TaskStatus::Error(stdout)),
-            _ => Err(anyhow!("{} is not a valid status", self.status))
-        }?;
-        let task = Task {
-            id: self.id,
-            name: self.name,
-            command: self.command,
-            env: self.env.map(parse_env_var),
-        };
-        Ok((task, status))
+// Tasks whose id is stored are ignored by the insertion, their artifacts and hook failures with them
+pub fn import_tasks(conn: &SqliteConnection, imported_tasks: &[ExportedTask]) -> Result<usize, Error> {
+    conn.immediate_transaction::<_, Error, _>(|| imported_tasks.iter()
+        .try_fold(0, |imported, exported| {
+            let inserted = diesel::insert_or_ignore_into(tasks::table)
+                .values(&DbTask::from(exported))
+                .execute(conn)?;
+            if inserted == 0 {
+                return Ok(imported);
+            }
+            if !exported.artifacts.is_empty() {
+                create_artifacts(conn, &exported.task, &exported.artifacts)?;
+            }
+            exported.hook_failures.iter().try_for_each(|failure| create_hook_failure(conn, &exported.task, failure).map(|_| ()))?;
+            Ok(imported + inserted)
+        }))
+        .context("Error importing tasks in database")
+}
+
+pub fn create_artifacts(conn: &SqliteConnection, task: &Task, new_artifacts: &[Artifact]) -> Result<usize, Error> {
+    let insertable_artifacts: Vec<DbArtifact> = new_artifacts.iter()
+        .map(|artifact| (task, artifact).into())
+        .collect();
+    diesel::replace_into(artifacts::table)
+        .values(&insertable_artifacts)
+        .execute(conn)
+        .context(format!("Error inserting in db artifacts of task {}", task.id))
+}
+
+// Id of the task, looked up when it is given by name
+pub fn resolve_id(conn: &SqliteConnection, task_id: &TaskId) -> Result<String, Error> {
+    match task_id {
+        TaskId::Id(id_value) => Ok(id_value.clone()),
+        TaskId::Name(name_value) => tasks::table.select(tasks::id)
+            .filter(tasks::name.nullable().eq(name_value))
+            .order(last_saved())
+            .first::<String>(conn)
+            .context(format!("Error loading from database name {}", name_value)),
     }
 }
 
-fn parse_env_var(source: String) -> HashMap<String,String> {
-    From::from(source.split(";").map(|key_val| {
-        let splited: Vec<&str> = key_val.splitn(2, "=").collect();
-        match splited.as_slice() {
-            [key, value] => (String::from(*key), String::from(*value)),
-            _ => (String::from(""), String::from(""))
-        }
-    }).collect::<Vec<(String, String)>>())
+pub fn get_artifacts(conn: &SqliteConnection, task_id: &TaskId) -> Result<Vec<Artifact>, Error> {
+    conn.transaction::<_, Error, _>(|| {
+        let id_value = resolve_id(conn, task_id)?;
+        artifacts::table.filter(artifacts::task_id.eq(&id_value))
+            .order(artifacts::path)
+            .load::<DbArtifact>(conn)
+            .context(format!("Error loading from database artifacts of task {}", id_value))
+            .map(|artifacts| artifacts.into_iter().map(Artifact::from).collect())
+    })
+}
+
+pub fn create_hook_failure(conn: &SqliteConnection, task: &Task, failure: &HookFailure) -> Result<usize, Error> {
+    diesel::replace_into(hook_failures::table)
+        .values(&DbHookFailure::from((task, failure)))
+        .execute(conn)
+        .context(format!("Error inserting in db {} hook failure of task {}", failure.stage, task.id))
+}
+
+pub fn get_hook_failures(conn: &SqliteConnection, task_id: &TaskId) -> Result<Vec<HookFailure>, Error> {
+    conn.transaction::<_, Error, _>(|| {
+        let id_value = resolve_id(conn, task_id)?;
+        hook_failures::table.filter(hook_failures::task_id.eq(&id_value))
+            .order(hook_failures::stage.desc())
+            .load::<DbHookFailure>(conn)
+            .context(format!("Error loading from database hook failures of task {}", id_value))?
+            .into_iter()
+            .map(HookFailure::try_from)
+            .collect()
+    })
+}
+
+pub fn create_template(conn: &SqliteConnection, new_template: &TaskTemplate) -> Result<usize, Error> {
+    diesel::replace_into(templates::table)
+        .values(&DbTemplate::from(new_template))
+        .execute(conn)
+        .context(format!("Error inserting in db template {}", new_template.name))
+}
+
+pub fn get_template(conn: &SqliteConnection, template_name: &str) -> Result<TaskTemplate, Error> {
+    use super::schema::templates::dsl::*;
+    templates.find(template_name)
+        .first::<DbTemplate>(conn)
+        .map(TaskTemplate::from)
+        .context(format!("Error loading from database template {}", template_name))
+}
+
+// The rowid grows with the insertions
+fn saved_order() -> SqlLiteral<BigInt> {
+    sql::<BigInt>("tasks.rowid")
+}
+
+// A name resolves to the last task saved with it
+fn last_saved() -> diesel::expression::operators::Desc<SqlLiteral<BigInt>> {
+    saved_order().desc()
 }
\ No newline at end of file
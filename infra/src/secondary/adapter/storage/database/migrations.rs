@@ -0,0 +1,245 @@
+use anyhow::{anyhow, Context, Error};
+use diesel::connection::SimpleConnection;
+use diesel_migrations::{Migration, MigrationConnection, RunMigrationsError};
+use std::io::{self, Write};
+use diesel::Connection;
+#[cfg(feature = "postgres")]
+use diesel::PgConnection;
+#[cfg(feature = "sqlite")]
+use diesel::SqliteConnection;
+#[cfg(feature = "sqlite")]
+use std::time::Duration;
+
+use crate::secondary::adapter::storage::SchemaAdapter;
+
+// Migration directory embedded with its down.sql, the migrations of embed_migrations! can't be reverted
+pub struct SchemaMigration {
+    name: &'static str,
+    up: &'static str,
+    down: &'static str,
+}
+
+macro_rules! schema_migrations {
+    ($directory:literal, [$($name:literal),* $(,)?]) => {
+        &[$(SchemaMigration {
+            name: $name,
+            up: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../", $directory, "/", $name, "/up.sql")),
+            down: include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../", $directory, "/", $name, "/down.sql")),
+        }),*]
+    };
+}
+
+#[cfg(feature = "sqlite")]
+pub const SQLITE_MIGRATIONS: &[SchemaMigration] = schema_migrations!("migrations", [
+    "20200329_init_db",
+    "20261019_task_exit_code",
+    "20261020_task_timeout",
+    "20261021_task_templates",
+    "20261022_task_labels",
+    "20261023_task_process_settings",
+    "20261024_task_resource_limits",
+    "20261025_task_sandbox",
+    "20261026_task_credentials",
+    "20261027_task_container",
+    "20261028_task_host",
+    "20261029_task_wasm",
+    "20261030_task_executor",
+    "20261031_task_output",
+    "20261101_empty_task_output",
+    "20261102_task_artifacts",
+    "20261103_task_hooks",
+    "20261104_task_events",
+    "20261105_task_completed_at",
+    "20261106_task_search",
+    "20261107_task_event_message",
+    "20261108_task_event_output_size",
+]);
+
+// Migrations of an event database of its own, with the versions of the same migrations of the storage so a shared database runs them once
+#[cfg(feature = "sqlite")]
+pub const EVENTS_MIGRATIONS: &[SchemaMigration] = schema_migrations!("migrations_events", [
+    "20261104_task_events",
+    "20261107_task_event_message",
+    "20261108_task_event_output_size",
+]);
+
+#[cfg(feature = "postgres")]
+pub const POSTGRES_MIGRATIONS: &[SchemaMigration] = schema_migrations!("migrations_postgres", [
+    "20261104_init_db",
+    "20261105_task_saved_order",
+    "20261106_task_completed_at",
+    "20261107_task_stdin_bytes",
+    "20261108_task_events",
+]);
+
+// diesel records a migration by the date prefixing its directory
+impl Migration for SchemaMigration {
+    fn version(&self) -> &str {
+        self.name.split('_').next().unwrap_or(self.name)
+    }
+
+    fn run(&self, conn: &dyn SimpleConnection) -> Result<(), RunMigrationsError> {
+        conn.batch_execute(self.up).map_err(Into::into)
+    }
+
+    fn revert(&self, conn: &dyn SimpleConnection) -> Result<(), RunMigrationsError> {
+        conn.batch_execute(self.down).map_err(Into::into)
+    }
+}
+
+// Migrations of tasc with whether they are applied to the database
+pub fn status<C: MigrationConnection>(conn: &C, migrations: &'static [SchemaMigration]) -> Result<Vec<(&'static SchemaMigration, bool)>, Error> {
+    diesel_migrations::setup_database(conn)?;
+    let applied = conn.previously_run_migration_versions()?;
+    Ok(migrations.iter()
+        .map(|migration| (migration, applied.contains(migration.version())))
+        .collect())
+}
+
+fn pending<C: MigrationConnection>(conn: &C, migrations: &'static [SchemaMigration]) -> Result<Vec<&'static SchemaMigration>, Error> {
+    Ok(status(conn, migrations)?.into_iter()
+        .filter(|(_, applied)| !applied)
+        .map(|(migration, _)| migration)
+        .collect())
+}
+
+// Pending migrations applied in order, diesel writing the name of each one to the output
+pub fn migrate<C: MigrationConnection>(conn: &C, migrations: &'static [SchemaMigration], output: &mut dyn Write) -> Result<Vec<&'static SchemaMigration>, Error> {
+    let pending = pending(conn, migrations)?;
+    diesel_migrations::run_migrations(conn, pending.iter().map(|migration| *migration as &dyn Migration), output)?;
+    Ok(pending)
+}
+
+// The last applied migration is reverted, it must be one of this tasc
+pub fn rollback<C: MigrationConnection>(conn: &C, migrations: &'static [SchemaMigration]) -> Result<&'static SchemaMigration, Error> {
+    diesel_migrations::setup_database(conn)?;
+    let latest = conn.latest_run_migration_version()?
+        .ok_or_else(|| anyhow!("No migration applied"))?;
+    let migration = migrations.iter()
+        .find(|migration| migration.version() == latest)
+        .ok_or_else(|| anyhow!("Migration {} is unknown to this version of tasc", latest))?;
+    conn.transaction::<_, Error, _>(|| {
+        migration.revert(conn)?;
+        // The version comes from the embedded migrations, never from the input
+        conn.batch_execute(&format!("DELETE FROM __diesel_schema_migrations WHERE version = '{}'", migration.version()))?;
+        Ok(())
+    }).context(format!("Error reverting migration {}", migration.name))?;
+    Ok(migration)
+}
+
+// Adapters migrate only when allowed, otherwise a schema behind tasc is refused instead of being used
+pub fn prepare<C: MigrationConnection>(conn: &C, database: &str, migrations: &'static [SchemaMigration], auto_migrate: bool) -> Result<(), Error> {
+    if auto_migrate {
+        // The standard output is left to the commands
+        return migrate(conn, migrations, &mut io::stderr()).map(|_| ());
+    }
+    let pending = pending(conn, migrations)?;
+    match pending.last() {
+        None => Ok(()),
+        Some(latest) => Err(anyhow!("Schema of the {} database is behind by {} migrations up to {}, run tasc db migrate or set database.auto_migrate", database, pending.len(), latest.name)),
+    }
+}
+
+// Schema of a database migrated by tasc db
+pub struct DatabaseSchema<C> {
+    database: &'static str,
+    connection: C,
+    migrations: &'static [SchemaMigration],
+}
+
+#[cfg(feature = "sqlite")]
+impl DatabaseSchema<SqliteConnection> {
+    pub fn sqlite(database: &'static str, database_url: &str, busy_timeout: Duration, migrations: &'static [SchemaMigration]) -> Result<Self, Error> {
+        let connection = SqliteConnection::establish(database_url)
+            .context(format!("Error connecting to {} database", database))?;
+        connection.batch_execute(&format!("PRAGMA busy_timeout = {};", busy_timeout.as_millis()))?;
+        Ok(DatabaseSchema { database, connection, migrations })
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl DatabaseSchema<PgConnection> {
+    pub fn postgres(database: &'static str, database_url: &str) -> Result<Self, Error> {
+        let connection = PgConnection::establish(database_url)
+            .context(format!("Error connecting to {} database", database))?;
+        Ok(DatabaseSchema { database, connection, migrations: POSTGRES_MIGRATIONS })
+    }
+}
+
+impl<C: MigrationConnection> SchemaAdapter for DatabaseSchema<C> {
+    fn database(&self) -> &str {
+        self.database
+    }
+
+    fn status(&self) -> Result<Vec<(&'static str, bool)>, Error> {
+        status(&self.connection, self.migrations)
+            .map(|migrations| migrations.into_iter().map(|(migration, applied)| (migration.name, applied)).collect())
+    }
+
+    fn migrate(&self) -> Result<Vec<&'static str>, Error> {
+        migrate(&self.connection, self.migrations, &mut io::sink())
+            .map(|applied| applied.into_iter().map(|migration| migration.name).collect())
+    }
+
+    fn rollback(&self) -> Result<&'static str, Error> {
+        rollback(&self.connection, self.migrations).map(|migration| migration.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn directories(directory: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join(directory)).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_migrations_should_list_every_directory() {
+        assert_eq!(SQLITE_MIGRATIONS.iter().map(|migration| migration.name).collect::<Vec<&str>>(), directories("migrations"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_events_migrations_should_match_the_ones_of_the_storage() {
+        assert_eq!(EVENTS_MIGRATIONS.iter().map(|migration| migration.name).collect::<Vec<&str>>(), directories("migrations_events"));
+        for migration in EVENTS_MIGRATIONS {
+            let storage = SQLITE_MIGRATIONS.iter().find(|storage| storage.name == migration.name).unwrap();
+            assert_eq!((storage.up, storage.down), (migration.up, migration.down), "{}", migration.name);
+        }
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres_migrations_should_list_every_directory() {
+        assert_eq!(POSTGRES_MIGRATIONS.iter().map(|migration| migration.name).collect::<Vec<&str>>(), directories("migrations_postgres"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_prepare_should_refuse_schema_behind_unless_auto_migrate() {
+        let connection = SqliteConnection::establish(":memory:").unwrap();
+        let error = prepare(&connection, "storage", SQLITE_MIGRATIONS, false).unwrap_err();
+        assert!(error.to_string().contains("behind by 22 migrations up to 20261108_task_event_output_size"), "{}", error);
+        prepare(&connection, "storage", SQLITE_MIGRATIONS, true).unwrap();
+        prepare(&connection, "storage", SQLITE_MIGRATIONS, false).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_rollback_should_revert_last_migration() {
+        let connection = SqliteConnection::establish(":memory:").unwrap();
+        assert_eq!(migrate(&connection, SQLITE_MIGRATIONS, &mut io::sink()).unwrap().len(), SQLITE_MIGRATIONS.len());
+        assert_eq!(rollback(&connection, SQLITE_MIGRATIONS).unwrap().name, "20261108_task_event_output_size");
+        let pending: Vec<&str> = pending(&connection, SQLITE_MIGRATIONS).unwrap().into_iter().map(|migration| migration.name).collect();
+        assert_eq!(pending, vec!["20261108_task_event_output_size"]);
+        assert_eq!(migrate(&connection, SQLITE_MIGRATIONS, &mut io::sink()).unwrap().len(), 1);
+    }
+}
\ No newline at end of file
@@ -1,42 +1,10 @@
-use diesel::SqliteConnection;
-use anyhow::Error;
-use domain::executor::model::model::{Task, TaskId, TaskStatus};
-use domain::executor::ports::secondary::TaskStoragePort;
-
-mod schema;
+pub(super) mod schema;
+pub(super) mod model;
+pub mod migrations;
+#[cfg(feature = "sqlite")]
 mod commands;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
-embed_migrations!("../migrations");
-
-pub struct SqliteStorageAdapter {
-    connection: SqliteConnection
-}
-
-impl TaskStoragePort for SqliteStorageAdapter {
-    fn save(&mut self, task: Task) -> Result<Task, Error> {
-        commands::create_task(&self.connection, &task)
-            .map(|_| task)
-    }
-
-    fn status(&mut self, id: TaskId) -> Result<TaskStatus, Error> {
-        commands::get_task(&self.connection, &id)
-    }
-
-    fn complete(&mut self, task: &Task, status: TaskStatus) -> Result<(), Error> {
-        match status {
-            TaskStatus::Scheduled => commands::update_task(&self.connection, task.id.as_str(), commands::SCHEDULED, None),
-            TaskStatus::Success(stdout) => commands::update_task(&self.connection, task.id.as_str(), commands::SUCCESS, Some(stdout.as_str())),
-            TaskStatus::Error(stderr) => commands::update_task(&self.connection, task.id.as_str(), commands::ERROR, Some(stderr.as_str())),
-        }
-    }
-}
-
-impl SqliteStorageAdapter {
-    pub fn new(database_url: &str) -> Result<SqliteStorageAdapter, Error> {
-        let database_connection = commands::establish_connection(database_url)?;
-        embedded_migrations::run_with_output(&database_connection, &mut std::io::stdout())?;
-        Ok(SqliteStorageAdapter {
-            connection: database_connection
-        })
-    }
-}
\ No newline at end of file
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorageAdapter;
\ No newline at end of file
@@ -0,0 +1,407 @@
+use super::schema::{artifacts, hook_failures, tasks, templates};
+use anyhow::{anyhow, Error, Context};
+use domain::executor::model::model::{Artifact, CompletedTask, Container, Credentials, ExportedTask, HookFailure, Mount, ResourceLimits, Task, TaskHooks, TaskOutcome, TaskOutput, TaskStatus, TaskTemplate, WasmModule, WasmSource};
+use std::convert::{TryFrom, TryInto};
+use im::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Records of the tables, shared by the diesel backends
+
+pub const SCHEDULED: &str = "SCHEDULED";
+pub const SUCCESS: &str = "SUCCESS";
+pub const ERROR: &str = "ERROR";
+pub const CANCELLED: &str = "CANCELLED";
+
+#[derive(Queryable, Insertable)]
+#[table_name = "tasks"]
+pub struct DbTask {
+    id: String,
+    name: Option<String>,
+    command: String,
+    env: Option<String>,
+    status: String,
+    status_log: Option<Vec<u8>>,
+    exit_code: Option<i32>,
+    timeout: Option<i64>,
+    labels: Option<String>,
+    working_dir: Option<String>,
+    stdin: Option<Vec<u8>>,
+    umask: Option<i32>,
+    cpu_time_limit: Option<i64>,
+    memory_limit: Option<i64>,
+    open_files_limit: Option<i64>,
+    processes_limit: Option<i64>,
+    sandboxed: bool,
+    uid: Option<i32>,
+    gid: Option<i32>,
+    groups: Option<String>,
+    container_rootfs: Option<String>,
+    container_mounts: Option<String>,
+    host: Option<String>,
+    wasm_path: Option<String>,
+    wasm_blob: Option<Vec<u8>>,
+    wasm_dir: Option<String>,
+    wasm_fuel: Option<i64>,
+    executor: Option<String>,
+    output_truncated: Option<i64>,
+    output_log: Option<String>,
+    artifact_patterns: Option<String>,
+    pre_hook: Option<String>,
+    post_hook: Option<String>,
+    // Milliseconds since the epoch
+    completed_at: Option<i64>,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "templates"]
+pub struct DbTemplate {
+    name: String,
+    command: String,
+    env: Option<String>,
+    timeout: Option<i64>,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "artifacts"]
+pub struct DbArtifact {
+    pub task_id: String,
+    path: String,
+    digest: String,
+    size: i64,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "hook_failures"]
+pub struct DbHookFailure {
+    pub task_id: String,
+    stage: String,
+    message: String,
+}
+
+#[derive(AsChangeset)]
+#[table_name = "tasks"]
+pub struct TaskStatusUpdate<'a> {
+    status: &'a str,
+    status_log: Option<&'a [u8]>,
+    exit_code: Option<i32>,
+    output_truncated: Option<i64>,
+    output_log: Option<String>,
+    completed_at: Option<i64>,
+}
+
+// Columns of the completed tasks listed for the retention policy
+#[derive(Queryable)]
+pub struct DbCompletedTask {
+    id: String,
+    name: Option<String>,
+    status: String,
+    completed_at: Option<i64>,
+}
+
+impl<'a> From<&'a TaskStatus> for TaskStatusUpdate<'a> {
+    fn from(status: &'a TaskStatus) -> Self {
+        let (status, output, exit_code) = match status {
+            TaskStatus::Scheduled => (SCHEDULED, None, None),
+            TaskStatus::Success(ref stdout) => (SUCCESS, Some(stdout), Some(0)),
+            TaskStatus::Error(ref stderr, code) => (ERROR, Some(stderr), *code),
+            TaskStatus::Cancelled => (CANCELLED, None, None),
+        };
+        TaskStatusUpdate {
+            status,
+            status_log: output.map(|output| output.content.as_slice()).filter(|content| !content.is_empty()),
+            exit_code,
+            output_truncated: output.map(|output| output.truncated as i64),
+            output_log: output.and_then(|output| output.log.as_ref()).map(|log| log.to_string_lossy().into_owned()),
+            // The update is the completion of the task
+            completed_at: if status == SCHEDULED { None } else { Some(epoch_millis(SystemTime::now())) },
+        }
+    }
+}
+
+impl TryFrom<DbCompletedTask> for CompletedTask {
+    type Error = Error;
+
+    fn try_from(task: DbCompletedTask) -> Result<Self, Self::Error> {
+        let outcome = match task.status.as_str() {
+            SUCCESS => Ok(TaskOutcome::Success),
+            ERROR => Ok(TaskOutcome::Error),
+            CANCELLED => Ok(TaskOutcome::Cancelled),
+            _ => Err(anyhow!("{} is not a valid status of a completed task", task.status))
+        }?;
+        Ok(CompletedTask {
+            id: task.id,
+            name: task.name,
+            outcome,
+            completed_at: task.completed_at.map(from_epoch_millis),
+        })
+    }
+}
+
+fn epoch_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+fn from_epoch_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis as u64)
+}
+
+impl From<(&Task, &Artifact)> for DbArtifact {
+    fn from((task, artifact): (&Task, &Artifact)) -> Self {
+        DbArtifact {
+            task_id: task.id.clone(),
+            path: artifact.path.to_string_lossy().into_owned(),
+            digest: artifact.digest.clone(),
+            size: artifact.size as i64,
+        }
+    }
+}
+
+impl From<DbArtifact> for Artifact {
+    fn from(artifact: DbArtifact) -> Self {
+        Artifact { path: PathBuf::from(artifact.path), digest: artifact.digest, size: artifact.size as u64 }
+    }
+}
+
+impl From<(&Task, &HookFailure)> for DbHookFailure {
+    fn from((task, failure): (&Task, &HookFailure)) -> Self {
+        DbHookFailure { task_id: task.id.clone(), stage: failure.stage.to_string(), message: failure.message.clone() }
+    }
+}
+
+impl TryFrom<DbHookFailure> for HookFailure {
+    type Error = Error;
+
+    fn try_from(failure: DbHookFailure) -> Result<Self, Self::Error> {
+        failure.stage.parse()
+            .map(|stage| HookFailure { stage, message: failure.message })
+            .map_err(Error::from)
+    }
+}
+
+impl From<(&Task, &TaskStatus)> for DbTask {
+    fn from(insertable_value: (&Task, &TaskStatus)) -> Self {
+        let (task, status) = insertable_value;
+        let (status, output, exit_code) = match status {
+            TaskStatus::Scheduled => (SCHEDULED.to_string(), None, None),
+            TaskStatus::Success(ref stdout) => (SUCCESS.to_string(), Some(stdout), Some(0)),
+            TaskStatus::Error(ref stderr, code) => (ERROR.to_string(), Some(stderr), *code),
+            TaskStatus::Cancelled => (CANCELLED.to_string(), None, None),
+        };
+        DbTask {
+            id: task.id.clone(),
+            name: task.name.clone(),
+            command: task.command.clone(),
+            env: task.env.as_ref().map(format_env_var),
+            status,
+            status_log: output.map(|output| output.content.clone()).filter(|content| !content.is_empty()),
+            exit_code,
+            timeout: task.timeout.map(|timeout| timeout.as_secs() as i64),
+            labels: if task.labels.is_empty() { None } else { Some(format_labels(&task.labels)) },
+            working_dir: task.working_dir.as_ref().map(|working_dir| working_dir.to_string_lossy().into_owned()),
+            // An empty payload is stored as NULL like the output, the command then reads nothing either way
+            stdin: task.stdin.clone().filter(|stdin| !stdin.is_empty()),
+            umask: task.umask.map(|umask| umask as i32),
+            cpu_time_limit: task.limits.cpu_time.map(|cpu_time| cpu_time.as_secs() as i64),
+            memory_limit: task.limits.memory.map(|memory| memory as i64),
+            open_files_limit: task.limits.open_files.map(|open_files| open_files as i64),
+            processes_limit: task.limits.processes.map(|processes| processes as i64),
+            sandboxed: task.sandboxed,
+            uid: task.credentials.as_ref().map(|credentials| credentials.uid as i32),
+            gid: task.credentials.as_ref().map(|credentials| credentials.gid as i32),
+            groups: task.credentials.as_ref().map(|credentials| format_groups(&credentials.groups)),
+            container_rootfs: task.container.as_ref().map(|container| container.rootfs.to_string_lossy().into_owned()),
+            container_mounts: task.container.as_ref().map(|container| format_mounts(&container.mounts)),
+            host: task.host.clone(),
+            wasm_path: task.wasm.as_ref().and_then(|wasm| match wasm.source {
+                WasmSource::Path(ref path) => Some(path.to_string_lossy().into_owned()),
+                WasmSource::Blob(_) => None,
+            }),
+            wasm_blob: task.wasm.as_ref().and_then(|wasm| match wasm.source {
+                WasmSource::Blob(ref blob) => Some(blob.clone()),
+                WasmSource::Path(_) => None,
+            }),
+            wasm_dir: task.wasm.as_ref().and_then(|wasm| wasm.dir.as_ref()).map(|dir| dir.to_string_lossy().into_owned()),
+            wasm_fuel: task.wasm.as_ref().and_then(|wasm| wasm.fuel).map(|fuel| fuel as i64),
+            executor: task.executor.clone(),
+            output_truncated: output.map(|output| output.truncated as i64),
+            output_log: output.and_then(|output| output.log.as_ref()).map(|log| log.to_string_lossy().into_owned()),
+            artifact_patterns: if task.artifacts.is_empty() { None } else { Some(task.artifacts.join("\n")) },
+            pre_hook: task.hooks.pre.clone(),
+            post_hook: task.hooks.post.clone(),
+            completed_at: None,
+        }
+    }
+}
+
+impl TryInto<(Task, TaskStatus)> for DbTask {
+    type Error = Error;
+
+    fn try_into(self) -> Result<(Task, TaskStatus), Self::Error> {
+        let exit_code = self.exit_code;
+        let container_mounts = self.container_mounts;
+        let (wasm_blob, wasm_dir, wasm_fuel) = (self.wasm_blob, self.wasm_dir, self.wasm_fuel);
+        let (truncated, log) = (self.output_truncated.unwrap_or_default() as u64, self.output_log.map(PathBuf::from));
+        let output = |content| TaskOutput { content, truncated, log: log.clone() };
+        let status = match self.status.as_str() {
+            SCHEDULED => Ok(TaskStatus::Scheduled),
+            // Empty output is stored as NULL, diesel can't read zero-length blobs
+            SUCCESS => Ok(TaskStatus::Success(output(self.status_log.unwrap_or_default()))),
+            ERROR => Ok(TaskStatus::Error(output(self.status_log.unwrap_or_default()), exit_code)),
+            CANCELLED => Ok(TaskStatus::Cancelled),
+            _ => Err(anyhow!("{} is not a valid status", self.status))
+        }?;
+        let task = Task {
+            id: self.id,
+            name: self.name,
+            command: self.command,
+            env: self.env.map(parse_env_var),
+            timeout: self.timeout.map(|timeout| Duration::from_secs(timeout as u64)),
+            labels: self.labels.map(parse_labels).transpose()?.unwrap_or_default(),
+            working_dir: self.working_dir.map(PathBuf::from),
+            stdin: self.stdin,
+            umask: self.umask.map(|umask| umask as u32),
+            limits: ResourceLimits {
+                cpu_time: self.cpu_time_limit.map(|cpu_time| Duration::from_secs(cpu_time as u64)),
+                memory: self.memory_limit.map(|memory| memory as u64),
+                open_files: self.open_files_limit.map(|open_files| open_files as u64),
+                processes: self.processes_limit.map(|processes| processes as u64),
+            },
+            sandboxed: self.sandboxed,
+            credentials: match (self.uid, self.gid) {
+                (Some(uid), Some(gid)) => Some(Credentials {
+                    uid: uid as u32,
+                    gid: gid as u32,
+                    groups: parse_groups(self.groups.as_deref().unwrap_or_default())?,
+                }),
+                _ => None,
+            },
+            container: self.container_rootfs.map(|rootfs| Container {
+                rootfs: PathBuf::from(rootfs),
+                mounts: parse_mounts(container_mounts.as_deref().unwrap_or_default()),
+            }),
+            host: self.host,
+            wasm: self.wasm_path.map(|path| WasmSource::Path(PathBuf::from(path)))
+                .or_else(|| wasm_blob.map(WasmSource::Blob))
+                .map(|source| WasmModule {
+                    source,
+                    dir: wasm_dir.map(PathBuf::from),
+                    fuel: wasm_fuel.map(|fuel| fuel as u64),
+                }),
+            executor: self.executor,
+            artifacts: self.artifact_patterns.map(|patterns| patterns.lines().map(String::from).collect()).unwrap_or_default(),
+            hooks: TaskHooks { pre: self.pre_hook, post: self.post_hook },
+        };
+        Ok((task, status))
+    }
+}
+
+// Row of an imported task, completed when it was exported
+impl From<&ExportedTask> for DbTask {
+    fn from(exported: &ExportedTask) -> Self {
+        DbTask {
+            completed_at: exported.completed_at.map(epoch_millis),
+            ..DbTask::from((&exported.task, &exported.status))
+        }
+    }
+}
+
+// The artifacts and hook failures of the task are loaded apart
+impl TryFrom<DbTask> for ExportedTask {
+    type Error = Error;
+
+    fn try_from(task: DbTask) -> Result<Self, Self::Error> {
+        let completed_at = task.completed_at.map(from_epoch_millis);
+        let (task, status) = task.try_into()?;
+        Ok(ExportedTask { task, status, completed_at, artifacts: vec![], hook_failures: vec![] })
+    }
+}
+
+impl From<&TaskTemplate> for DbTemplate {
+    fn from(template: &TaskTemplate) -> Self {
+        DbTemplate {
+            name: template.name.clone(),
+            command: template.command.clone(),
+            env: template.env.as_ref().map(format_env_var),
+            timeout: template.timeout.map(|timeout| timeout.as_secs() as i64),
+        }
+    }
+}
+
+impl From<DbTemplate> for TaskTemplate {
+    fn from(template: DbTemplate) -> Self {
+        TaskTemplate {
+            name: template.name,
+            command: template.command,
+            env: template.env.map(parse_env_var),
+            timeout: template.timeout.map(|timeout| Duration::from_secs(timeout as u64)),
+        }
+    }
+}
+
+// GID,GID2,GID3
+fn format_groups(groups: &[u32]) -> String {
+    groups.iter().map(u32::to_string).collect::<Vec<String>>().join(",")
+}
+
+fn parse_groups(source: &str) -> Result<Vec<u32>, Error> {
+    source.split(',')
+        .filter(|group| !group.is_empty())
+        .map(|group| group.parse::<u32>().context(format!("{} is not a valid group id", group)))
+        .collect()
+}
+
+// SOURCE:DESTINATION:ro;SOURCE2:DESTINATION2:rw
+fn format_mounts(mounts: &[Mount]) -> String {
+    mounts.iter()
+        .map(|mount| format!("{}:{}:{}", mount.source.display(), mount.destination.display(), if mount.read_only { "ro" } else { "rw" }))
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+fn parse_mounts(source: &str) -> Vec<Mount> {
+    source.split(';')
+        .filter_map(|mount| match mount.split(':').collect::<Vec<&str>>().as_slice() {
+            [source, destination, mode] => Some(Mount { source: PathBuf::from(source), destination: PathBuf::from(destination), read_only: *mode == "ro" }),
+            _ => None,
+        })
+        .collect()
+}
+
+// KEY=VAL;KEY2=VAL2;KEY3=VAL
+fn format_env_var(env_vars: &HashMap<String, String>) -> String {
+    env_vars.iter()
+        .fold(vec![], |mut acc, (key, value)| {
+            acc.push(format!("{}={}", key, value));
+            acc
+        })
+        .join(";")
+}
+
+// Labels are a JSON object so their values may contain any separator
+fn format_labels(labels: &HashMap<String, String>) -> String {
+    serde_json::Value::Object(labels.iter()
+        .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+        .collect())
+        .to_string()
+}
+
+// Labels saved before they were JSON are KEY=VAL;KEY2=VAL2
+fn parse_labels(source: String) -> Result<HashMap<String, String>, Error> {
+    if !source.starts_with('{') {
+        return Ok(parse_env_var(source));
+    }
+    serde_json::from_str::<std::collections::HashMap<String, String>>(&source)
+        .map(|labels| labels.into_iter().collect())
+        .context(format!("Invalid labels {}", source))
+}
+
+fn parse_env_var(source: String) -> HashMap<String,String> {
+    From::from(source.split(";").map(|key_val| {
+        let splited: Vec<&str> = key_val.splitn(2, "=").collect();
+        match splited.as_slice() {
+            [key, value] => (String::from(*key), String::from(*value)),
+            _ => (String::from(""), String::from(""))
+        }
+    }).collect::<Vec<(String, String)>>())
+}
\ No newline at end of file
@@ -1,3 +1,12 @@
+table! {
+    templates (name) {
+        name -> Text,
+        command -> Text,
+        env -> Nullable<Text>,
+        timeout -> Nullable<BigInt>,
+    }
+}
+
 table! {
     tasks (id) {
         id -> Text,
@@ -5,6 +14,51 @@ table! {
         command -> Text,
         env -> Nullable<Text>,
         status -> Text,
-        status_log -> Nullable<Text>,
+        status_log -> Nullable<Binary>,
+        exit_code -> Nullable<Integer>,
+        timeout -> Nullable<BigInt>,
+        labels -> Nullable<Text>,
+        working_dir -> Nullable<Text>,
+        stdin -> Nullable<Binary>,
+        umask -> Nullable<Integer>,
+        cpu_time_limit -> Nullable<BigInt>,
+        memory_limit -> Nullable<BigInt>,
+        open_files_limit -> Nullable<BigInt>,
+        processes_limit -> Nullable<BigInt>,
+        sandboxed -> Bool,
+        uid -> Nullable<Integer>,
+        gid -> Nullable<Integer>,
+        groups -> Nullable<Text>,
+        container_rootfs -> Nullable<Text>,
+        container_mounts -> Nullable<Text>,
+        host -> Nullable<Text>,
+        wasm_path -> Nullable<Text>,
+        wasm_blob -> Nullable<Binary>,
+        wasm_dir -> Nullable<Text>,
+        wasm_fuel -> Nullable<BigInt>,
+        executor -> Nullable<Text>,
+        output_truncated -> Nullable<BigInt>,
+        output_log -> Nullable<Text>,
+        artifact_patterns -> Nullable<Text>,
+        pre_hook -> Nullable<Text>,
+        post_hook -> Nullable<Text>,
+        completed_at -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    artifacts (task_id, path) {
+        task_id -> Text,
+        path -> Text,
+        digest -> Text,
+        size -> BigInt,
+    }
+}
+
+table! {
+    hook_failures (task_id, stage) {
+        task_id -> Text,
+        stage -> Text,
+        message -> Text,
     }
 }
\ No newline at end of file
@@ -0,0 +1,184 @@
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection, Pool, PooledConnection};
+use diesel::SqliteConnection;
+use anyhow::{Error, Context};
+use std::time::Duration;
+use domain::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, LabelSelector, SearchQuery, Task, TaskId, TaskStatus, TaskTemplate};
+use domain::executor::ports::secondary::TaskStoragePort;
+
+use super::commands;
+use super::migrations::{self, SQLITE_MIGRATIONS};
+
+// Connections are pooled so a database file can be shared by several tasc processes
+pub struct SqliteStorageAdapter {
+    pool: Pool<ConnectionManager<SqliteConnection>>,
+}
+
+// Settings applied to every connection opened by the pool
+#[derive(Debug)]
+struct SqliteConnectionCustomizer {
+    busy_timeout: Duration,
+}
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for SqliteConnectionCustomizer {
+    // WAL lets readers run beside the writer, the busy timeout makes writers wait for each other instead of failing
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        conn.batch_execute(&format!("PRAGMA busy_timeout = {}; PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;", self.busy_timeout.as_millis()))
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+impl TaskStoragePort for SqliteStorageAdapter {
+    fn save(&mut self, task: Task) -> Result<Task, Error> {
+        commands::create_task(&*self.connection()?, &task)
+            .map(|_| task)
+    }
+
+    fn save_all(&mut self, tasks: Vec<Task>) -> Result<Vec<Task>, Error> {
+        commands::create_tasks(&*self.connection()?, &tasks)
+            .map(|_| tasks)
+    }
+
+    fn status(&mut self, id: TaskId) -> Result<TaskStatus, Error> {
+        commands::get_task(&*self.connection()?, &id)
+    }
+
+    fn resolve(&mut self, id: TaskId) -> Result<String, Error> {
+        commands::resolve_id(&*self.connection()?, &id)
+    }
+
+    fn complete(&mut self, task: &Task, status: TaskStatus) -> Result<(), Error> {
+        commands::update_task(&*self.connection()?, task.id.as_str(), &status)
+    }
+
+    fn find(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        commands::find_tasks(&*self.connection()?, selector)
+    }
+
+    fn search(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        commands::search_tasks(&*self.connection()?, query)
+    }
+
+    fn save_template(&mut self, template: TaskTemplate) -> Result<(), Error> {
+        commands::create_template(&*self.connection()?, &template)
+            .map(|_| ())
+    }
+
+    fn template(&mut self, name: &str) -> Result<TaskTemplate, Error> {
+        commands::get_template(&*self.connection()?, name)
+    }
+
+    fn save_artifacts(&mut self, task: &Task, artifacts: Vec<Artifact>) -> Result<(), Error> {
+        commands::create_artifacts(&*self.connection()?, task, &artifacts)
+            .map(|_| ())
+    }
+
+    fn artifacts(&mut self, id: TaskId) -> Result<Vec<Artifact>, Error> {
+        commands::get_artifacts(&*self.connection()?, &id)
+    }
+
+    fn save_hook_failure(&mut self, task: &Task, failure: HookFailure) -> Result<(), Error> {
+        commands::create_hook_failure(&*self.connection()?, task, &failure)
+            .map(|_| ())
+    }
+
+    fn hook_failures(&mut self, id: TaskId) -> Result<Vec<HookFailure>, Error> {
+        commands::get_hook_failures(&*self.connection()?, &id)
+    }
+
+    fn completed(&mut self) -> Result<Vec<CompletedTask>, Error> {
+        commands::get_completed_tasks(&*self.connection()?)
+    }
+
+    fn delete(&mut self, ids: &[String]) -> Result<Vec<String>, Error> {
+        commands::delete_tasks(&*self.connection()?, ids)
+    }
+
+    fn export(&mut self, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error> {
+        commands::export_tasks(&*self.connection()?, offset, limit)
+    }
+
+    fn import(&mut self, tasks: Vec<ExportedTask>) -> Result<usize, Error> {
+        commands::import_tasks(&*self.connection()?, &tasks)
+    }
+}
+
+impl SqliteStorageAdapter {
+    pub fn new(database_url: &str, pool_size: u32, busy_timeout: Duration, auto_migrate: bool) -> Result<SqliteStorageAdapter, Error> {
+        let pool = Pool::builder()
+            .max_size(pool_size)
+            // A CLI invocation only needs one, the others are opened on demand
+            .min_idle(Some(1))
+            .connection_customizer(Box::new(SqliteConnectionCustomizer { busy_timeout }))
+            .build(ConnectionManager::<SqliteConnection>::new(database_url))
+            .context("Error connecting to database")?;
+        // Processes started together on a new database would otherwise all run the migrations
+        let connection = pool.get().context("Error getting a database connection")?;
+        connection.immediate_transaction(|| migrations::prepare(&*connection, "storage", SQLITE_MIGRATIONS, auto_migrate))?;
+        Ok(SqliteStorageAdapter { pool })
+    }
+
+    fn connection(&self) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, Error> {
+        self.pool.get().context("Error getting a database connection")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::task;
+    use domain::executor::model::model::TaskOutput;
+    use domain::executor::testkit::{concurrent_handles_should_not_lose_updates, storage_conformance};
+    use std::{env, fs, thread};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_sqlite_storage_conformance() {
+        let directory = env::temp_dir().join(format!("tasc-sqlite-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        storage_conformance(|| {
+            let database = directory.join(format!("{}.db", Uuid::new_v4()));
+            SqliteStorageAdapter::new(database.to_str().unwrap(), 4, Duration::from_secs(5), true).unwrap()
+        });
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_handles_on_one_file_should_not_lose_updates() {
+        let database = env::temp_dir().join(format!("tasc-sqlite-{}.db", Uuid::new_v4()));
+        let url = database.to_str().unwrap().to_string();
+        SqliteStorageAdapter::new(&url, 1, Duration::from_secs(5), true).unwrap();
+        concurrent_handles_should_not_lose_updates(move || SqliteStorageAdapter::new(&url, 2, Duration::from_secs(5), false).unwrap());
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", database.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_sqlite_adapters_on_one_file_should_wait_for_each_other() {
+        let database = env::temp_dir().join(format!("tasc-sqlite-{}.db", Uuid::new_v4()));
+        let url = database.to_str().unwrap().to_string();
+        SqliteStorageAdapter::new(&url, 1, Duration::from_millis(250), true).unwrap();
+        // Each adapter stands for a tasc process, with its own pool and connections
+        let writers: Vec<thread::JoinHandle<Result<(), Error>>> = (0..2).map(|writer| {
+            let url = url.clone();
+            thread::spawn(move || {
+                let mut storage = SqliteStorageAdapter::new(&url, 2, Duration::from_millis(250), false)?;
+                for index in 0..100 {
+                    let saved = storage.save(Task { id: format!("{}-{}", writer, index), ..task("true") })?;
+                    storage.complete(&saved, TaskStatus::Success(TaskOutput::from("done")))?;
+                }
+                storage.save_all((0..50).map(|index| Task { id: format!("{}-batch-{}", writer, index), ..task("true") }).collect()).map(|_| ())
+            })
+        }).collect();
+        let results: Vec<Result<(), Error>> = writers.into_iter().map(|writer| writer.join().unwrap()).collect();
+        let completed = SqliteStorageAdapter::new(&url, 1, Duration::from_millis(250), false).unwrap().completed().unwrap().len();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", url, suffix));
+        }
+        for result in results {
+            result.unwrap();
+        }
+        assert_eq!(completed, 200);
+    }
+}
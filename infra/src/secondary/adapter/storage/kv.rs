@@ -0,0 +1,429 @@
+use anyhow::{anyhow, Error, Context};
+use redb::{Database, MultimapTableDefinition, ReadTransaction, ReadableTable, TableDefinition, WriteTransaction};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::SystemTime;
+
+use domain::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, LabelSelector, SearchQuery, Task, TaskId, TaskStatus, TaskTemplate, WasmModule, WasmSource};
+use domain::executor::ports::secondary::TaskStoragePort;
+
+use crate::secondary::adapter::storage::record::{ArtifactRecord, HookFailureRecord, TaskRecord, TemplateRecord, WasmRecord, CANCELLED, ERROR, SCHEDULED, SUCCESS};
+
+// Records are JSON values keyed by the id of their task, the outputs and WASI modules are kept as raw bytes beside them
+const TASKS: TableDefinition<&str, &str> = TableDefinition::new("tasks");
+const OUTPUTS: TableDefinition<&str, &[u8]> = TableDefinition::new("outputs");
+const WASM_MODULES: TableDefinition<&str, &[u8]> = TableDefinition::new("wasm_modules");
+const TEMPLATES: TableDefinition<&str, &str> = TableDefinition::new("templates");
+const ARTIFACTS: TableDefinition<&str, &str> = TableDefinition::new("artifacts");
+const HOOK_FAILURES: TableDefinition<&str, &str> = TableDefinition::new("hook_failures");
+// Secondary indexes, id of the last task saved with a name and ids of the tasks by status, updated in the transaction writing the task
+const TASK_BY_NAME: TableDefinition<&str, &str> = TableDefinition::new("task_by_name");
+const TASKS_BY_STATUS: MultimapTableDefinition<&str, &str> = MultimapTableDefinition::new("tasks_by_status");
+// Ids of the tasks in the order they were saved, a name goes back to the last task left with it once its task is deleted
+const TASK_ORDER: TableDefinition<u64, &str> = TableDefinition::new("task_order");
+
+// Stores opened by the process, redb refuses to open a file twice so the event store shares the one of the storage
+static DATABASES: Mutex<Vec<(PathBuf, Weak<Database>)>> = Mutex::new(Vec::new());
+
+// Embedded store in a single file, it is locked by the tasc process which opens it
+pub struct KvStorageAdapter {
+    database: Arc<Database>,
+}
+
+impl TaskStoragePort for KvStorageAdapter {
+    fn save(&mut self, task: Task) -> Result<Task, Error> {
+        self.write(|txn| insert_task(txn, &task))
+            .context(format!("Error inserting in kv store task {:?}", task))?;
+        Ok(task)
+    }
+
+    fn save_all(&mut self, tasks: Vec<Task>) -> Result<Vec<Task>, Error> {
+        self.write(|txn| tasks.iter().try_for_each(|task| insert_task(txn, task)))
+            .context("Error inserting tasks in kv store")?;
+        Ok(tasks)
+    }
+
+    fn status(&mut self, id: TaskId) -> Result<TaskStatus, Error> {
+        self.read(|txn| {
+            let id_value = resolve_id(txn, &id)?;
+            get_record::<TaskRecord>(&txn.open_table(TASKS)?, &id_value)?
+                .ok_or_else(|| anyhow!("No task with id {}", id_value))
+                .and_then(|record| load_task(txn, record))
+        })
+            .map(|(_, status)| status)
+            .context(format!("Error loading from kv store task {:?}", id))
+    }
+
+    fn resolve(&mut self, id: TaskId) -> Result<String, Error> {
+        self.read(|txn| resolve_id(txn, &id))
+            .context(format!("Error loading from kv store task {:?}", id))
+    }
+
+    // Only the status is updated, as with the sql storages
+    fn complete(&mut self, task: &Task, status: TaskStatus) -> Result<(), Error> {
+        let completed_at = if status.is_terminal() { Some(SystemTime::now()) } else { None };
+        self.write(|txn| update_status(txn, task.id.as_str(), &status, completed_at))
+            .context(format!("Error update in kv store for task id {}", task.id))
+    }
+
+    // Labels are part of the records so the selection is done while reading them
+    fn find(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        self.read(|txn| txn.open_table(TASKS)?.iter()?
+            .map(|entry| entry.map_err(Error::from)
+                .and_then(|(_, record)| serde_json::from_str::<TaskRecord>(record.value()).map_err(Error::from)))
+            .filter(|result| result.as_ref().map_or(true, |record| selector.matches(&record.labels)))
+            .map(|result| result.and_then(|record| load_task(txn, record)))
+            .collect())
+            .context("Error loading tasks from kv store")
+    }
+
+    // Every task is loaded with its output and matched, in the order they were saved
+    fn search(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        self.read(|txn| {
+            let tasks = txn.open_table(TASKS)?;
+            txn.open_table(TASK_ORDER)?.iter()?
+                .map(|entry| {
+                    let (_, id) = entry?;
+                    let record = get_record::<TaskRecord>(&tasks, id.value())?
+                        .ok_or_else(|| anyhow!("No task with id {}", id.value()))?;
+                    load_task(txn, record)
+                })
+                .filter(|result| result.as_ref().map_or(true, |(task, status)| query.matches_task(task, status)))
+                .collect()
+        }).context(format!("Error searching tasks in kv store for {}", query.words.join(" ")))
+    }
+
+    fn save_template(&mut self, template: TaskTemplate) -> Result<(), Error> {
+        self.write(|txn| {
+            txn.open_table(TEMPLATES)?.insert(template.name.as_str(), serde_json::to_string(&TemplateRecord::from(&template))?.as_str())?;
+            Ok(())
+        }).context(format!("Error inserting in kv store template {}", template.name))
+    }
+
+    fn template(&mut self, name: &str) -> Result<TaskTemplate, Error> {
+        self.read(|txn| get_record::<TemplateRecord>(&txn.open_table(TEMPLATES)?, name))
+            .context(format!("Error loading from kv store template {}", name))?
+            .map(TaskTemplate::from)
+            .ok_or_else(|| anyhow!("No template named {}", name))
+    }
+
+    fn save_artifacts(&mut self, task: &Task, mut artifacts: Vec<Artifact>) -> Result<(), Error> {
+        artifacts.sort_by(|first, second| first.path.cmp(&second.path));
+        let records: Vec<ArtifactRecord> = artifacts.into_iter().map(ArtifactRecord::from).collect();
+        self.write(|txn| {
+            txn.open_table(ARTIFACTS)?.insert(task.id.as_str(), serde_json::to_string(&records)?.as_str())?;
+            Ok(())
+        }).context(format!("Error inserting in kv store artifacts of task {}", task.id))
+    }
+
+    fn artifacts(&mut self, id: TaskId) -> Result<Vec<Artifact>, Error> {
+        self.read(|txn| get_record::<Vec<ArtifactRecord>>(&txn.open_table(ARTIFACTS)?, &resolve_id(txn, &id)?))
+            .context(format!("Error loading from kv store artifacts of task {:?}", id))
+            .map(|records| records.unwrap_or_default().into_iter().map(Artifact::from).collect())
+    }
+
+    // A stage keeps its last failure
+    fn save_hook_failure(&mut self, task: &Task, failure: HookFailure) -> Result<(), Error> {
+        self.write(|txn| {
+            let mut table = txn.open_table(HOOK_FAILURES)?;
+            let mut records = get_record::<Vec<HookFailureRecord>>(&table, &task.id)?.unwrap_or_default();
+            records.retain(|record| record.stage != failure.stage.to_string());
+            records.push(HookFailureRecord::from(&failure));
+            // The pre hook failure comes first
+            records.sort_by(|first, second| second.stage.cmp(&first.stage));
+            table.insert(task.id.as_str(), serde_json::to_string(&records)?.as_str())?;
+            Ok(())
+        }).context(format!("Error inserting in kv store {} hook failure of task {}", failure.stage, task.id))
+    }
+
+    fn hook_failures(&mut self, id: TaskId) -> Result<Vec<HookFailure>, Error> {
+        self.read(|txn| get_record::<Vec<HookFailureRecord>>(&txn.open_table(HOOK_FAILURES)?, &resolve_id(txn, &id)?))
+            .context(format!("Error loading from kv store hook failures of task {:?}", id))?
+            .unwrap_or_default()
+            .into_iter()
+            .map(HookFailure::try_from)
+            .collect()
+    }
+
+    // Only the records of the tasks indexed under a terminal status are read, the scheduled ones are skipped
+    fn completed(&mut self) -> Result<Vec<CompletedTask>, Error> {
+        self.read(|txn| {
+            let tasks = txn.open_table(TASKS)?;
+            let by_status = txn.open_multimap_table(TASKS_BY_STATUS)?;
+            let mut completed = vec![];
+            for status in &[SUCCESS, ERROR, CANCELLED] {
+                for id in by_status.get(*status)? {
+                    let id = id?;
+                    completed.extend(get_record::<TaskRecord>(&tasks, id.value())?
+                        .ok_or_else(|| anyhow!("No task with id {}", id.value()))?
+                        .completed());
+                }
+            }
+            Ok(completed)
+        }).context("Error loading completed tasks from kv store")
+    }
+
+    // There is no index of the artifacts by digest, the remaining ones are all read to find the digests still referred to
+    fn delete(&mut self, ids: &[String]) -> Result<Vec<String>, Error> {
+        self.write(|txn| {
+            let mut tasks = txn.open_table(TASKS)?;
+            let mut by_status = txn.open_multimap_table(TASKS_BY_STATUS)?;
+            let mut by_name = txn.open_table(TASK_BY_NAME)?;
+            // Names whose last task is deleted
+            let mut orphan_names = vec![];
+            for id in ids {
+                let record = match get_record::<TaskRecord>(&tasks, id)? {
+                    Some(record) => record,
+                    None => continue,
+                };
+                tasks.remove(id.as_str())?;
+                by_status.remove(record.status.as_str(), id.as_str())?;
+                if let Some(name) = record.name {
+                    if by_name.get(name.as_str())?.is_some_and(|last| last.value() == id) {
+                        by_name.remove(name.as_str())?;
+                        orphan_names.push(name);
+                    }
+                }
+            }
+            for table in &[OUTPUTS, WASM_MODULES] {
+                let mut table = txn.open_table(*table)?;
+                ids.iter().try_for_each(|id| table.remove(id.as_str()).map(|_| ()))?;
+            }
+            let mut digests = BTreeSet::new();
+            let mut artifacts = txn.open_table(ARTIFACTS)?;
+            for id in ids {
+                digests.extend(get_record::<Vec<ArtifactRecord>>(&artifacts, id)?.unwrap_or_default().into_iter().map(|record| Artifact::from(record).digest));
+                artifacts.remove(id.as_str())?;
+            }
+            for entry in artifacts.iter()? {
+                let (_, records) = entry?;
+                for record in serde_json::from_str::<Vec<ArtifactRecord>>(records.value())? {
+                    digests.remove(&Artifact::from(record).digest);
+                }
+            }
+            let mut hook_failures = txn.open_table(HOOK_FAILURES)?;
+            ids.iter().try_for_each(|id| hook_failures.remove(id.as_str()).map(|_| ()))?;
+            let mut order = txn.open_table(TASK_ORDER)?;
+            order.retain(|_, id| !ids.iter().any(|deleted| deleted == id))?;
+            for name in orphan_names {
+                for entry in order.iter()?.rev() {
+                    let (_, id) = entry?;
+                    if get_record::<TaskRecord>(&tasks, id.value())?.is_some_and(|record| record.name.as_deref() == Some(name.as_str())) {
+                        by_name.insert(name.as_str(), id.value())?;
+                        break;
+                    }
+                }
+            }
+            Ok(digests.into_iter().collect())
+        }).context("Error deleting tasks from kv store")
+    }
+
+    fn export(&mut self, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error> {
+        self.read(|txn| {
+            let tasks = txn.open_table(TASKS)?;
+            let artifacts = txn.open_table(ARTIFACTS)?;
+            let hook_failures = txn.open_table(HOOK_FAILURES)?;
+            txn.open_table(TASK_ORDER)?.iter()?
+                .skip(offset)
+                .take(limit)
+                .map(|entry| {
+                    let (_, id) = entry?;
+                    let id = id.value();
+                    let record = get_record::<TaskRecord>(&tasks, id)?
+                        .ok_or_else(|| anyhow!("No task with id {}", id))?;
+                    let completed_at = record.completed_at;
+                    let (task, status) = load_task(txn, record)?;
+                    Ok(ExportedTask {
+                        task,
+                        status,
+                        completed_at,
+                        artifacts: get_record::<Vec<ArtifactRecord>>(&artifacts, id)?.unwrap_or_default()
+                            .into_iter().map(Artifact::from).collect(),
+                        hook_failures: get_record::<Vec<HookFailureRecord>>(&hook_failures, id)?.unwrap_or_default()
+                            .into_iter().map(HookFailure::try_from).collect::<Result<Vec<HookFailure>, Error>>()?,
+                    })
+                })
+                .collect()
+        }).context("Error exporting tasks from kv store")
+    }
+
+    fn import(&mut self, tasks: Vec<ExportedTask>) -> Result<usize, Error> {
+        self.write(|txn| {
+            let mut imported = 0;
+            for exported in &tasks {
+                let id = exported.task.id.as_str();
+                if txn.open_table(TASKS)?.get(id)?.is_some() {
+                    continue;
+                }
+                insert_task(txn, &exported.task)?;
+                update_status(txn, id, &exported.status, exported.completed_at)?;
+                if !exported.artifacts.is_empty() {
+                    let records: Vec<ArtifactRecord> = exported.artifacts.iter().cloned().map(ArtifactRecord::from).collect();
+                    txn.open_table(ARTIFACTS)?.insert(id, serde_json::to_string(&records)?.as_str())?;
+                }
+                if !exported.hook_failures.is_empty() {
+                    let records: Vec<HookFailureRecord> = exported.hook_failures.iter().map(HookFailureRecord::from).collect();
+                    txn.open_table(HOOK_FAILURES)?.insert(id, serde_json::to_string(&records)?.as_str())?;
+                }
+                imported += 1;
+            }
+            Ok(imported)
+        }).context("Error importing tasks in kv store")
+    }
+}
+
+impl KvStorageAdapter {
+    pub fn new(path: &Path) -> Result<KvStorageAdapter, Error> {
+        let adapter = KvStorageAdapter { database: open_database(path)? };
+        // Tables are created on their first write, reading them before fails
+        adapter.write(|txn| {
+            txn.open_table(TASKS)?;
+            txn.open_table(OUTPUTS)?;
+            txn.open_table(WASM_MODULES)?;
+            txn.open_table(TEMPLATES)?;
+            txn.open_table(ARTIFACTS)?;
+            txn.open_table(HOOK_FAILURES)?;
+            txn.open_table(TASK_BY_NAME)?;
+            txn.open_multimap_table(TASKS_BY_STATUS)?;
+            txn.open_table(TASK_ORDER)?;
+            Ok(())
+        }).context("Error creating kv store tables")?;
+        Ok(adapter)
+    }
+
+    // The transaction is aborted when the operation fails
+    fn write<T>(&self, operation: impl FnOnce(&WriteTransaction) -> Result<T, Error>) -> Result<T, Error> {
+        let txn = self.database.begin_write()?;
+        let result = operation(&txn)?;
+        txn.commit()?;
+        Ok(result)
+    }
+
+    fn read<T>(&self, operation: impl FnOnce(&ReadTransaction) -> Result<T, Error>) -> Result<T, Error> {
+        operation(&self.database.begin_read()?)
+    }
+}
+
+// The store is opened once by the process, its handle is shared as long as one is kept
+pub(crate) fn open_database(path: &Path) -> Result<Arc<Database>, Error> {
+    let mut databases = DATABASES.lock().map_err(|_| anyhow!("Error opening kv store {}, its registry is poisoned", path.display()))?;
+    databases.retain(|(_, database)| database.strong_count() > 0);
+    if let Ok(canonical) = fs::canonicalize(path) {
+        if let Some(database) = databases.iter().find(|(opened, _)| *opened == canonical).and_then(|(_, database)| database.upgrade()) {
+            return Ok(database);
+        }
+    }
+    let database = Arc::new(Database::create(path)
+        .context(format!("Error opening kv store {}", path.display()))?);
+    databases.push((fs::canonicalize(path)?, Arc::downgrade(&database)));
+    Ok(database)
+}
+
+// A task reusing an id fails the transaction, the tasks saved with it are not kept
+fn insert_task(txn: &WriteTransaction, task: &Task) -> Result<(), Error> {
+    let id = task.id.as_str();
+    let mut tasks = txn.open_table(TASKS)?;
+    if tasks.get(id)?.is_some() {
+        return Err(anyhow!("A task with id {} already exists", id));
+    }
+    tasks.insert(id, serde_json::to_string(&TaskRecord::from(task))?.as_str())?;
+    let mut order = txn.open_table(TASK_ORDER)?;
+    let next = order.last()?.map_or(0, |(last, _)| last.value() + 1);
+    order.insert(next, id)?;
+    if let Some(ref name) = task.name {
+        txn.open_table(TASK_BY_NAME)?.insert(name.as_str(), id)?;
+    }
+    txn.open_multimap_table(TASKS_BY_STATUS)?.insert(SCHEDULED, id)?;
+    if let Some(WasmModule { source: WasmSource::Blob(ref blob), .. }) = task.wasm {
+        txn.open_table(WASM_MODULES)?.insert(id, blob.as_slice())?;
+    }
+    Ok(())
+}
+
+// Status of a saved task with its output, the completion time is only replaced by a new one
+fn update_status(txn: &WriteTransaction, id: &str, status: &TaskStatus, completed_at: Option<SystemTime>) -> Result<(), Error> {
+    let mut tasks = txn.open_table(TASKS)?;
+    let mut record = get_record::<TaskRecord>(&tasks, id)?
+        .ok_or_else(|| anyhow!("No task with id {}", id))?;
+    let mut by_status = txn.open_multimap_table(TASKS_BY_STATUS)?;
+    by_status.remove(record.status.as_str(), id)?;
+    let output = record.set_status(status);
+    if completed_at.is_some() {
+        record.completed_at = completed_at;
+    }
+    by_status.insert(record.status.as_str(), id)?;
+    tasks.insert(id, serde_json::to_string(&record)?.as_str())?;
+    let mut outputs = txn.open_table(OUTPUTS)?;
+    match output.filter(|output| !output.content.is_empty()) {
+        Some(output) => outputs.insert(id, output.content.as_slice()).map(|_| ()),
+        None => outputs.remove(id).map(|_| ()),
+    }?;
+    Ok(())
+}
+
+// Id of the task, looked up in the name index when it is given by name
+fn resolve_id(txn: &ReadTransaction, task_id: &TaskId) -> Result<String, Error> {
+    match task_id {
+        TaskId::Id(id_value) => Ok(id_value.clone()),
+        TaskId::Name(name_value) => txn.open_table(TASK_BY_NAME)?
+            .get(name_value.as_str())?
+            .map(|id_value| id_value.value().to_string())
+            .ok_or_else(|| anyhow!("No task named {}", name_value)),
+    }
+}
+
+fn get_record<R: DeserializeOwned>(table: &impl ReadableTable<&'static str, &'static str>, key: &str) -> Result<Option<R>, Error> {
+    table.get(key)?
+        .map(|record| serde_json::from_str(record.value()))
+        .transpose()
+        .map_err(Error::from)
+}
+
+// Record completed by the bytes stored beside it
+fn load_task(txn: &ReadTransaction, record: TaskRecord) -> Result<(Task, TaskStatus), Error> {
+    let output = txn.open_table(OUTPUTS)?.get(record.id.as_str())?
+        .map(|content| content.value().to_vec())
+        .unwrap_or_default();
+    let module = match record.wasm {
+        Some(WasmRecord { path: None, .. }) => txn.open_table(WASM_MODULES)?.get(record.id.as_str())?
+            .map(|module| module.value().to_vec()),
+        _ => None,
+    };
+    record.into_task(output, module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::task;
+    use domain::executor::model::model::TaskOutput;
+    use domain::executor::testkit::storage_conformance;
+    use std::{env, fs};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_kv_storage_conformance() {
+        let directory = env::temp_dir().join(format!("tasc-kv-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        storage_conformance(|| KvStorageAdapter::new(&directory.join(format!("{}.redb", Uuid::new_v4()))).unwrap());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_completed_should_only_read_the_indexed_tasks() {
+        let path = env::temp_dir().join(format!("tasc-kv-{}.redb", Uuid::new_v4()));
+        let mut storage = KvStorageAdapter::new(&path).unwrap();
+        let (done, scheduled) = (Task { id: "done".to_string(), ..task("true") }, Task { id: "scheduled".to_string(), ..task("true") });
+        storage.save_all(vec![done.clone(), scheduled]).unwrap();
+        storage.complete(&done, TaskStatus::Success(TaskOutput::from("ok"))).unwrap();
+        // A scheduled record which can't be read shows that it is skipped
+        storage.write(|txn| txn.open_table(TASKS)?.insert("scheduled", "not a record").map(|_| ()).map_err(Error::from)).unwrap();
+        let completed = storage.completed();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(completed.unwrap().into_iter().map(|task| task.id).collect::<Vec<String>>(), vec!["done".to_string()]);
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, Error, Context};
+use im::{HashMap, Vector};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use domain::executor::model::model::{Artifact, HookFailure, Task, TaskTemplate, WasmModule, WasmSource};
+
+use crate::secondary::adapter::storage::record::{ArtifactRecord, HookFailureRecord, TaskRecord, TemplateRecord};
+use super::StoredTask;
+
+// State of the storage, the journal holds the changes made since it was written
+#[derive(Serialize, Deserialize, Default)]
+struct Snapshot {
+    tasks: Vec<SnapshotTask>,
+    templates: Vec<TemplateRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotTask {
+    task: TaskRecord,
+    output: Vec<u8>,
+    // Content of the WASI module stored with the task
+    module: Option<Vec<u8>>,
+    artifacts: Vec<ArtifactRecord>,
+    hook_failures: Vec<HookFailureRecord>,
+}
+
+// Line of the journal, the new state of a task or a template or the ids of deleted tasks so replaying it twice is harmless
+#[derive(Serialize, Deserialize)]
+enum Entry {
+    Task(Box<SnapshotTask>),
+    Template(TemplateRecord),
+    Deletion(Vec<String>),
+}
+
+pub(super) struct Journal {
+    snapshot: PathBuf,
+    path: PathBuf,
+    // Locked by the process as long as it is open
+    file: File,
+    // Entries appended since the last snapshot
+    entries: usize,
+    compact_after: usize,
+}
+
+impl Journal {
+    // The journal is replayed over the snapshot into the given tasks and templates, then the state is snapshotted so that the journal starts empty
+    pub(super) fn open(snapshot: &Path, compact_after: usize, tasks: &mut Vector<StoredTask>, templates: &mut HashMap<String, TaskTemplate>) -> Result<Journal, Error> {
+        let path = with_suffix(snapshot, ".journal");
+        let file = OpenOptions::new().read(true).append(true).create(true).open(&path)
+            .context(format!("Error opening journal {}", path.display()))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(anyhow!("Journal {} is locked by another tasc process", path.display()));
+        }
+
+        let state = match fs::read(snapshot) {
+            Ok(content) => serde_json::from_slice::<Snapshot>(&content)
+                .context(format!("Error reading snapshot {}", snapshot.display()))?,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Snapshot::default(),
+            Err(err) => return Err(Error::from(err).context(format!("Error reading snapshot {}", snapshot.display()))),
+        };
+        *tasks = state.tasks.into_iter()
+            .map(StoredTask::try_from)
+            .collect::<Result<Vector<StoredTask>, Error>>()?;
+        *templates = state.templates.into_iter()
+            .map(TaskTemplate::from)
+            .map(|template| (template.name.clone(), template))
+            .collect();
+
+        let lines = BufReader::new(&file).lines()
+            .collect::<Result<Vec<String>, _>>()
+            .context(format!("Error reading journal {}", path.display()))?;
+        for (number, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<Entry>(line) {
+                Ok(Entry::Task(task)) => {
+                    let stored_task = StoredTask::try_from(*task)?;
+                    match tasks.iter().position(|recorded| recorded.id == stored_task.id) {
+                        Some(index) => { tasks.set(index, stored_task); },
+                        None => tasks.push_back(stored_task),
+                    }
+                },
+                Ok(Entry::Template(template)) => {
+                    let template = TaskTemplate::from(template);
+                    templates.insert(template.name.clone(), template);
+                },
+                Ok(Entry::Deletion(ids)) => tasks.retain(|stored_task| !ids.contains(&stored_task.id)),
+                // The last entry is incomplete when tasc was stopped while writing it
+                Err(_) if number + 1 == lines.len() => (),
+                Err(err) => return Err(Error::from(err).context(format!("Error reading line {} of journal {}", number + 1, path.display()))),
+            }
+        }
+
+        let mut journal = Journal { snapshot: snapshot.to_path_buf(), path, file, entries: lines.len(), compact_after };
+        if journal.entries > 0 {
+            journal.write_snapshot(tasks, templates)?;
+        }
+        Ok(journal)
+    }
+
+    pub(super) fn append_task(&mut self, task: &StoredTask) -> Result<(), Error> {
+        self.append(&Entry::Task(Box::new(SnapshotTask::from(task))))
+    }
+
+    pub(super) fn append_template(&mut self, template: &TaskTemplate) -> Result<(), Error> {
+        self.append(&Entry::Template(TemplateRecord::from(template)))
+    }
+
+    pub(super) fn append_deletion(&mut self, ids: &[String]) -> Result<(), Error> {
+        self.append(&Entry::Deletion(ids.to_vec()))
+    }
+
+    // Snapshots the state once enough entries have been appended
+    pub(super) fn compact(&mut self, tasks: &Vector<StoredTask>, templates: &HashMap<String, TaskTemplate>) -> Result<(), Error> {
+        if self.entries >= self.compact_after {
+            self.write_snapshot(tasks, templates)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn append(&mut self, entry: &Entry) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+            .and_then(|_| self.file.sync_data())
+            .context(format!("Error writing journal {}", self.path.display()))?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    // The snapshot is replaced by a rename, the journal is truncated once the new one is on disk
+    fn write_snapshot(&mut self, tasks: &Vector<StoredTask>, templates: &HashMap<String, TaskTemplate>) -> Result<(), Error> {
+        let state = Snapshot {
+            tasks: tasks.iter().map(SnapshotTask::from).collect(),
+            templates: templates.values().map(TemplateRecord::from).collect(),
+        };
+        let temporary = with_suffix(&self.snapshot, ".tmp");
+        let directory = match self.snapshot.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        File::create(&temporary)
+            .and_then(|mut file| file.write_all(&serde_json::to_vec(&state)?).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary, &self.snapshot))
+            .and_then(|_| File::open(directory).and_then(|directory| directory.sync_all()))
+            .context(format!("Error writing snapshot {}", self.snapshot.display()))?;
+        self.file.set_len(0)
+            .and_then(|_| self.file.sync_all())
+            .context(format!("Error truncating journal {}", self.path.display()))?;
+        self.entries = 0;
+        Ok(())
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+impl From<&StoredTask> for SnapshotTask {
+    fn from(stored_task: &StoredTask) -> Self {
+        let task = Task::from(stored_task);
+        let mut record = TaskRecord::from(&task);
+        record.completed_at = stored_task.completed_at;
+        let output = record.set_status(&stored_task.status)
+            .map(|output| output.content.clone())
+            .unwrap_or_default();
+        SnapshotTask {
+            task: record,
+            output,
+            module: match task.wasm {
+                Some(WasmModule { source: WasmSource::Blob(blob), .. }) => Some(blob),
+                _ => None,
+            },
+            artifacts: stored_task.artifacts.iter().cloned().map(ArtifactRecord::from).collect(),
+            hook_failures: stored_task.hook_failures.iter().map(HookFailureRecord::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<SnapshotTask> for StoredTask {
+    type Error = Error;
+
+    fn try_from(snapshot_task: SnapshotTask) -> Result<Self, Self::Error> {
+        let completed_at = snapshot_task.task.completed_at;
+        let (task, status) = snapshot_task.task.into_task(snapshot_task.output, snapshot_task.module)?;
+        let mut stored_task = StoredTask::from(&task);
+        stored_task.status = status;
+        stored_task.completed_at = completed_at;
+        stored_task.artifacts = snapshot_task.artifacts.into_iter().map(Artifact::from).collect();
+        stored_task.hook_failures = snapshot_task.hook_failures.into_iter()
+            .map(HookFailure::try_from)
+            .collect::<Result<Vec<HookFailure>, Error>>()?;
+        Ok(stored_task)
+    }
+}
\ No newline at end of file
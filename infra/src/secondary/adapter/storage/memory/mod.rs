@@ -0,0 +1,454 @@
+use anyhow::{anyhow, Error, Context};
+use im::HashMap;
+use im::Vector;
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use domain::executor::model::model::{Artifact, CompletedTask, Container, Credentials, ExportedTask, HookFailure, LabelSelector, ResourceLimits, SearchQuery, Task, TaskHooks, TaskId, TaskStatus, TaskTemplate, WasmModule};
+use domain::executor::ports::secondary::TaskStoragePort;
+
+use journal::Journal;
+
+mod journal;
+
+#[derive(Clone)]
+struct StoredTask {
+    id: String,
+    name: Option<String>,
+    command: String,
+    env: Option<HashMap<String, String>>,
+    timeout: Option<Duration>,
+    labels: HashMap<String, String>,
+    working_dir: Option<PathBuf>,
+    stdin: Option<Vec<u8>>,
+    umask: Option<u32>,
+    limits: ResourceLimits,
+    sandboxed: bool,
+    credentials: Option<Credentials>,
+    container: Option<Container>,
+    host: Option<String>,
+    wasm: Option<WasmModule>,
+    executor: Option<String>,
+    artifact_patterns: Vec<String>,
+    // Files collected once the task is completed
+    artifacts: Vec<Artifact>,
+    hooks: TaskHooks,
+    hook_failures: Vec<HookFailure>,
+    status: TaskStatus,
+    completed_at: Option<SystemTime>,
+}
+
+pub struct InMemoryStorageAdapter {
+    tasks: Vector<StoredTask>,
+    templates: HashMap<String, TaskTemplate>,
+    // Kept when the storage outlives the process
+    journal: Option<Journal>,
+}
+
+impl TaskStoragePort for InMemoryStorageAdapter {
+    fn save(&mut self, task: Task) -> Result<Task, Error> {
+        self.check_new_ids(std::slice::from_ref(&task))?;
+        self.tasks.push_back(StoredTask::from(&task));
+        self.journal_tasks(self.tasks.len() - 1..self.tasks.len())
+            .map(|_| task)
+    }
+
+    fn save_all(&mut self, tasks: Vec<Task>) -> Result<Vec<Task>, Error> {
+        self.check_new_ids(&tasks)?;
+        let saved = self.tasks.len();
+        self.tasks.extend(tasks.iter().map(StoredTask::from));
+        self.journal_tasks(saved..self.tasks.len())
+            .map(|_| tasks)
+    }
+
+    fn status(&mut self, id: TaskId) -> Result<TaskStatus, Error> {
+        let kept_id = id.clone();
+        self.find_only_one(id).context(format!("Error searching for id {:?}", kept_id))
+            .map(|stored_task| stored_task.status.clone())
+    }
+
+    fn resolve(&mut self, id: TaskId) -> Result<String, Error> {
+        let kept_id = id.clone();
+        self.find_only_one(id).context(format!("Error searching for id {:?}", kept_id))
+            .map(|stored_task| stored_task.id.clone())
+    }
+
+    fn complete(&mut self, task: &Task, status: TaskStatus) -> Result<(), Error> {
+        let index = self.position(task)
+            .context(format!("Error completing task {:?}", task.id))?;
+        let mut stored_task = StoredTask::from(task);
+        if status.is_terminal() {
+            stored_task.completed_at = Some(SystemTime::now());
+        }
+        stored_task.status = status;
+        stored_task.artifacts = self.tasks[index].artifacts.clone();
+        stored_task.hook_failures = self.tasks[index].hook_failures.clone();
+        self.tasks.set(index, stored_task);
+        self.journal_tasks(index..index + 1)
+    }
+
+    fn find(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        Ok(self.tasks.iter()
+            .filter(|stored_task| selector.matches(&stored_task.labels))
+            .map(|stored_task| (Task::from(stored_task), stored_task.status.clone()))
+            .collect())
+    }
+
+    fn search(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        Ok(self.tasks.iter()
+            .map(|stored_task| (Task::from(stored_task), stored_task.status.clone()))
+            .filter(|(task, status)| query.matches_task(task, status))
+            .collect())
+    }
+
+    fn save_template(&mut self, template: TaskTemplate) -> Result<(), Error> {
+        let name = template.name.clone();
+        self.templates.insert(name.clone(), template);
+        self.journal_template(&name)
+    }
+
+    fn template(&mut self, name: &str) -> Result<TaskTemplate, Error> {
+        self.templates.get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("No template named {}", name))
+    }
+
+    fn save_artifacts(&mut self, task: &Task, artifacts: Vec<Artifact>) -> Result<(), Error> {
+        let index = self.position(task)
+            .context(format!("Error saving artifacts of task {:?}", task.id))?;
+        let mut stored_task = self.tasks[index].clone();
+        stored_task.artifacts = artifacts;
+        self.tasks.set(index, stored_task);
+        self.journal_tasks(index..index + 1)
+    }
+
+    fn artifacts(&mut self, id: TaskId) -> Result<Vec<Artifact>, Error> {
+        let kept_id = id.clone();
+        self.find_only_one(id).context(format!("Error searching for id {:?}", kept_id))
+            .map(|stored_task| stored_task.artifacts.clone())
+    }
+
+    fn save_hook_failure(&mut self, task: &Task, failure: HookFailure) -> Result<(), Error> {
+        let index = self.position(task)
+            .context(format!("Error saving hook failure of task {:?}", task.id))?;
+        let mut stored_task = self.tasks[index].clone();
+        stored_task.hook_failures.retain(|recorded| recorded.stage != failure.stage);
+        stored_task.hook_failures.push(failure);
+        self.tasks.set(index, stored_task);
+        self.journal_tasks(index..index + 1)
+    }
+
+    fn hook_failures(&mut self, id: TaskId) -> Result<Vec<HookFailure>, Error> {
+        let kept_id = id.clone();
+        self.find_only_one(id).context(format!("Error searching for id {:?}", kept_id))
+            .map(|stored_task| stored_task.hook_failures.clone())
+    }
+
+    fn completed(&mut self) -> Result<Vec<CompletedTask>, Error> {
+        Ok(self.tasks.iter()
+            .filter_map(|stored_task| stored_task.status.outcome().map(|outcome| CompletedTask {
+                id: stored_task.id.clone(),
+                name: stored_task.name.clone(),
+                outcome,
+                completed_at: stored_task.completed_at,
+            }))
+            .collect())
+    }
+
+    fn delete(&mut self, ids: &[String]) -> Result<Vec<String>, Error> {
+        let digests: BTreeSet<String> = self.tasks.iter()
+            .filter(|stored_task| ids.contains(&stored_task.id))
+            .flat_map(|stored_task| stored_task.artifacts.iter().map(|artifact| artifact.digest.clone()))
+            .collect();
+        self.tasks.retain(|stored_task| !ids.contains(&stored_task.id));
+        if let Some(ref mut journal) = self.journal {
+            journal.append_deletion(ids)?;
+            journal.compact(&self.tasks, &self.templates)?;
+        }
+        Ok(digests.into_iter()
+            .filter(|digest| !self.tasks.iter().any(|stored_task| stored_task.artifacts.iter().any(|artifact| artifact.digest == *digest)))
+            .collect())
+    }
+
+    fn export(&mut self, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error> {
+        Ok(self.tasks.iter()
+            .skip(offset)
+            .take(limit)
+            .map(|stored_task| ExportedTask {
+                task: Task::from(stored_task),
+                status: stored_task.status.clone(),
+                completed_at: stored_task.completed_at,
+                artifacts: stored_task.artifacts.clone(),
+                hook_failures: stored_task.hook_failures.clone(),
+            })
+            .collect())
+    }
+
+    fn import(&mut self, tasks: Vec<ExportedTask>) -> Result<usize, Error> {
+        let mut ids: HashSet<String> = self.tasks.iter().map(|stored_task| stored_task.id.clone()).collect();
+        let imported = self.tasks.len();
+        self.tasks.extend(tasks.into_iter()
+            .filter(|exported| ids.insert(exported.task.id.clone()))
+            .map(|exported| StoredTask {
+                status: exported.status,
+                completed_at: exported.completed_at,
+                artifacts: exported.artifacts,
+                hook_failures: exported.hook_failures,
+                ..StoredTask::from(&exported.task)
+            }));
+        self.journal_tasks(imported..self.tasks.len())
+            .map(|_| self.tasks.len() - imported)
+    }
+}
+
+impl InMemoryStorageAdapter {
+    pub fn new() -> InMemoryStorageAdapter {
+        InMemoryStorageAdapter {
+            tasks: Vector::new(),
+            templates: HashMap::new(),
+            journal: None,
+        }
+    }
+
+    // Tasks loaded from the snapshot file and its journal, where the changes are then written
+    pub fn durable(snapshot: &Path, compact_after: usize) -> Result<InMemoryStorageAdapter, Error> {
+        let mut storage = InMemoryStorageAdapter::new();
+        storage.journal = Some(Journal::open(snapshot, compact_after, &mut storage.tasks, &mut storage.templates)?);
+        Ok(storage)
+    }
+
+    fn journal_tasks(&mut self, indexes: Range<usize>) -> Result<(), Error> {
+        match self.journal {
+            Some(ref mut journal) => {
+                for index in indexes {
+                    journal.append_task(&self.tasks[index])?;
+                }
+                journal.compact(&self.tasks, &self.templates)
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn journal_template(&mut self, name: &str) -> Result<(), Error> {
+        match self.journal {
+            Some(ref mut journal) => {
+                journal.append_template(&self.templates[name])?;
+                journal.compact(&self.tasks, &self.templates)
+            },
+            None => Ok(()),
+        }
+    }
+
+    // Ids are unique, a name resolves to the last task saved with it
+    fn find_only_one(&mut self, id: TaskId) -> Result<&StoredTask, Error> {
+        self.tasks.iter().rev()
+            .find(|stored_task| *stored_task == id)
+            .ok_or_else(|| anyhow!("No task correspond to your selection"))
+    }
+
+    // Tasks are updated by id, the others may share their name
+    fn position(&self, task: &Task) -> Option<usize> {
+        self.tasks.iter().position(|stored_task| stored_task.id == task.id)
+    }
+
+    // The tasks are rejected together when one of them reuses an id
+    fn check_new_ids(&self, tasks: &[Task]) -> Result<(), Error> {
+        let mut ids: HashSet<&str> = self.tasks.iter().map(|stored_task| stored_task.id.as_str()).collect();
+        match tasks.iter().find(|task| !ids.insert(task.id.as_str())) {
+            Some(task) => Err(anyhow!("A task with id {} already exists", task.id)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl From<&Task> for StoredTask {
+    fn from(task: &Task) -> Self {
+        StoredTask {
+            id: task.id.clone(),
+            name: task.name.clone(),
+            command: task.command.clone(),
+            env: task.env.clone(),
+            timeout: task.timeout,
+            labels: task.labels.clone(),
+            working_dir: task.working_dir.clone(),
+            stdin: task.stdin.clone(),
+            umask: task.umask,
+            limits: task.limits.clone(),
+            sandboxed: task.sandboxed,
+            credentials: task.credentials.clone(),
+            container: task.container.clone(),
+            host: task.host.clone(),
+            wasm: task.wasm.clone(),
+            executor: task.executor.clone(),
+            artifact_patterns: task.artifacts.clone(),
+            artifacts: vec![],
+            hooks: task.hooks.clone(),
+            hook_failures: vec![],
+            status: TaskStatus::Scheduled,
+            completed_at: None,
+        }
+    }
+}
+
+impl From<Task> for StoredTask {
+    fn from(task: Task) -> Self {
+        From::from(&task)
+    }
+}
+
+impl From<&StoredTask> for Task {
+    fn from(stored_task: &StoredTask) -> Self {
+        Task {
+            id: stored_task.id.clone(),
+            name: stored_task.name.clone(),
+            command: stored_task.command.clone(),
+            env: stored_task.env.clone(),
+            timeout: stored_task.timeout,
+            labels: stored_task.labels.clone(),
+            working_dir: stored_task.working_dir.clone(),
+            stdin: stored_task.stdin.clone(),
+            umask: stored_task.umask,
+            limits: stored_task.limits.clone(),
+            sandboxed: stored_task.sandboxed,
+            credentials: stored_task.credentials.clone(),
+            container: stored_task.container.clone(),
+            host: stored_task.host.clone(),
+            wasm: stored_task.wasm.clone(),
+            executor: stored_task.executor.clone(),
+            artifacts: stored_task.artifact_patterns.clone(),
+            hooks: stored_task.hooks.clone(),
+        }
+    }
+}
+
+impl PartialEq<TaskId> for &StoredTask {
+    fn eq(&self, other: &TaskId) -> bool {
+       match other {
+           TaskId::Id(ref id) => self.id == id.clone(),
+           TaskId::Name(ref name) => self.name == Some(name.clone())
+       }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::task;
+    use domain::executor::model::model::TaskOutput;
+    use domain::executor::testkit::storage_conformance;
+    use std::{env, fs};
+    use uuid::Uuid;
+
+    // Directory of the snapshot and journal of a test, removed when dropped
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new() -> Fixture {
+            let directory = env::temp_dir().join(format!("tasc-memory-{}", Uuid::new_v4()));
+            fs::create_dir_all(&directory).unwrap();
+            Fixture(directory)
+        }
+
+        fn snapshot(&self) -> PathBuf {
+            self.0.join("tasks.json")
+        }
+
+        fn journal(&self) -> String {
+            fs::read_to_string(self.0.join("tasks.json.journal")).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn saved(id: &str) -> Task {
+        Task { id: id.to_string(), name: Some(format!("{}-name", id)), ..task("true") }
+    }
+
+    fn template() -> TaskTemplate {
+        TaskTemplate { name: "greet".to_string(), command: "echo {{greeting}}".to_string(), env: None, timeout: None }
+    }
+
+    fn status(storage: &mut InMemoryStorageAdapter, id: TaskId) -> String {
+        format!("{:?}", storage.status(id).unwrap())
+    }
+
+    #[test]
+    fn test_in_memory_storage_conformance() {
+        storage_conformance(InMemoryStorageAdapter::new);
+    }
+
+    #[test]
+    fn test_durable_in_memory_storage_conformance() {
+        let fixture = Fixture::new();
+        storage_conformance(|| InMemoryStorageAdapter::durable(&fixture.0.join(Uuid::new_v4().to_string()), 3).unwrap());
+    }
+
+    #[test]
+    fn test_durable_should_replay_the_journal_over_the_snapshot() {
+        let fixture = Fixture::new();
+        let mut storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 4).unwrap();
+        // The fourth entry writes a snapshot and truncates the journal, the next ones are only journaled
+        storage.save(saved("a")).unwrap();
+        storage.save(saved("b")).unwrap();
+        storage.complete(&saved("a"), TaskStatus::Success(TaskOutput::from("done"))).unwrap();
+        assert!(!fixture.snapshot().exists());
+        storage.delete(&["b".to_string()]).unwrap();
+        assert!(fixture.snapshot().is_file());
+        assert_eq!(fixture.journal(), "");
+        storage.save(saved("c")).unwrap();
+        storage.save_template(template()).unwrap();
+        drop(storage);
+        assert_eq!(fixture.journal().lines().count(), 2);
+
+        let mut storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).unwrap();
+        // The replayed journal is snapshotted so it starts empty
+        assert_eq!(fixture.journal(), "");
+        assert_eq!(status(&mut storage, TaskId::Id("a".to_string())), format!("{:?}", TaskStatus::Success(TaskOutput::from("done"))));
+        assert!(storage.status(TaskId::Id("b".to_string())).is_err());
+        assert_eq!(status(&mut storage, TaskId::Name("c-name".to_string())), "Scheduled");
+        assert_eq!(storage.template("greet").unwrap(), template());
+    }
+
+    #[test]
+    fn test_durable_should_replay_the_deletions_of_the_journal() {
+        let fixture = Fixture::new();
+        let mut storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).unwrap();
+        storage.save_all(vec![saved("a"), saved("b")]).unwrap();
+        storage.delete(&["a".to_string()]).unwrap();
+        drop(storage);
+        assert!(!fixture.snapshot().exists());
+        let mut storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).unwrap();
+        assert_eq!(storage.export(0, 10).unwrap().into_iter().map(|exported| exported.task.id).collect::<Vec<String>>(), vec!["b".to_string()]);
+    }
+
+    #[test]
+    fn test_durable_should_ignore_a_torn_last_entry() {
+        let fixture = Fixture::new();
+        let mut storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).unwrap();
+        storage.save(saved("a")).unwrap();
+        drop(storage);
+        let journal = fixture.journal();
+        fs::write(fixture.0.join("tasks.json.journal"), format!("{}{}", journal, &journal[..journal.len() / 2])).unwrap();
+        let mut storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).unwrap();
+        assert_eq!(status(&mut storage, TaskId::Id("a".to_string())), "Scheduled");
+        drop(storage);
+        // Only the last entry may be incomplete
+        fs::write(fixture.0.join("tasks.json.journal"), format!("{}\n{}", &journal[..journal.len() / 2], journal)).unwrap();
+        let error = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).err().unwrap();
+        assert!(error.to_string().starts_with("Error reading line 1 of journal"), "{}", error);
+    }
+
+    #[test]
+    fn test_durable_should_refuse_a_journal_opened_by_another_storage() {
+        let fixture = Fixture::new();
+        let _storage = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).unwrap();
+        let error = InMemoryStorageAdapter::durable(&fixture.snapshot(), 100).err().unwrap();
+        assert_eq!(error.to_string(), format!("Journal {}.journal is locked by another tasc process", fixture.snapshot().display()));
+    }
+}
@@ -1,23 +1,77 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 
 use domain::executor::ports::secondary::TaskStoragePort;
 
+#[cfg(feature = "sqlite")]
 use crate::secondary::adapter::storage::database::SqliteStorageAdapter;
+use crate::secondary::adapter::storage::kv::KvStorageAdapter;
 use crate::secondary::adapter::storage::memory::InMemoryStorageAdapter;
-use crate::primary::settings::StorageConfiguration;
+#[cfg(feature = "postgres")]
+use crate::secondary::adapter::storage::postgres::PostgresStorageAdapter;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+use crate::secondary::adapter::storage::database::migrations::DatabaseSchema;
+#[cfg(feature = "sqlite")]
+use crate::secondary::adapter::storage::database::migrations::{EVENTS_MIGRATIONS, SQLITE_MIGRATIONS};
+use crate::primary::settings::{EventStoreConfiguration, StorageConfiguration};
 
+// Records shared by the diesel storages
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
 pub mod database;
+pub mod kv;
 pub mod memory;
+mod record;
+#[cfg(feature = "postgres")]
+pub mod postgres;
 
 pub fn new_storage_adapter(storage_type: StorageConfiguration) -> Result<Box<dyn TaskStoragePort>, Error> {
     match storage_type {
-        StorageConfiguration::Database { database_url } => {
+        #[cfg(feature = "sqlite")]
+        StorageConfiguration::Database { database_url, pool_size, busy_timeout, auto_migrate } => {
             // Result<SqliteStorageAdapter, Error>  => Result<Box<SqliteStorageAdapter>, Error> == Result<Box<dyn TaskStoragePort>, Error>
             // Why does it ot work with this code, check type at compile
             // SqliteStorageAdapter::new(&database_url).map(|adapter| Box::new(adapter))
-            Ok(Box::new(SqliteStorageAdapter::new(&database_url)?))
+            Ok(Box::new(SqliteStorageAdapter::new(&database_url, pool_size, busy_timeout, auto_migrate)?))
 
         },
-        StorageConfiguration::InMemory => Ok(Box::new(InMemoryStorageAdapter::new()))
+        #[cfg(feature = "postgres")]
+        StorageConfiguration::Postgres { database_url, auto_migrate } => Ok(Box::new(PostgresStorageAdapter::new(&database_url, auto_migrate)?)),
+        StorageConfiguration::Kv { path } => Ok(Box::new(KvStorageAdapter::new(&path)?)),
+        StorageConfiguration::InMemory { snapshot: Some(snapshot), compact_after } => Ok(Box::new(InMemoryStorageAdapter::durable(&snapshot, compact_after)?)),
+        StorageConfiguration::InMemory { snapshot: None, .. } => Ok(Box::new(InMemoryStorageAdapter::new()))
     }
+}
+
+// Schema of a database, migrated by tasc db unless database.auto_migrate is set
+pub trait SchemaAdapter {
+    // storage or events
+    fn database(&self) -> &str;
+    // Every migration of tasc with whether it is applied
+    fn status(&self) -> Result<Vec<(&'static str, bool)>, Error>;
+    // Migrations applied, in order
+    fn migrate(&self) -> Result<Vec<&'static str>, Error>;
+    // Migration reverted, the last applied one
+    fn rollback(&self) -> Result<&'static str, Error>;
+}
+
+// The events have their own schema only when they are not stored in the database of the tasks
+pub fn new_schema_adapters(storage: &StorageConfiguration, events: &EventStoreConfiguration) -> Result<Vec<Box<dyn SchemaAdapter>>, Error> {
+    let storage_schema: Option<Box<dyn SchemaAdapter>> = match storage {
+        #[cfg(feature = "sqlite")]
+        StorageConfiguration::Database { database_url, busy_timeout, .. } => Some(Box::new(DatabaseSchema::sqlite("storage", database_url, *busy_timeout, SQLITE_MIGRATIONS)?)),
+        #[cfg(feature = "postgres")]
+        StorageConfiguration::Postgres { database_url, .. } => Some(Box::new(DatabaseSchema::postgres("storage", database_url)?)),
+        StorageConfiguration::Kv { .. } | StorageConfiguration::InMemory { .. } => None,
+    };
+    let events_schema: Option<Box<dyn SchemaAdapter>> = match events {
+        #[cfg(feature = "sqlite")]
+        EventStoreConfiguration::Database { database_url, busy_timeout, .. }
+            if !matches!(storage, StorageConfiguration::Database { database_url: storage_url, .. } if storage_url == database_url) =>
+            Some(Box::new(DatabaseSchema::sqlite("events", database_url, *busy_timeout, EVENTS_MIGRATIONS)?)),
+        _ => None,
+    };
+    let schemas: Vec<Box<dyn SchemaAdapter>> = storage_schema.into_iter().chain(events_schema).collect();
+    if schemas.is_empty() {
+        return Err(anyhow!("Neither the storage nor the event store use a database with a schema"));
+    }
+    Ok(schemas)
 }
\ No newline at end of file
@@ -0,0 +1,243 @@
+use crate::secondary::adapter::storage::database::model::{DbArtifact, DbCompletedTask, DbHookFailure, DbTask, DbTemplate, TaskStatusUpdate, SCHEDULED};
+use crate::secondary::adapter::storage::database::schema::{artifacts, hook_failures, tasks, templates};
+use diesel::pg::upsert::excluded;
+use diesel::{PgConnection, Connection, RunQueryDsl};
+use anyhow::{anyhow, Error, Context};
+use domain::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, LabelSelector, SearchQuery, Task, TaskId, TaskStatus, TaskTemplate};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use crate::diesel::*;
+use diesel::dsl::sql;
+use diesel::expression::SqlLiteral;
+use diesel::sql_types::BigInt;
+
+const DELETE_CHUNK_SIZE: usize = 500;
+
+pub fn establish_connection(database_url: &str) -> Result<PgConnection, Error> {
+    PgConnection::establish(database_url)
+        .context("Error connecting to postgres")
+}
+
+pub fn create_task(conn: &PgConnection, new_task: &Task) -> Result<usize, Error> {
+    let insertable_task: DbTask = (new_task, &TaskStatus::Scheduled).into();
+    diesel::insert_into(tasks::table)
+        .values(&insertable_task)
+        .execute(conn)
+        .context(format!("Error inserting in db task {:?}", new_task))
+}
+
+pub fn create_tasks(conn: &PgConnection, new_tasks: &[Task]) -> Result<usize, Error> {
+    conn.transaction::<_, Error, _>(|| new_tasks.iter()
+        .try_fold(0, |inserted, new_task| create_task(conn, new_task).map(|count| inserted + count)))
+        .context("Error inserting tasks in db")
+}
+
+pub fn get_task(conn: &PgConnection, task_id: &TaskId) -> Result<TaskStatus, Error> {
+    use crate::secondary::adapter::storage::database::schema::tasks::dsl::*;
+    let (_, status_value) = match task_id {
+        TaskId::Id(id_value) => tasks.filter(id.eq(id_value))
+            .limit(1)
+            .first::<DbTask>(conn)
+            .context(format!("Error loading from database id {}", id_value))?.try_into(),
+        TaskId::Name(name_value) => tasks.filter(name.nullable().eq(name_value))
+            .order(last_saved())
+            .limit(1)
+            .first::<DbTask>(conn)
+            .context(format!("Error loading from database name {}", name_value))?.try_into()
+    }?;
+    return Ok(status_value);
+}
+
+// Labels are stored serialized so the selection is done after loading the tasks
+pub fn find_tasks(conn: &PgConnection, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+    use crate::secondary::adapter::storage::database::schema::tasks::dsl::*;
+    tasks.load::<DbTask>(conn)
+        .context("Error loading tasks from database")?
+        .into_iter()
+        .map(TryInto::try_into)
+        .filter(|result: &Result<(Task, TaskStatus), Error>| result.as_ref().map_or(true, |(task, _)| selector.matches(&task.labels)))
+        .collect()
+}
+
+// The outputs are stored as bytes, the tasks are matched once loaded as for the labels
+pub fn search_tasks(conn: &PgConnection, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+    tasks::table.order(saved_order())
+        .load::<DbTask>(conn)
+        .context("Error loading tasks from database")?
+        .into_iter()
+        .map(TryInto::try_into)
+        .filter(|result: &Result<(Task, TaskStatus), Error>| result.as_ref().map_or(true, |(task, status)| query.matches_task(task, status)))
+        .collect()
+}
+
+pub fn update_task(conn: &PgConnection, id_value: &str, status: &TaskStatus) -> Result<(), Error> {
+    use crate::secondary::adapter::storage::database::schema::tasks::dsl as tasks_dsl;
+    diesel::update(tasks_dsl::tasks.find(id_value))
+        .set(&TaskStatusUpdate::from(status))
+        .execute(conn)
+        .context(format!("Error update in database for task id {}", id_value))
+        .and_then(|updated| match updated {
+            0 => Err(anyhow!("No task with id {}", id_value)),
+            _ => Ok(()),
+        })
+}
+
+pub fn get_completed_tasks(conn: &PgConnection) -> Result<Vec<CompletedTask>, Error> {
+    tasks::table.select((tasks::id, tasks::name, tasks::status, tasks::completed_at))
+        .filter(tasks::status.ne(SCHEDULED))
+        .load::<DbCompletedTask>(conn)
+        .context("Error loading completed tasks from database")?
+        .into_iter()
+        .map(CompletedTask::try_from)
+        .collect()
+}
+
+// The ids are bound by chunks to stay under the limit of parameters of a statement, the digests of the deleted
+// artifacts are returned unless another artifact has them
+pub fn delete_tasks(conn: &PgConnection, ids: &[String]) -> Result<Vec<String>, Error> {
+    conn.transaction::<_, Error, _>(|| {
+        let mut digests = BTreeSet::new();
+        for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+            digests.extend(artifacts::table.select(artifacts::digest).filter(artifacts::task_id.eq_any(chunk)).load::<String>(conn)?);
+            diesel::delete(artifacts::table.filter(artifacts::task_id.eq_any(chunk))).execute(conn)?;
+            diesel::delete(hook_failures::table.filter(hook_failures::task_id.eq_any(chunk))).execute(conn)?;
+            diesel::delete(tasks::table.filter(tasks::id.eq_any(chunk))).execute(conn)?;
+        }
+        let digests: Vec<String> = digests.into_iter().collect();
+        let mut referenced = HashSet::new();
+        for chunk in digests.chunks(DELETE_CHUNK_SIZE) {
+            referenced.extend(artifacts::table.select(artifacts::digest).filter(artifacts::digest.eq_any(chunk)).load::<String>(conn)?);
+        }
+        Ok(digests.into_iter().filter(|digest| !referenced.contains(digest)).collect())
+    }).context("Error deleting tasks from database")
+}
+
+// Page of the tasks in the order they were saved, read with their artifacts and hook failures in one transaction
+pub fn export_tasks(conn: &PgConnection, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error> {
+    conn.transaction::<_, Error, _>(|| {
+        let mut exported = tasks::table.order(saved_order())
+            .offset(offset as i64)
+            .limit(limit as i64)
+            .load::<DbTask>(conn)?
+            .into_iter()
+            .map(ExportedTask::try_from)
+            .collect::<Result<Vec<ExportedTask>, Error>>()?;
+        let ids: Vec<String> = exported.iter().map(|exported| exported.task.id.clone()).collect();
+        let mut task_artifacts: HashMap<String, Vec<Artifact>> = HashMap::new();
+        for artifact in artifacts::table.filter(artifacts::task_id.eq_any(&ids)).order(artifacts::path).load::<DbArtifact>(conn)? {
+            task_artifacts.entry(artifact.task_id.clone()).or_default().push(Artifact::from(artifact));
+        }
+        let mut task_hook_failures: HashMap<String, Vec<HookFailure>> = HashMap::new();
+        for failure in hook_failures::table.filter(hook_failures::task_id.eq_any(&ids)).order(hook_failures::stage.desc()).load::<DbHookFailure>(conn)? {
+            task_hook_failures.entry(failure.task_id.clone()).or_default().push(HookFailure::try_from(failure)?);
+        }
+        for exported in exported.iter_mut() {
+            exported.artifacts = task_artifacts.remove(&exported.task.id).unwrap_or_default();
+            exported.hook_failures = task_hook_failures.remove(&exported.task.id).unwrap_or_default();
+        }
+        Ok(exported)
+    }).context("Error exporting tasks from database")
+}
+
+// Tasks whose id is stored are ignored by the insertion, their artifacts and hook failures with them
+pub fn import_tasks(conn: &PgConnection, imported_tasks: &[ExportedTask]) -> Result<usize, Error> {
+    conn.transaction::<_, Error, _>(|| imported_tasks.iter()
+        .try_fold(0, |imported, exported| {
+            let inserted = diesel::insert_into(tasks::table)
+                .values(&DbTask::from(exported))
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            if inserted == 0 {
+                return Ok(imported);
+            }
+            if !exported.artifacts.is_empty() {
+                create_artifacts(conn, &exported.task, &exported.artifacts)?;
+            }
+            exported.hook_failures.iter().try_for_each(|failure| create_hook_failure(conn, &exported.task, failure).map(|_| ()))?;
+            Ok(imported + inserted)
+        }))
+        .context("Error importing tasks in database")
+}
+
+pub fn create_artifacts(conn: &PgConnection, task: &Task, new_artifacts: &[Artifact]) -> Result<usize, Error> {
+    let insertable_artifacts: Vec<DbArtifact> = new_artifacts.iter()
+        .map(|artifact| (task, artifact).into())
+        .collect();
+    diesel::insert_into(artifacts::table)
+        .values(&insertable_artifacts)
+        .on_conflict((artifacts::task_id, artifacts::path))
+        .do_update()
+        .set((artifacts::digest.eq(excluded(artifacts::digest)), artifacts::size.eq(excluded(artifacts::size))))
+        .execute(conn)
+        .context(format!("Error inserting in db artifacts of task {}", task.id))
+}
+
+// Id of the task, looked up when it is given by name
+pub fn resolve_id(conn: &PgConnection, task_id: &TaskId) -> Result<String, Error> {
+    match task_id {
+        TaskId::Id(id_value) => Ok(id_value.clone()),
+        TaskId::Name(name_value) => tasks::table.select(tasks::id)
+            .filter(tasks::name.nullable().eq(name_value))
+            .order(last_saved())
+            .first::<String>(conn)
+            .context(format!("Error loading from database name {}", name_value)),
+    }
+}
+
+pub fn get_artifacts(conn: &PgConnection, task_id: &TaskId) -> Result<Vec<Artifact>, Error> {
+    let id_value = resolve_id(conn, task_id)?;
+    artifacts::table.filter(artifacts::task_id.eq(&id_value))
+        .order(artifacts::path)
+        .load::<DbArtifact>(conn)
+        .context(format!("Error loading from database artifacts of task {}", id_value))
+        .map(|artifacts| artifacts.into_iter().map(Artifact::from).collect())
+}
+
+pub fn create_hook_failure(conn: &PgConnection, task: &Task, failure: &HookFailure) -> Result<usize, Error> {
+    diesel::insert_into(hook_failures::table)
+        .values(&DbHookFailure::from((task, failure)))
+        .on_conflict((hook_failures::task_id, hook_failures::stage))
+        .do_update()
+        .set(hook_failures::message.eq(excluded(hook_failures::message)))
+        .execute(conn)
+        .context(format!("Error inserting in db {} hook failure of task {}", failure.stage, task.id))
+}
+
+pub fn get_hook_failures(conn: &PgConnection, task_id: &TaskId) -> Result<Vec<HookFailure>, Error> {
+    let id_value = resolve_id(conn, task_id)?;
+    hook_failures::table.filter(hook_failures::task_id.eq(&id_value))
+        .order(hook_failures::stage.desc())
+        .load::<DbHookFailure>(conn)
+        .context(format!("Error loading from database hook failures of task {}", id_value))?
+        .into_iter()
+        .map(HookFailure::try_from)
+        .collect()
+}
+
+pub fn create_template(conn: &PgConnection, new_template: &TaskTemplate) -> Result<usize, Error> {
+    diesel::insert_into(templates::table)
+        .values(&DbTemplate::from(new_template))
+        .on_conflict(templates::name)
+        .do_update()
+        .set((templates::command.eq(excluded(templates::command)), templates::env.eq(excluded(templates::env)), templates::timeout.eq(excluded(templates::timeout))))
+        .execute(conn)
+        .context(format!("Error inserting in db template {}", new_template.name))
+}
+
+pub fn get_template(conn: &PgConnection, template_name: &str) -> Result<TaskTemplate, Error> {
+    use crate::secondary::adapter::storage::database::schema::templates::dsl::*;
+    templates.find(template_name)
+        .first::<DbTemplate>(conn)
+        .map(TaskTemplate::from)
+        .context(format!("Error loading from database template {}", template_name))
+}
+
+// The saved column is filled by a sequence on insertion
+fn saved_order() -> SqlLiteral<BigInt> {
+    sql::<BigInt>("tasks.saved")
+}
+
+// A name resolves to the last task saved with it
+fn last_saved() -> diesel::expression::operators::Desc<SqlLiteral<BigInt>> {
+    saved_order().desc()
+}
\ No newline at end of file
@@ -0,0 +1,221 @@
+use diesel::PgConnection;
+use anyhow::Error;
+use domain::executor::model::model::{Artifact, CompletedTask, ExportedTask, HookFailure, LabelSelector, SearchQuery, Task, TaskId, TaskStatus, TaskTemplate};
+use domain::executor::ports::secondary::TaskStoragePort;
+
+use super::database::migrations::{self, POSTGRES_MIGRATIONS};
+
+mod commands;
+
+pub struct PostgresStorageAdapter {
+    connection: PgConnection
+}
+
+impl TaskStoragePort for PostgresStorageAdapter {
+    fn save(&mut self, task: Task) -> Result<Task, Error> {
+        commands::create_task(&self.connection, &task)
+            .map(|_| task)
+    }
+
+    fn save_all(&mut self, tasks: Vec<Task>) -> Result<Vec<Task>, Error> {
+        commands::create_tasks(&self.connection, &tasks)
+            .map(|_| tasks)
+    }
+
+    fn status(&mut self, id: TaskId) -> Result<TaskStatus, Error> {
+        commands::get_task(&self.connection, &id)
+    }
+
+    fn resolve(&mut self, id: TaskId) -> Result<String, Error> {
+        commands::resolve_id(&self.connection, &id)
+    }
+
+    fn complete(&mut self, task: &Task, status: TaskStatus) -> Result<(), Error> {
+        commands::update_task(&self.connection, task.id.as_str(), &status)
+    }
+
+    fn find(&mut self, selector: &LabelSelector) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        commands::find_tasks(&self.connection, selector)
+    }
+
+    fn search(&mut self, query: &SearchQuery) -> Result<Vec<(Task, TaskStatus)>, Error> {
+        commands::search_tasks(&self.connection, query)
+    }
+
+    fn save_template(&mut self, template: TaskTemplate) -> Result<(), Error> {
+        commands::create_template(&self.connection, &template)
+            .map(|_| ())
+    }
+
+    fn template(&mut self, name: &str) -> Result<TaskTemplate, Error> {
+        commands::get_template(&self.connection, name)
+    }
+
+    fn save_artifacts(&mut self, task: &Task, artifacts: Vec<Artifact>) -> Result<(), Error> {
+        commands::create_artifacts(&self.connection, task, &artifacts)
+            .map(|_| ())
+    }
+
+    fn artifacts(&mut self, id: TaskId) -> Result<Vec<Artifact>, Error> {
+        commands::get_artifacts(&self.connection, &id)
+    }
+
+    fn save_hook_failure(&mut self, task: &Task, failure: HookFailure) -> Result<(), Error> {
+        commands::create_hook_failure(&self.connection, task, &failure)
+            .map(|_| ())
+    }
+
+    fn hook_failures(&mut self, id: TaskId) -> Result<Vec<HookFailure>, Error> {
+        commands::get_hook_failures(&self.connection, &id)
+    }
+
+    fn completed(&mut self) -> Result<Vec<CompletedTask>, Error> {
+        commands::get_completed_tasks(&self.connection)
+    }
+
+    fn delete(&mut self, ids: &[String]) -> Result<Vec<String>, Error> {
+        commands::delete_tasks(&self.connection, ids)
+    }
+
+    fn export(&mut self, offset: usize, limit: usize) -> Result<Vec<ExportedTask>, Error> {
+        commands::export_tasks(&self.connection, offset, limit)
+    }
+
+    fn import(&mut self, tasks: Vec<ExportedTask>) -> Result<usize, Error> {
+        commands::import_tasks(&self.connection, &tasks)
+    }
+}
+
+impl PostgresStorageAdapter {
+    pub fn new(database_url: &str, auto_migrate: bool) -> Result<PostgresStorageAdapter, Error> {
+        let database_connection = commands::establish_connection(database_url)?;
+        migrations::prepare(&database_connection, "storage", POSTGRES_MIGRATIONS, auto_migrate)?;
+        Ok(PostgresStorageAdapter {
+            connection: database_connection
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secondary::adapter::execution::tests::{program, task};
+    use diesel::{Connection, RunQueryDsl};
+    use domain::executor::model::model::TaskEvent;
+    use domain::executor::ports::secondary::EventStorePort;
+    use crate::secondary::adapter::events::postgres::PostgresEventStoreAdapter;
+    use domain::executor::testkit::{concurrent_handles_should_not_lose_updates, storage_conformance};
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command, Stdio};
+    use std::time::{Duration, Instant};
+    use std::{env, fs, thread};
+    use uuid::Uuid;
+
+    // Cluster of a test listening on a socket of its directory, stopped and removed when dropped
+    struct Postgres(Child, PathBuf);
+
+    impl Drop for Postgres {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+            let _ = fs::remove_dir_all(&self.1);
+        }
+    }
+
+    impl Postgres {
+        fn url(&self, database: &str) -> String {
+            format!("host={} user=tasc dbname={}", self.1.display(), database)
+        }
+
+        // Every adapter of the conformance gets an empty database
+        fn database(&self) -> String {
+            let database = format!("tasc_{}", Uuid::new_v4().to_simple());
+            let admin = PgConnection::establish(&self.url("postgres")).unwrap();
+            diesel::sql_query(format!("CREATE DATABASE {}", database)).execute(&admin).unwrap();
+            self.url(&database)
+        }
+    }
+
+    // postgres refuses to run as root, the cluster then belongs to nobody
+    fn as_user(command: &mut Command) -> &mut Command {
+        if unsafe { libc::geteuid() } == 0 {
+            command.uid(65534).gid(65534);
+        }
+        command.stdout(Stdio::null()).stderr(Stdio::null())
+    }
+
+    fn start_postgres(initdb: &Path, postgres: &Path) -> Postgres {
+        let directory = env::temp_dir().join(format!("tasc-postgres-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        if unsafe { libc::geteuid() } == 0 {
+            std::os::unix::fs::chown(&directory, Some(65534), Some(65534)).unwrap();
+        }
+        let status = as_user(Command::new(initdb).args(["-U", "tasc", "-A", "trust", "-D"]).arg(directory.join("data"))).status().unwrap();
+        assert!(status.success());
+        let child = as_user(Command::new(postgres).arg("-D").arg(directory.join("data"))
+            .args(["-c", "listen_addresses=", "-c", "fsync=off"])
+            .arg("-k").arg(&directory))
+            .spawn()
+            .unwrap();
+        let postgres = Postgres(child, directory);
+        let start = Instant::now();
+        while PgConnection::establish(&postgres.url("postgres")).is_err() {
+            assert!(start.elapsed() < Duration::from_secs(30), "postgres is not accepting connections in {}", postgres.1.display());
+            thread::sleep(Duration::from_millis(100));
+        }
+        postgres
+    }
+
+    fn postgres() -> Option<Postgres> {
+        match (program("initdb"), program("postgres")) {
+            (Some(initdb), Some(postgres)) => Some(start_postgres(&initdb, &postgres)),
+            _ => {
+                eprintln!("Postgres tests skipped, postgres is not installed");
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_postgres_events_should_be_kept_in_the_database_of_the_storage() {
+        let postgres = match postgres() {
+            Some(postgres) => postgres,
+            None => return,
+        };
+        let url = postgres.database();
+        let mut storage = PostgresStorageAdapter::new(&url, true).unwrap();
+        let mut store = PostgresEventStoreAdapter::new(&url, false, String::from("tasc@host")).unwrap();
+        storage.save(task("true")).unwrap();
+        store.append("test", TaskEvent::Submitted { command: String::from("true") }).unwrap();
+        store.append("other", TaskEvent::Submitted { command: String::from("false") }).unwrap();
+        store.append("test", TaskEvent::Started).unwrap();
+        store.append("test", TaskEvent::Completed { success: true, exit_code: Some(0), truncated: 0, log: Some(PathBuf::from("/var/log/tasc/test.log")) }).unwrap();
+
+        let events = PostgresEventStoreAdapter::new(&url, false, String::from("tasc@host")).unwrap().events("test").unwrap();
+        assert_eq!(events.iter().map(|recorded| recorded.event.clone()).collect::<Vec<TaskEvent>>(), vec![
+            TaskEvent::Submitted { command: String::from("true") },
+            TaskEvent::Started,
+            TaskEvent::Completed { success: true, exit_code: Some(0), truncated: 0, log: Some(PathBuf::from("/var/log/tasc/test.log")) },
+        ]);
+        assert!(events.windows(2).all(|pair| pair[0].sequence < pair[1].sequence));
+        assert!(events.iter().all(|recorded| recorded.task_id == "test" && recorded.actor == "tasc@host"));
+        let connection = PgConnection::establish(&url).unwrap();
+        for statement in ["UPDATE task_events SET command = 'false'", "DELETE FROM task_events", "TRUNCATE task_events"].iter() {
+            let error = diesel::sql_query(*statement).execute(&connection).unwrap_err();
+            assert!(error.to_string().contains("task events are immutable"), "{}: {}", statement, error);
+        }
+        assert_eq!(store.events("other").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_postgres_storage_conformance() {
+        let postgres = match postgres() {
+            Some(postgres) => postgres,
+            None => return,
+        };
+        storage_conformance(|| PostgresStorageAdapter::new(&postgres.database(), true).unwrap());
+        let url = postgres.database();
+        PostgresStorageAdapter::new(&url, true).unwrap();
+        concurrent_handles_should_not_lose_updates(move || PostgresStorageAdapter::new(&url, false).unwrap());
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{anyhow, Error};
+use im::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use domain::executor::model::model::{Artifact, CompletedTask, Container, Credentials, HookFailure, Mount, ResourceLimits, Task, TaskHooks, TaskOutcome, TaskOutput, TaskStatus, TaskTemplate, WasmModule, WasmSource};
+
+// Serializable records of the storages without a schema, the content of the outputs and WASI modules is stored by the storage
+
+pub(super) const SCHEDULED: &str = "scheduled";
+pub(super) const SUCCESS: &str = "success";
+pub(super) const ERROR: &str = "error";
+pub(super) const CANCELLED: &str = "cancelled";
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct TaskRecord {
+    pub(super) id: String,
+    pub(super) name: Option<String>,
+    command: String,
+    env: Option<HashMap<String, String>>,
+    // Seconds
+    timeout: Option<u64>,
+    pub(super) labels: HashMap<String, String>,
+    working_dir: Option<PathBuf>,
+    // Text in the records written before it was stored as bytes
+    #[serde(default, deserialize_with = "deserialize_payload")]
+    stdin: Option<Vec<u8>>,
+    umask: Option<u32>,
+    cpu_time_limit: Option<u64>,
+    memory_limit: Option<u64>,
+    open_files_limit: Option<u64>,
+    processes_limit: Option<u64>,
+    sandboxed: bool,
+    // uid, gid and supplementary groups
+    credentials: Option<(u32, u32, Vec<u32>)>,
+    container_rootfs: Option<PathBuf>,
+    // source, destination and read only flag
+    container_mounts: Vec<(PathBuf, PathBuf, bool)>,
+    host: Option<String>,
+    pub(super) wasm: Option<WasmRecord>,
+    executor: Option<String>,
+    artifact_patterns: Vec<String>,
+    pre_hook: Option<String>,
+    post_hook: Option<String>,
+    pub(super) status: String,
+    exit_code: Option<i32>,
+    output_truncated: u64,
+    output_log: Option<PathBuf>,
+    // Missing from the records written before it was recorded
+    #[serde(default)]
+    pub(super) completed_at: Option<SystemTime>,
+}
+
+// The content of the module is stored apart when it has no path
+#[derive(Serialize, Deserialize)]
+pub(super) struct WasmRecord {
+    pub(super) path: Option<PathBuf>,
+    dir: Option<PathBuf>,
+    fuel: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct TemplateRecord {
+    name: String,
+    command: String,
+    env: Option<HashMap<String, String>>,
+    timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct ArtifactRecord {
+    path: PathBuf,
+    digest: String,
+    size: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct HookFailureRecord {
+    pub(super) stage: String,
+    message: String,
+}
+
+fn deserialize_payload<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Payload {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+    Ok(Option::<Payload>::deserialize(deserializer)?.map(|payload| match payload {
+        Payload::Text(text) => text.into_bytes(),
+        Payload::Bytes(bytes) => bytes,
+    }))
+}
+
+impl From<&Task> for TaskRecord {
+    fn from(task: &Task) -> Self {
+        TaskRecord {
+            id: task.id.clone(),
+            name: task.name.clone(),
+            command: task.command.clone(),
+            env: task.env.clone(),
+            timeout: task.timeout.map(|timeout| timeout.as_secs()),
+            labels: task.labels.clone(),
+            working_dir: task.working_dir.clone(),
+            stdin: task.stdin.clone(),
+            umask: task.umask,
+            cpu_time_limit: task.limits.cpu_time.map(|cpu_time| cpu_time.as_secs()),
+            memory_limit: task.limits.memory,
+            open_files_limit: task.limits.open_files,
+            processes_limit: task.limits.processes,
+            sandboxed: task.sandboxed,
+            credentials: task.credentials.as_ref().map(|credentials| (credentials.uid, credentials.gid, credentials.groups.clone())),
+            container_rootfs: task.container.as_ref().map(|container| container.rootfs.clone()),
+            container_mounts: task.container.as_ref()
+                .map(|container| container.mounts.iter().map(|mount| (mount.source.clone(), mount.destination.clone(), mount.read_only)).collect())
+                .unwrap_or_default(),
+            host: task.host.clone(),
+            wasm: task.wasm.as_ref().map(|wasm| WasmRecord {
+                path: match wasm.source {
+                    WasmSource::Path(ref path) => Some(path.clone()),
+                    WasmSource::Blob(_) => None,
+                },
+                dir: wasm.dir.clone(),
+                fuel: wasm.fuel,
+            }),
+            executor: task.executor.clone(),
+            artifact_patterns: task.artifacts.clone(),
+            pre_hook: task.hooks.pre.clone(),
+            post_hook: task.hooks.post.clone(),
+            status: SCHEDULED.to_string(),
+            exit_code: None,
+            output_truncated: 0,
+            output_log: None,
+            completed_at: None,
+        }
+    }
+}
+
+impl TaskRecord {
+    // Output of the status, its content is stored apart
+    pub(super) fn set_status<'a>(&mut self, status: &'a TaskStatus) -> Option<&'a TaskOutput> {
+        let (status, output, exit_code) = match status {
+            TaskStatus::Scheduled => (SCHEDULED, None, None),
+            TaskStatus::Success(ref stdout) => (SUCCESS, Some(stdout), Some(0)),
+            TaskStatus::Error(ref stderr, code) => (ERROR, Some(stderr), *code),
+            TaskStatus::Cancelled => (CANCELLED, None, None),
+        };
+        self.status = status.to_string();
+        self.exit_code = exit_code;
+        self.output_truncated = output.map_or(0, |output| output.truncated);
+        self.output_log = output.and_then(|output| output.log.clone());
+        output
+    }
+
+    // Task listed for the retention policy once it is completed
+    pub(super) fn completed(&self) -> Option<CompletedTask> {
+        let outcome = match self.status.as_str() {
+            SUCCESS => TaskOutcome::Success,
+            ERROR => TaskOutcome::Error,
+            CANCELLED => TaskOutcome::Cancelled,
+            _ => return None,
+        };
+        Some(CompletedTask { id: self.id.clone(), name: self.name.clone(), outcome, completed_at: self.completed_at })
+    }
+
+    pub(super) fn into_task(self, content: Vec<u8>, module: Option<Vec<u8>>) -> Result<(Task, TaskStatus), Error> {
+        let id = self.id;
+        let mounts = self.container_mounts;
+        let output = TaskOutput { content, truncated: self.output_truncated, log: self.output_log };
+        let status = match self.status.as_str() {
+            SCHEDULED => TaskStatus::Scheduled,
+            SUCCESS => TaskStatus::Success(output),
+            ERROR => TaskStatus::Error(output, self.exit_code),
+            CANCELLED => TaskStatus::Cancelled,
+            other => return Err(anyhow!("Unknown status {} of task {}", other, id)),
+        };
+        let wasm = match self.wasm {
+            Some(WasmRecord { path, dir, fuel }) => Some(WasmModule {
+                source: match (path, module) {
+                    (Some(path), _) => WasmSource::Path(path),
+                    (None, Some(module)) => WasmSource::Blob(module),
+                    (None, None) => return Err(anyhow!("Missing WASI module of task {}", id)),
+                },
+                dir,
+                fuel,
+            }),
+            None => None,
+        };
+        let task = Task {
+            id,
+            name: self.name,
+            command: self.command,
+            env: self.env,
+            timeout: self.timeout.map(Duration::from_secs),
+            labels: self.labels,
+            working_dir: self.working_dir,
+            stdin: self.stdin,
+            umask: self.umask,
+            limits: ResourceLimits {
+                cpu_time: self.cpu_time_limit.map(Duration::from_secs),
+                memory: self.memory_limit,
+                open_files: self.open_files_limit,
+                processes: self.processes_limit,
+            },
+            sandboxed: self.sandboxed,
+            credentials: self.credentials.map(|(uid, gid, groups)| Credentials { uid, gid, groups }),
+            container: self.container_rootfs.map(|rootfs| Container {
+                rootfs,
+                mounts: mounts.into_iter()
+                    .map(|(source, destination, read_only)| Mount { source, destination, read_only })
+                    .collect(),
+            }),
+            host: self.host,
+            wasm,
+            executor: self.executor,
+            artifacts: self.artifact_patterns,
+            hooks: TaskHooks { pre: self.pre_hook, post: self.post_hook },
+        };
+        Ok((task, status))
+    }
+}
+
+impl From<&TaskTemplate> for TemplateRecord {
+    fn from(template: &TaskTemplate) -> Self {
+        TemplateRecord {
+            name: template.name.clone(),
+            command: template.command.clone(),
+            env: template.env.clone(),
+            timeout: template.timeout.map(|timeout| timeout.as_secs()),
+        }
+    }
+}
+
+impl From<TemplateRecord> for TaskTemplate {
+    fn from(template: TemplateRecord) -> Self {
+        TaskTemplate {
+            name: template.name,
+            command: template.command,
+            env: template.env,
+            timeout: template.timeout.map(Duration::from_secs),
+        }
+    }
+}
+
+impl From<Artifact> for ArtifactRecord {
+    fn from(artifact: Artifact) -> Self {
+        ArtifactRecord { path: artifact.path, digest: artifact.digest, size: artifact.size }
+    }
+}
+
+impl From<ArtifactRecord> for Artifact {
+    fn from(artifact: ArtifactRecord) -> Self {
+        Artifact { path: artifact.path, digest: artifact.digest, size: artifact.size }
+    }
+}
+
+impl From<&HookFailure> for HookFailureRecord {
+    fn from(failure: &HookFailure) -> Self {
+        HookFailureRecord { stage: failure.stage.to_string(), message: failure.message.clone() }
+    }
+}
+
+impl TryFrom<HookFailureRecord> for HookFailure {
+    type Error = Error;
+
+    fn try_from(failure: HookFailureRecord) -> Result<Self, Self::Error> {
+        failure.stage.parse()
+            .map(|stage| HookFailure { stage, message: failure.message })
+            .map_err(Error::from)
+    }
+}
\ No newline at end of file
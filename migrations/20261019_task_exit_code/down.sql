@@ -0,0 +1 @@
+ALTER TABLE tasks DROP COLUMN exit_code
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN exit_code INTEGER
\ No newline at end of file
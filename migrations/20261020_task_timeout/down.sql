@@ -0,0 +1 @@
+ALTER TABLE tasks DROP COLUMN timeout
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN timeout BIGINT
\ No newline at end of file
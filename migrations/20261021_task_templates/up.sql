@@ -0,0 +1,6 @@
+CREATE TABLE templates (
+  name VARCHAR NOT NULL PRIMARY KEY,
+  command VARCHAR NOT NULL,
+  env VARCHAR,
+  timeout BIGINT
+)
\ No newline at end of file
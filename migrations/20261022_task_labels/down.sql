@@ -0,0 +1 @@
+ALTER TABLE tasks DROP COLUMN labels
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN labels VARCHAR
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE tasks DROP COLUMN umask;
+ALTER TABLE tasks DROP COLUMN stdin;
+ALTER TABLE tasks DROP COLUMN working_dir;
\ No newline at end of file
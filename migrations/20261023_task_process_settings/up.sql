@@ -0,0 +1,3 @@
+ALTER TABLE tasks ADD COLUMN working_dir VARCHAR;
+ALTER TABLE tasks ADD COLUMN stdin VARCHAR;
+ALTER TABLE tasks ADD COLUMN umask INTEGER;
\ No newline at end of file
@@ -0,0 +1,4 @@
+ALTER TABLE tasks DROP COLUMN processes_limit;
+ALTER TABLE tasks DROP COLUMN open_files_limit;
+ALTER TABLE tasks DROP COLUMN memory_limit;
+ALTER TABLE tasks DROP COLUMN cpu_time_limit;
\ No newline at end of file
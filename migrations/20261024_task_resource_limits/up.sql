@@ -0,0 +1,4 @@
+ALTER TABLE tasks ADD COLUMN cpu_time_limit BIGINT;
+ALTER TABLE tasks ADD COLUMN memory_limit BIGINT;
+ALTER TABLE tasks ADD COLUMN open_files_limit BIGINT;
+ALTER TABLE tasks ADD COLUMN processes_limit BIGINT;
\ No newline at end of file
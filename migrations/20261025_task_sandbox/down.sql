@@ -0,0 +1 @@
+ALTER TABLE tasks DROP COLUMN sandboxed;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN sandboxed BOOLEAN NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE tasks DROP COLUMN groups;
+ALTER TABLE tasks DROP COLUMN gid;
+ALTER TABLE tasks DROP COLUMN uid;
\ No newline at end of file
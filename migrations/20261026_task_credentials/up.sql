@@ -0,0 +1,3 @@
+ALTER TABLE tasks ADD COLUMN uid INTEGER;
+ALTER TABLE tasks ADD COLUMN gid INTEGER;
+ALTER TABLE tasks ADD COLUMN groups VARCHAR;
\ No newline at end of file
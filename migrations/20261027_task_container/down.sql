@@ -0,0 +1,2 @@
+ALTER TABLE tasks DROP COLUMN container_mounts;
+ALTER TABLE tasks DROP COLUMN container_rootfs;
\ No newline at end of file
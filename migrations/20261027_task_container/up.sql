@@ -0,0 +1,2 @@
+ALTER TABLE tasks ADD COLUMN container_rootfs VARCHAR;
+ALTER TABLE tasks ADD COLUMN container_mounts VARCHAR;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks DROP COLUMN host;
\ No newline at end of file
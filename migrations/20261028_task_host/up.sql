@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN host VARCHAR;
\ No newline at end of file
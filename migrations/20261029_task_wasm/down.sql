@@ -0,0 +1,4 @@
+ALTER TABLE tasks DROP COLUMN wasm_fuel;
+ALTER TABLE tasks DROP COLUMN wasm_dir;
+ALTER TABLE tasks DROP COLUMN wasm_blob;
+ALTER TABLE tasks DROP COLUMN wasm_path;
\ No newline at end of file
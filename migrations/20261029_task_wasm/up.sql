@@ -0,0 +1,4 @@
+ALTER TABLE tasks ADD COLUMN wasm_path VARCHAR;
+ALTER TABLE tasks ADD COLUMN wasm_blob BLOB;
+ALTER TABLE tasks ADD COLUMN wasm_dir VARCHAR;
+ALTER TABLE tasks ADD COLUMN wasm_fuel BIGINT;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks ADD COLUMN executor VARCHAR;
\ No newline at end of file
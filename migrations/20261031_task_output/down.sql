@@ -0,0 +1,3 @@
+ALTER TABLE tasks DROP COLUMN output_log;
+ALTER TABLE tasks DROP COLUMN output_truncated;
+UPDATE tasks SET status_log = CAST(status_log AS TEXT);
\ No newline at end of file
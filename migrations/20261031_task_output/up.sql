@@ -0,0 +1,3 @@
+UPDATE tasks SET status_log = CAST(status_log AS BLOB);
+ALTER TABLE tasks ADD COLUMN output_truncated BIGINT;
+ALTER TABLE tasks ADD COLUMN output_log VARCHAR;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Empty output can't be told apart from a missing one once stored as NULL
+SELECT 1;
\ No newline at end of file
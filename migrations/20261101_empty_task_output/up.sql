@@ -0,0 +1 @@
+UPDATE tasks SET status_log = NULL WHERE length(status_log) = 0;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DROP TABLE artifacts;
+ALTER TABLE tasks DROP COLUMN artifact_patterns;
\ No newline at end of file
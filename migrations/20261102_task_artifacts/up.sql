@@ -0,0 +1,8 @@
+ALTER TABLE tasks ADD COLUMN artifact_patterns VARCHAR;
+CREATE TABLE artifacts (
+  task_id VARCHAR NOT NULL,
+  path VARCHAR NOT NULL,
+  digest VARCHAR NOT NULL,
+  size BIGINT NOT NULL,
+  PRIMARY KEY (task_id, path)
+)
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP TABLE hook_failures;
+ALTER TABLE tasks DROP COLUMN post_hook;
+ALTER TABLE tasks DROP COLUMN pre_hook;
\ No newline at end of file
@@ -0,0 +1,8 @@
+ALTER TABLE tasks ADD COLUMN pre_hook VARCHAR;
+ALTER TABLE tasks ADD COLUMN post_hook VARCHAR;
+CREATE TABLE hook_failures (
+  task_id VARCHAR NOT NULL,
+  stage VARCHAR NOT NULL,
+  message VARCHAR NOT NULL,
+  PRIMARY KEY (task_id, stage)
+)
\ No newline at end of file
@@ -0,0 +1,4 @@
+DROP TRIGGER tasks_search_delete;
+DROP TRIGGER tasks_search_update;
+DROP TRIGGER tasks_search_insert;
+DROP TABLE tasks_search;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Words of the names, commands and outputs, the index reading the tasks by their rowid
+-- Words are split as SearchQuery splits them, diacritics included
+CREATE VIRTUAL TABLE tasks_search USING fts5(name, command, status_log, content = 'tasks', tokenize = 'unicode61 remove_diacritics 0');
+CREATE TRIGGER tasks_search_insert AFTER INSERT ON tasks
+BEGIN
+  INSERT INTO tasks_search (rowid, name, command, status_log) VALUES (new.rowid, new.name, new.command, new.status_log);
+END;
+CREATE TRIGGER tasks_search_update AFTER UPDATE OF name, command, status_log ON tasks
+BEGIN
+  INSERT INTO tasks_search (tasks_search, rowid, name, command, status_log) VALUES ('delete', old.rowid, old.name, old.command, old.status_log);
+  INSERT INTO tasks_search (rowid, name, command, status_log) VALUES (new.rowid, new.name, new.command, new.status_log);
+END;
+CREATE TRIGGER tasks_search_delete AFTER DELETE ON tasks
+BEGIN
+  INSERT INTO tasks_search (tasks_search, rowid, name, command, status_log) VALUES ('delete', old.rowid, old.name, old.command, old.status_log);
+END;
+INSERT INTO tasks_search (tasks_search) VALUES ('rebuild');
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Error of the event, set when the artifacts of a task could not be collected
+ALTER TABLE task_events ADD COLUMN message VARCHAR;
\ No newline at end of file
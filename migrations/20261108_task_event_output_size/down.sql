@@ -0,0 +1 @@
+ALTER TABLE task_events DROP COLUMN size;
\ No newline at end of file
@@ -0,0 +1,22 @@
+CREATE TABLE task_events (
+  sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+  task_id VARCHAR NOT NULL,
+  at BIGINT NOT NULL,
+  actor VARCHAR NOT NULL,
+  kind VARCHAR NOT NULL,
+  command VARCHAR,
+  output BLOB,
+  success BOOLEAN,
+  exit_code INTEGER,
+  truncated BIGINT,
+  log VARCHAR
+);
+CREATE INDEX task_events_task_id ON task_events (task_id);
+CREATE TRIGGER task_events_no_update BEFORE UPDATE ON task_events
+BEGIN
+  SELECT RAISE(ABORT, 'task events are immutable');
+END;
+CREATE TRIGGER task_events_no_delete BEFORE DELETE ON task_events
+BEGIN
+  SELECT RAISE(ABORT, 'task events are immutable');
+END;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Size of the output of the command, its content is only kept by the storage so it goes with the purged tasks
+ALTER TABLE task_events ADD COLUMN size BIGINT;
+DROP TRIGGER task_events_no_update;
+UPDATE task_events SET size = length(output), output = NULL WHERE kind = 'OUTPUT';
+CREATE TRIGGER task_events_no_update BEFORE UPDATE ON task_events
+BEGIN
+  SELECT RAISE(ABORT, 'task events are immutable');
+END;
\ No newline at end of file
@@ -0,0 +1,4 @@
+DROP TABLE hook_failures;
+DROP TABLE artifacts;
+DROP TABLE templates;
+DROP TABLE tasks;
\ No newline at end of file
@@ -0,0 +1,55 @@
+CREATE TABLE tasks (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  name VARCHAR,
+  command VARCHAR NOT NULL,
+  env VARCHAR,
+  status VARCHAR NOT NULL,
+  status_log BYTEA,
+  exit_code INTEGER,
+  timeout BIGINT,
+  labels VARCHAR,
+  working_dir VARCHAR,
+  stdin VARCHAR,
+  umask INTEGER,
+  cpu_time_limit BIGINT,
+  memory_limit BIGINT,
+  open_files_limit BIGINT,
+  processes_limit BIGINT,
+  sandboxed BOOLEAN NOT NULL DEFAULT FALSE,
+  uid INTEGER,
+  gid INTEGER,
+  groups VARCHAR,
+  container_rootfs VARCHAR,
+  container_mounts VARCHAR,
+  host VARCHAR,
+  wasm_path VARCHAR,
+  wasm_blob BYTEA,
+  wasm_dir VARCHAR,
+  wasm_fuel BIGINT,
+  executor VARCHAR,
+  output_truncated BIGINT,
+  output_log VARCHAR,
+  artifact_patterns VARCHAR,
+  pre_hook VARCHAR,
+  post_hook VARCHAR
+);
+CREATE INDEX tasks_name ON tasks (name);
+CREATE TABLE templates (
+  name VARCHAR NOT NULL PRIMARY KEY,
+  command VARCHAR NOT NULL,
+  env VARCHAR,
+  timeout BIGINT
+);
+CREATE TABLE artifacts (
+  task_id VARCHAR NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+  path VARCHAR NOT NULL,
+  digest VARCHAR NOT NULL,
+  size BIGINT NOT NULL,
+  PRIMARY KEY (task_id, path)
+);
+CREATE TABLE hook_failures (
+  task_id VARCHAR NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+  stage VARCHAR NOT NULL,
+  message VARCHAR NOT NULL,
+  PRIMARY KEY (task_id, stage)
+)
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE tasks DROP COLUMN saved;
\ No newline at end of file
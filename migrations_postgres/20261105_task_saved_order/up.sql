@@ -0,0 +1,2 @@
+-- Order of insertion of the tasks, a name resolves to the last task saved with it
+ALTER TABLE tasks ADD COLUMN saved BIGSERIAL;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Milliseconds since the epoch, NULL for the tasks completed before it was recorded
+ALTER TABLE tasks ADD COLUMN completed_at BIGINT;
\ No newline at end of file
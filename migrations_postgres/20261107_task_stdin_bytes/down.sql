@@ -0,0 +1 @@
+ALTER TABLE tasks ALTER COLUMN stdin TYPE VARCHAR USING convert_from(stdin, 'UTF8');
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- Payload of the standard input stored as bytes, it may not be UTF-8
+ALTER TABLE tasks ALTER COLUMN stdin TYPE BYTEA USING convert_to(stdin, 'UTF8');
\ No newline at end of file
@@ -0,0 +1,2 @@
+DROP TABLE task_events;
+DROP FUNCTION task_events_immutable();
\ No newline at end of file
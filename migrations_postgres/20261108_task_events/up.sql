@@ -0,0 +1,23 @@
+-- Event log of the tasks, its rows can only be inserted
+CREATE TABLE task_events (
+  sequence BIGSERIAL PRIMARY KEY,
+  task_id VARCHAR NOT NULL,
+  at BIGINT NOT NULL,
+  actor VARCHAR NOT NULL,
+  kind VARCHAR NOT NULL,
+  command VARCHAR,
+  size BIGINT,
+  success BOOLEAN,
+  exit_code INTEGER,
+  truncated BIGINT,
+  log VARCHAR,
+  message VARCHAR
+);
+CREATE INDEX task_events_task_id ON task_events (task_id);
+CREATE FUNCTION task_events_immutable() RETURNS TRIGGER AS $$
+BEGIN
+  RAISE EXCEPTION 'task events are immutable';
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER task_events_no_update BEFORE UPDATE OR DELETE ON task_events FOR EACH ROW EXECUTE FUNCTION task_events_immutable();
+CREATE TRIGGER task_events_no_truncate BEFORE TRUNCATE ON task_events FOR EACH STATEMENT EXECUTE FUNCTION task_events_immutable();
\ No newline at end of file